@@ -0,0 +1,218 @@
+use crate::{Error, Result};
+
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// Service name the tokens are stored under in the keyring
+const SERVICE: &str = "pac";
+
+/// Where a token was found
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Keyring,
+    /// Name of the environment variable
+    Env(String),
+}
+
+lazy_static! {
+    /// Keyring lookups run a command, do them once per host
+    static ref TOKENS: Mutex<HashMap<String, Option<(String, Source)>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Host part of a remote url like `https://user@github.com/owner/repo`
+pub fn host(url: &str) -> Option<&str> {
+    let rest = url.split("://").nth(1)?;
+    let authority = rest.split('/').next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    Some(host).filter(|h| !h.is_empty())
+}
+
+/// Tokens of a host are shared with the hosts serving its raw files and api
+pub fn canonical_host(host: &str) -> &str {
+    match host {
+        "raw.githubusercontent.com" | "api.github.com" | "codeload.github.com" => "github.com",
+        _ => host,
+    }
+}
+
+/// `$PAC_TOKEN_<HOST>`, like `PAC_TOKEN_GITHUB_COM`, then the variable the
+/// host's own tools read
+pub fn env_vars(host: &str) -> Vec<String> {
+    let own: String = host
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect();
+    let mut vars = vec![format!("PAC_TOKEN_{}", own)];
+    match host {
+        "github.com" => vars.push("GITHUB_TOKEN".to_string()),
+        "gitlab.com" => vars.push("GITLAB_TOKEN".to_string()),
+        _ => (),
+    }
+    vars
+}
+
+/// Token for `host` from the keyring, else from the environment
+pub fn token(host: &str) -> Option<(String, Source)> {
+    let host = canonical_host(host);
+    let mut tokens = TOKENS.lock().unwrap();
+    tokens
+        .entry(host.to_string())
+        .or_insert_with(|| {
+            if let Some(t) = keyring::get(host) {
+                return Some((t, Source::Keyring));
+            }
+            env_vars(host).into_iter().find_map(|var| {
+                let t = env::var(&var).ok().filter(|t| !t.is_empty())?;
+                Some((t, Source::Env(var)))
+            })
+        })
+        .clone()
+}
+
+/// User name sent along with the token over https
+pub fn token_user(host: &str) -> &'static str {
+    if host.contains("gitlab") {
+        "oauth2"
+    } else {
+        "x-access-token"
+    }
+}
+
+pub fn login(host: &str, token: &str) -> Result<()> {
+    let host = canonical_host(host);
+    keyring::set(host, token)?;
+    TOKENS.lock().unwrap().remove(host);
+    Ok(())
+}
+
+pub fn logout(host: &str) -> Result<()> {
+    let host = canonical_host(host);
+    keyring::delete(host)?;
+    TOKENS.lock().unwrap().remove(host);
+    Ok(())
+}
+
+/// Run `cmd`, writing `input` to its stdin, and return its stdout
+fn run(cmd: &mut Command, input: Option<&str>) -> Result<String> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            let tool = cmd.get_program().to_string_lossy();
+            Error::auth(format!("can not run `{}`: {}", tool, e))
+        })?;
+    if let Some(input) = input {
+        child.stdin.take().unwrap().write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::auth(stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string())
+}
+
+/// The keychain through the `security` tool
+#[cfg(target_os = "macos")]
+mod keyring {
+    use super::{run, SERVICE};
+    use crate::{Error, Result};
+    use std::process::Command;
+
+    pub fn get(host: &str) -> Option<String> {
+        let mut cmd = Command::new("security");
+        cmd.args(["find-generic-password", "-s", SERVICE, "-a", host, "-w"]);
+        run(&mut cmd, None).ok()
+    }
+
+    pub fn set(host: &str, token: &str) -> Result<()> {
+        // `-w <token>` would show in the process list, so the command is
+        // given on stdin to `security -i`, which reads it as a quoted line
+        let quotable = |s: &str| !s.contains(['"', '\\']) && !s.contains(char::is_control);
+        if !quotable(token) || !quotable(host) {
+            return Err(Error::auth(
+                "the keychain takes no token with quotes, backslashes or control \
+                 characters, set $PAC_TOKEN_<HOST> instead",
+            ));
+        }
+        let line = format!(
+            "add-generic-password -U -s \"{}\" -a \"{}\" -w \"{}\"\n",
+            SERVICE, host, token
+        );
+        let mut cmd = Command::new("security");
+        cmd.arg("-i");
+        run(&mut cmd, Some(&line))?;
+        // `security -i` exits fine when a command it read fails
+        match get(host) {
+            Some(ref t) if t == token => Ok(()),
+            _ => Err(Error::auth("the keychain did not store the token")),
+        }
+    }
+
+    pub fn delete(host: &str) -> Result<()> {
+        let mut cmd = Command::new("security");
+        cmd.args(["delete-generic-password", "-s", SERVICE, "-a", host]);
+        run(&mut cmd, None).map(|_| ())
+    }
+}
+
+/// The Secret Service (gnome-keyring, KWallet) through `secret-tool`
+#[cfg(not(target_os = "macos"))]
+mod keyring {
+    use super::{run, SERVICE};
+    use crate::Result;
+    use std::process::Command;
+
+    pub fn get(host: &str) -> Option<String> {
+        let mut cmd = Command::new("secret-tool");
+        cmd.args(["lookup", "service", SERVICE, "host", host]);
+        run(&mut cmd, None).ok().filter(|t| !t.is_empty())
+    }
+
+    pub fn set(host: &str, token: &str) -> Result<()> {
+        let label = format!("pac token for {}", host);
+        let mut cmd = Command::new("secret-tool");
+        cmd.args(["store", "--label", &label, "service", SERVICE, "host", host]);
+        // read from stdin, so the token doesn't show up in the process list
+        run(&mut cmd, Some(token)).map(|_| ())
+    }
+
+    pub fn delete(host: &str) -> Result<()> {
+        let mut cmd = Command::new("secret-tool");
+        cmd.args(["clear", "service", SERVICE, "host", host]);
+        run(&mut cmd, None).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auth_host() {
+        assert_eq!(
+            host("https://github.com/tpope/vim-fugitive"),
+            Some("github.com")
+        );
+        assert_eq!(host("https://me@gitlab.com:443/a/b"), Some("gitlab.com"));
+        assert_eq!(host("file:///tmp/repo"), None);
+        assert_eq!(canonical_host("raw.githubusercontent.com"), "github.com");
+        assert_eq!(
+            env_vars("git.example.org"),
+            vec!["PAC_TOKEN_GIT_EXAMPLE_ORG".to_string()]
+        );
+    }
+}
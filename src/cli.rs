@@ -6,6 +6,72 @@ pub fn build_cli() -> App<'static, 'static> {
         .author(clap::crate_authors!())
         .version(clap::crate_version!())
         .setting(AppSettings::ColoredHelp)
+        .arg(
+            Arg::with_name("root")
+                .long("root")
+                .global(true)
+                .help("Vim config directory to manage packages in")
+                .value_name("DIR"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .global(true)
+                .help("Use this paconfig.yaml instead of <root>/.pac/paconfig.yaml")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("notify")
+                .long("notify")
+                .global(true)
+                .help("Send a desktop notification with the outcome of the run"),
+        )
+        .arg(
+            Arg::with_name("yes")
+                .long("yes")
+                .short("y")
+                .global(true)
+                .conflicts_with("confirm")
+                .help("Go ahead with destructive steps, like removing orphans, without asking"),
+        )
+        .arg(
+            Arg::with_name("confirm")
+                .long("confirm")
+                .global(true)
+                .help("Ask before discarding local changes or removing packages"),
+        )
+        .arg(Arg::with_name("dry-run").long("dry-run").global(true).help(
+            "Only print what would be cloned, pulled, built or removed, for install, \
+                     update, uninstall and import",
+        ))
+        .arg(
+            Arg::with_name("retries")
+                .long("retries")
+                .global(true)
+                .help(
+                    "Times install, update and sync try a clone or pull again after a git or \
+                     network error, waiting longer each time (default 2)",
+                )
+                .value_name("N"),
+        )
+        .arg(
+            Arg::with_name("ui")
+                .long("ui")
+                .global(true)
+                .possible_values(&["tty", "simple"])
+                .help(
+                    "Progress display, simple prints one line per change (default with TERM=dumb)",
+                )
+                .value_name("UI"),
+        )
+        .arg(
+            Arg::with_name("warnings")
+                .long("warnings")
+                .global(true)
+                .possible_values(&["text", "json"])
+                .help("How warnings collected during the run are printed at its end, on stderr")
+                .value_name("FORMAT"),
+        )
         .subcommand(
             SubCommand::with_name("list")
                 .about("List installed packages")
@@ -29,17 +95,49 @@ pub fn build_cli() -> App<'static, 'static> {
                         .short("d")
                         .help("List detached(untracked) packages"),
                 )
+                .arg(
+                    Arg::with_name("tree")
+                        .long("tree")
+                        .short("t")
+                        .conflicts_with("detached")
+                        .help("Show packages by category with their dependencies"),
+                )
+                .arg(
+                    Arg::with_name("porcelain")
+                        .long("porcelain")
+                        .conflicts_with_all(&["detached", "tree"])
+                        .takes_value(true)
+                        .min_values(0)
+                        .require_equals(true)
+                        .possible_values(&["v1"])
+                        .help("Tab separated fields in a stable format for scripts, v1 by default")
+                        .value_name("VERSION"),
+                )
                 .arg(
                     Arg::with_name("category")
                         .long("category")
                         .short("c")
                         .help("List packages under this category")
                         .value_name("CATEGORY"),
+                )
+                .arg(
+                    Arg::with_name("query")
+                        .help("Only list packages fuzzy matching this, best match first"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Show details of the package best matching a query")
+                .arg(
+                    Arg::with_name("query")
+                        .required(true)
+                        .help("Fuzzy matched against idname, name and description"),
                 ),
         )
         .subcommand(
             SubCommand::with_name("install")
                 .about("Install new packages/plugins")
+                .arg(progress_format())
                 .arg(
                     Arg::with_name("opt")
                         .short("o")
@@ -50,8 +148,9 @@ pub fn build_cli() -> App<'static, 'static> {
                     Arg::with_name("category")
                         .long("category")
                         .short("c")
-                        .help("Install package under provided category")
-                        .default_value("default")
+                        .help(
+                            "Install package under provided category, default_category by default",
+                        )
                         .value_name("CATEGORY"),
                 )
                 .arg(
@@ -59,7 +158,15 @@ pub fn build_cli() -> App<'static, 'static> {
                         .long("rev")
                         .help("Checkout the branch, tag, or commit specified")
                         .value_name("REVISION")
-                        .visible_aliases(&["branch", "tag", "commit"])
+                        .visible_aliases(&["branch", "tag", "commit"]),
+                )
+                .arg(
+                    Arg::with_name("channel")
+                        .long("channel")
+                        .possible_values(&["stable", "nightly"])
+                        .conflicts_with("rev")
+                        .help("Follow the newest release tag or the default branch (default)")
+                        .value_name("CHANNEL"),
                 )
                 .arg(
                     Arg::with_name("as")
@@ -67,10 +174,22 @@ pub fn build_cli() -> App<'static, 'static> {
                         .help("Install plugin under this name")
                         .value_name("NAME"),
                 )
+                .arg(
+                    Arg::with_name("history-since")
+                        .long("history-since")
+                        .help("Only clone the history after this date, like `1 year` (needs git)")
+                        .value_name("DATE"),
+                )
+                .arg(
+                    Arg::with_name("fork")
+                        .long("fork")
+                        .help("Clone the fork of this user, fetching the original as `upstream`")
+                        .value_name("USER"),
+                )
                 .arg(
                     Arg::with_name("on")
                         .long("on")
-                        .help("Command for loading the plugins")
+                        .help("Command(s) for loading the plugins, comma separated")
                         .value_name("LOAD_CMD"),
                 )
                 .arg(
@@ -82,9 +201,19 @@ pub fn build_cli() -> App<'static, 'static> {
                 .arg(
                     Arg::with_name("build")
                         .long("build")
-                        .help("Build command for build package")
+                        .help("Build command for build package, repeat to run several in order")
+                        .multiple(true)
+                        .number_of_values(1)
                         .value_name("BUILD_CMD"),
                 )
+                .arg(
+                    Arg::with_name("auto-build")
+                        .long("auto-build")
+                        .conflicts_with("build")
+                        .help(
+                            "Run and keep the build command suggested for new plugins without one",
+                        ),
+                )
                 .arg(
                     Arg::with_name("threads")
                         .short("j")
@@ -92,13 +221,140 @@ pub fn build_cli() -> App<'static, 'static> {
                         .help("Installing packages concurrently")
                         .value_name("THREADS"),
                 )
+                .arg(
+                    Arg::with_name("build-threads")
+                        .long("build-threads")
+                        .help("Running build commands concurrently, once every package is synced")
+                        .value_name("THREADS"),
+                )
+                .arg(
+                    Arg::with_name("verify-load")
+                        .long("verify-load")
+                        .help("Load the new plugins in a headless editor and report their errors"),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Clone the plugins which are installed already again, after asking"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .help(
+                            "Install the plugins listed in this file, `-` for stdin, one per line \
+                             with its options",
+                        )
+                        .value_name("FILE"),
+                )
                 .arg(Arg::with_name("package").multiple(true)),
         )
+        .subcommand(
+            SubCommand::with_name("sync")
+                .about("Install what paconfig lists and remove what it does not")
+                .arg(progress_format())
+                .arg(
+                    Arg::with_name("no-ui")
+                        .long("no-ui")
+                        .help("Plain output, one line per change, like --ui simple"),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .short("j")
+                        .long("threads")
+                        .help("Installing packages concurrently")
+                        .value_name("THREADS"),
+                )
+                .arg(
+                    Arg::with_name("build-threads")
+                        .long("build-threads")
+                        .help("Running build commands concurrently, once every package is synced")
+                        .value_name("THREADS"),
+                )
+                .arg(
+                    Arg::with_name("locked")
+                        .long("locked")
+                        .help("Check out the commits of pac-lock.yaml, written by `pac lock`"),
+                )
+                .arg(
+                    Arg::with_name("prune")
+                        .long("prune")
+                        .requires("locked")
+                        .help("Remove the packages the lockfile does not list from paconfig too"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lock")
+                .about("Write the checked out commit of every package to pac-lock.yaml"),
+        )
+        .subcommand(
+            SubCommand::with_name("try")
+                .about("Open the editor with a plugin installed in a throwaway directory")
+                .arg(
+                    Arg::with_name("with-config")
+                        .long("with-config")
+                        .short("c")
+                        .help("Load your vimrc and packages as well"),
+                )
+                .arg(
+                    Arg::with_name("keep")
+                        .long("keep")
+                        .help("Don't remove the directory when the editor exits"),
+                )
+                .arg(
+                    Arg::with_name("editor")
+                        .long("editor")
+                        .help("Editor to run, vim or nvim like for packpath detection by default")
+                        .value_name("EDITOR"),
+                )
+                .arg(
+                    Arg::with_name("package")
+                        .help("Plugin to try, as for install")
+                        .required(true),
+                )
+                .arg(Arg::with_name("file").help("Files to open").multiple(true)),
+        )
         .subcommand(
             SubCommand::with_name("uninstall")
                 .about("Uninstall packages/plugins")
                 .arg(Arg::with_name("package").required(true).multiple(true)),
         )
+        .subcommand(
+            SubCommand::with_name("reinstall")
+                .about("Remove and freshly clone packages at their rev, keeping paconfig as it is")
+                .arg(progress_format())
+                .arg(
+                    Arg::with_name("threads")
+                        .short("j")
+                        .long("threads")
+                        .help("Installing packages concurrently")
+                        .value_name("THREADS"),
+                )
+                .arg(
+                    Arg::with_name("build-threads")
+                        .long("build-threads")
+                        .help("Running build commands concurrently, once every package is synced")
+                        .value_name("THREADS"),
+                )
+                .arg(Arg::with_name("package").required(true).multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("test")
+                .about("Run the vader, themis or busted tests of packages headless")
+                .arg(progress_format())
+                .arg(
+                    Arg::with_name("threads")
+                        .short("j")
+                        .long("threads")
+                        .help("Test suites running concurrently")
+                        .value_name("THREADS"),
+                )
+                .arg(
+                    Arg::with_name("package")
+                        .required(true)
+                        .multiple(true)
+                        .help("Idname or name of the package"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("move")
                 .about("Move a package to a different category or make it optional.")
@@ -120,9 +376,60 @@ pub fn build_cli() -> App<'static, 'static> {
                         .help("Category to move the package to"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("category")
+                .about("List categories, rename or merge them moving their packages")
+                .subcommand(
+                    SubCommand::with_name("rename")
+                        .about("Rename a category, which must not exist yet")
+                        .arg(
+                            Arg::with_name("from")
+                                .required(true)
+                                .help("Category to rename"),
+                        )
+                        .arg(Arg::with_name("to").required(true).help("New name")),
+                )
+                .subcommand(
+                    SubCommand::with_name("merge")
+                        .about("Move the packages of a category into another one")
+                        .arg(
+                            Arg::with_name("from")
+                                .required(true)
+                                .help("Category to empty"),
+                        )
+                        .arg(
+                            Arg::with_name("into")
+                                .required(true)
+                                .help("Category to move its packages to"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sets")
+                .about("List the package sets of paconfig and whether they are enabled"),
+        )
+        .subcommand(
+            SubCommand::with_name("enable-set")
+                .about("Load the packages of a set again")
+                .arg(
+                    Arg::with_name("set")
+                        .required(true)
+                        .help("Name under `sets`"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("disable-set")
+                .about("Keep the packages of a set installed under opt/ without loading them")
+                .arg(
+                    Arg::with_name("set")
+                        .required(true)
+                        .help("Name under `sets`"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("update")
                 .about("Update packages")
+                .arg(progress_format())
                 .arg(
                     Arg::with_name("skip")
                         .short("s")
@@ -136,13 +443,333 @@ pub fn build_cli() -> App<'static, 'static> {
                         .long("threads")
                         .help("Updating packages concurrently"),
                 )
+                .arg(
+                    Arg::with_name("build-threads")
+                        .long("build-threads")
+                        .help("Running build commands concurrently, once every package is synced")
+                        .value_name("THREADS"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .conflicts_with("skip")
+                        .help(
+                            "Check out this branch, tag or commit and save it as the package's rev",
+                        )
+                        .value_name("REVISION"),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .possible_values(&["origin", "upstream"])
+                        .conflicts_with("to")
+                        .help("Pull forks from the repos they were forked from with `upstream`")
+                        .value_name("REMOTE"),
+                )
                 .arg(
                     Arg::with_name("package")
                         .help("Packages to update, default all")
                         .multiple(true),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("outdated")
+                .about("List packages with updates on their remote")
+                .arg(progress_format())
+                .arg(
+                    Arg::with_name("threads")
+                        .short("j")
+                        .long("threads")
+                        .help("Checking packages concurrently")
+                        .value_name("THREADS"),
+                )
+                .arg(
+                    Arg::with_name("package")
+                        .help("Packages to check, default all")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stale")
+                .about("List packages by the date of their last upstream commit")
+                .arg(
+                    Arg::with_name("threads")
+                        .short("j")
+                        .long("threads")
+                        .help("Checking packages concurrently")
+                        .value_name("THREADS"),
+                )
+                .arg(
+                    Arg::with_name("package")
+                        .help("Packages to check, default all")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("schedule")
+                .about("Install a user timer (systemd or launchd) checking for updates")
+                .arg(
+                    Arg::with_name("daily")
+                        .long("daily")
+                        .conflicts_with("weekly")
+                        .help("Run every day"),
+                )
+                .arg(
+                    Arg::with_name("weekly")
+                        .long("weekly")
+                        .help("Run every week (default)"),
+                )
+                .arg(
+                    Arg::with_name("update")
+                        .long("update")
+                        .help("Run `pac update` instead of `pac outdated`"),
+                )
+                .arg(
+                    Arg::with_name("print")
+                        .long("print")
+                        .help("Print the units instead of installing them"),
+                )
+                .arg(
+                    Arg::with_name("remove")
+                        .long("remove")
+                        .conflicts_with_all(&["daily", "weekly", "update", "print"])
+                        .help("Disable and remove the timer"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Show where installs and updates spent their time, and how often they ran")
+                .arg(
+                    Arg::with_name("runs")
+                        .long("runs")
+                        .short("n")
+                        .help("Only look at this many of the latest runs")
+                        .value_name("N"),
+                )
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .help("Rows per table, 10 by default")
+                        .value_name("N"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("why")
+                .about("Explain why a package is installed")
+                .arg(
+                    Arg::with_name("package")
+                        .required(true)
+                        .help("Idname or name of the package"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("env")
+                .about("Print the directories, editor, settings and policy pac resolved")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .short("f")
+                        .possible_values(&["sh", "json"])
+                        .help("Output format, shell exports by default")
+                        .value_name("FORMAT"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("vendor")
+                .about("Copy the installed packages without git history into a directory")
+                .arg(
+                    Arg::with_name("into")
+                        .long("into")
+                        .required(true)
+                        .help("Directory laid out like pack/, eg. ~/dotfiles/vim/pack")
+                        .value_name("DIR"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("licenses")
+                .about("Report the license and origin of every installed package")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .short("f")
+                        .possible_values(&["text", "json", "spdx"])
+                        .help("Output format, text by default, spdx is a tag-value document")
+                        .value_name("FORMAT"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Print a document listing the plugins and how they are loaded")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .short("f")
+                        .possible_values(&["markdown", "html"])
+                        .help("Output format, markdown by default")
+                        .value_name("FORMAT"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Print the packages in the format of another tool")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .short("f")
+                        .required(true)
+                        .possible_values(&["home-manager", "lazy"])
+                        .help(
+                            "home-manager prints a module, lazy a lazy.nvim spec, both pinned \
+                             to the installed commits",
+                        )
+                        .value_name("FORMAT"),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .long("target")
+                        .possible_values(&["vim", "neovim"])
+                        .help("Editor to set the plugins of, neovim by default")
+                        .value_name("EDITOR"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Add the plugins of a packer.nvim or lazy.nvim config to paconfig")
+                .arg(
+                    Arg::with_name("category")
+                        .long("category")
+                        .short("c")
+                        .help("Add packages under provided category, default_category by default")
+                        .value_name("CATEGORY"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .help("Lua file with the plugin specs, eg. lua/plugins.lua"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Print the packages and their dependencies as a graph")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .short("f")
+                        .possible_values(&["dot", "json"])
+                        .help("Output format, dot by default")
+                        .value_name("FORMAT"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("exec")
+                .about("Run a command in the directory of every package")
+                .arg(
+                    Arg::with_name("category")
+                        .long("category")
+                        .short("c")
+                        .help("Only run in packages under this category")
+                        .value_name("CATEGORY"),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .short("j")
+                        .long("threads")
+                        .help("Running commands concurrently")
+                        .value_name("THREADS"),
+                )
+                .arg(
+                    Arg::with_name("package")
+                        .help("Packages to run the command in, default all")
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("command")
+                        .help("Command to run, given after --")
+                        .required(true)
+                        .multiple(true)
+                        .last(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("registry")
+                .about("List or refresh the index of short plugin names")
+                .subcommand(
+                    SubCommand::with_name("update")
+                        .about("Refetch the registry index")
+                        .arg(
+                            Arg::with_name("url")
+                                .long("url")
+                                .help("Fetch the index from this url or path instead")
+                                .value_name("URL")
+                                .multiple(true)
+                                .number_of_values(1),
+                        ),
+                ),
+        )
         // TODO: remove generate subcommand (package config no longer managed by pac)
+        .subcommand(
+            SubCommand::with_name("auth")
+                .about("Show or manage the tokens used for private repositories")
+                .subcommand(
+                    SubCommand::with_name("login")
+                        .about("Save a token in the system keyring")
+                        .arg(
+                            Arg::with_name("token")
+                                .long("token")
+                                .help("The token, read from stdin if not given")
+                                .value_name("TOKEN"),
+                        )
+                        .arg(Arg::with_name("host").help("Git host, github.com by default")),
+                )
+                .subcommand(
+                    SubCommand::with_name("logout")
+                        .about("Remove a token from the system keyring")
+                        .arg(Arg::with_name("host").help("Git host, github.com by default")),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sync-config")
+                .about("Share the config directory between machines through a git remote")
+                .subcommand(
+                    SubCommand::with_name("push")
+                        .about("Commit the config directory and push it")
+                        .arg(
+                            Arg::with_name("remote")
+                                .long("remote")
+                                .help("Git remote to push to, remembered for later runs")
+                                .value_name("URL"),
+                        )
+                        .arg(
+                            Arg::with_name("message")
+                                .long("message")
+                                .short("m")
+                                .help("Commit message")
+                                .value_name("MSG"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("pull")
+                        .about("Pull the config directory and install its packages")
+                        .arg(
+                            Arg::with_name("remote")
+                                .long("remote")
+                                .help("Git remote to pull from, remembered for later runs")
+                                .value_name("URL"),
+                        )
+                        .arg(
+                            Arg::with_name("no-sync")
+                                .long("no-sync")
+                                .help("Only pull, don't install the packages"),
+                        )
+                        .arg(
+                            Arg::with_name("threads")
+                                .short("j")
+                                .long("threads")
+                                .help("Installing packages concurrently")
+                                .value_name("THREADS"),
+                        ),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("generate")
                 .about("Generate the pack package file")
@@ -160,3 +787,12 @@ pub fn build_cli() -> App<'static, 'static> {
                 ),
         )
 }
+
+/// `--format jsonl` of the commands running tasks
+fn progress_format() -> Arg<'static, 'static> {
+    Arg::with_name("format")
+        .long("format")
+        .possible_values(&["text", "jsonl"])
+        .help("Progress output, jsonl prints a JSON object per change of a task's state")
+        .value_name("FORMAT")
+}
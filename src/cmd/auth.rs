@@ -0,0 +1,126 @@
+use crate::auth::{self, Source};
+use crate::context::Context;
+use crate::package;
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+#[derive(Debug)]
+struct AuthArgs {
+    host: String,
+    token: Option<String>,
+}
+
+impl AuthArgs {
+    fn from_matches(m: &ArgMatches) -> AuthArgs {
+        AuthArgs {
+            host: value_t!(m, "host", String).unwrap_or_else(|_| "github.com".to_string()),
+            token: value_t!(m, "token", String).ok(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let res = match matches.subcommand() {
+        ("login", Some(m)) => login(AuthArgs::from_matches(m)),
+        ("logout", Some(m)) => logout(AuthArgs::from_matches(m)),
+        _ => status(ctx),
+    };
+    if let Err(e) = res {
+        die!("Err: {}", e);
+    }
+}
+
+fn login(args: AuthArgs) -> Result<()> {
+    let token = match args.token {
+        Some(t) => t,
+        None => read_token(&args.host)?,
+    };
+    let token = token.trim();
+    if token.is_empty() {
+        die!("No token given");
+    }
+    auth::login(&args.host, token)?;
+    println!("Token for {} saved in the keyring", args.host);
+    Ok(())
+}
+
+fn logout(args: AuthArgs) -> Result<()> {
+    auth::logout(&args.host)?;
+    println!("Token for {} removed from the keyring", args.host);
+    Ok(())
+}
+
+/// Where the token of every host with tracked packages comes from
+fn status(ctx: &Context) -> Result<()> {
+    let mut hosts = vec!["github.com".to_string(), "gitlab.com".to_string()];
+    for pack in package::fetch(ctx)? {
+        if let Some(h) = auth::host(&pack.remote) {
+            if !hosts.iter().any(|e| e == h) {
+                hosts.push(h.to_string());
+            }
+        }
+    }
+    for host in hosts {
+        match auth::token(&host) {
+            Some((_, Source::Keyring)) => println!("{}: keyring", host),
+            Some((_, Source::Env(var))) => println!("{}: ${}", host, var),
+            None => println!("{}: no token ({})", host, auth::env_vars(&host).join(", ")),
+        }
+    }
+    Ok(())
+}
+
+/// Read the token from stdin, without echoing it when that's a terminal
+fn read_token(host: &str) -> Result<String> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        let mut line = String::new();
+        stdin.lock().read_line(&mut line)?;
+        return Ok(line);
+    }
+
+    eprint!("Token for {}: ", host);
+    io::stderr().flush()?;
+    terminal::enable_raw_mode()?;
+    let mut token = String::new();
+    let res = loop {
+        let key = match event::read() {
+            Ok(Event::Key(k)) if k.kind != KeyEventKind::Release => k,
+            Ok(_) => continue,
+            Err(e) => break Err(e),
+        };
+        match key {
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => {
+                token.clear();
+                break Ok(());
+            }
+            KeyEvent {
+                code: KeyCode::Char(c),
+                ..
+            } => token.push(c),
+            KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            } => {
+                token.pop();
+            }
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } => break Ok(()),
+            _ => (),
+        }
+    };
+    terminal::disable_raw_mode()?;
+    eprintln!();
+    res?;
+    Ok(token)
+}
@@ -0,0 +1,125 @@
+use crate::cmd::move_cmd;
+use crate::context::Context;
+use crate::package::{self, Package};
+use crate::{Error, Result};
+
+use clap::{value_t, ArgMatches};
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let arg = |m: &ArgMatches, name: &str| value_t!(m, name, String).unwrap();
+    let res = match matches.subcommand() {
+        ("rename", Some(m)) => move_category(ctx, &arg(m, "from"), &arg(m, "to"), false),
+        ("merge", Some(m)) => move_category(ctx, &arg(m, "from"), &arg(m, "into"), true),
+        _ => list(ctx),
+    };
+    if let Err(e) = res {
+        die!("Err: {}", e);
+    }
+}
+
+/// Categories with how many packages are in them, by name
+fn counts(packs: &[Package]) -> Vec<(&str, usize, usize)> {
+    let mut counts: Vec<(&str, usize, usize)> = vec![];
+    for pack in packs {
+        let opt = usize::from(pack.opt);
+        match counts.iter_mut().find(|(c, _, _)| *c == pack.category) {
+            Some((_, n, o)) => {
+                *n += 1;
+                *o += opt;
+            }
+            None => counts.push((&pack.category, 1, opt)),
+        }
+    }
+    counts.sort();
+    counts
+}
+
+fn list(ctx: &Context) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    let counts = counts(&packs);
+    let width = counts.iter().map(|(c, _, _)| c.len()).max().unwrap_or(0);
+    for (category, n, opt) in counts {
+        let plural = if n == 1 { "" } else { "s" };
+        println!(
+            "{:<width$} {} package{}, {} opt",
+            category,
+            n,
+            plural,
+            opt,
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// Move every package of `from` to `to`. A rename refuses to mix packages
+/// into a category that exists, a merge only refuses when two packages
+/// would end up at the same path. Nothing moves if either is the case.
+fn move_category(ctx: &Context, from: &str, to: &str, merge: bool) -> Result<()> {
+    package::validate_category(to)?;
+    let mut packs = package::fetch(ctx)?;
+    if from == to {
+        return Err(Error::category(format!("{} is moved to itself", from)));
+    }
+    if !packs.iter().any(|p| p.category == from) {
+        return Err(Error::category(format!("no packages in {}", from)));
+    }
+    if !merge && packs.iter().any(|p| p.category == to) {
+        return Err(Error::category(format!(
+            "{} exists, use `pac category merge {} {}`",
+            to, from, to
+        )));
+    }
+    let clashes: Vec<String> = packs
+        .iter()
+        .filter(|p| p.category == from)
+        .filter(|p| {
+            packs
+                .iter()
+                .any(|o| o.category == to && o.name == p.name && o.opt == p.opt)
+        })
+        .map(|p| p.name.clone())
+        .collect();
+    if !clashes.is_empty() {
+        return Err(Error::category(format!(
+            "{} already has {}",
+            to,
+            clashes.join(", ")
+        )));
+    }
+
+    let mut res = Ok(());
+    for pack in packs.iter_mut().filter(|p| p.category == from) {
+        let opt = pack.opt;
+        if pack.is_installed(ctx) {
+            if let Err(e) = move_cmd::relocate(ctx, pack, to, opt) {
+                res = Err(e);
+                break;
+            }
+        } else {
+            pack.set_category(to);
+        }
+        println!("Moved {} => {}", pack.idname, to);
+    }
+    // the packages moved before a failure are saved where they are now
+    package::sort(&mut packs);
+    package::update_pac_plugin(ctx, &packs)?;
+    package::save(ctx, packs)?;
+    res?;
+    package::prune_categories(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn category_counts() {
+        let packs = vec![
+            Package::new("b", "https://github.com/a/b", "lang", true),
+            Package::new("c", "https://github.com/a/c", "default", false),
+            Package::new("d", "https://github.com/a/d", "lang", false),
+        ];
+        assert_eq!(counts(&packs), vec![("default", 1, 0), ("lang", 2, 1)]);
+    }
+}
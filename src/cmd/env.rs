@@ -0,0 +1,229 @@
+use crate::context::{self, Context};
+use crate::echo::{self, Ui};
+use crate::git;
+use crate::policy::Policy;
+use crate::settings::Settings;
+use crate::utils::{json_string, on_path};
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+use crossterm::style::Color;
+use std::path::Path;
+
+#[derive(Debug)]
+struct EnvArgs {
+    format: String,
+}
+
+impl EnvArgs {
+    fn from_matches(m: &ArgMatches) -> EnvArgs {
+        EnvArgs {
+            format: value_t!(m, "format", String).unwrap_or_else(|_| "sh".to_string()),
+        }
+    }
+}
+
+/// A resolved value, `Unset` for a missing editor or an unrestricted policy
+#[derive(Debug, PartialEq)]
+enum Value {
+    Str(String),
+    Bool(bool),
+    List(Vec<String>),
+    Unset,
+}
+
+/// Values of one section, `None` for the top level
+type Section = (Option<&'static str>, Vec<(&'static str, Value)>);
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = EnvArgs::from_matches(matches);
+
+    match resolve(ctx) {
+        Ok(sections) if args.format == "json" => println!("{}", to_json(&sections)),
+        Ok(sections) => print!("{}", to_sh(&sections)),
+        Err(e) => die!("Err: {}", e),
+    }
+}
+
+fn resolve(ctx: &Context) -> Result<Vec<Section>> {
+    let path = |p: &Path| Value::Str(p.display().to_string());
+    let editor = context::editors()
+        .into_iter()
+        .find(|e| on_path(e))
+        .map_or(Value::Unset, Value::Str);
+    let ui = match echo::ui() {
+        Ui::Tty => "tty",
+        Ui::Simple => "simple",
+        Ui::Jsonl => "jsonl",
+    };
+    let paths = vec![
+        ("base_dir", ctx.pack_dir.parent().map_or(Value::Unset, path)),
+        ("pack_dir", path(&ctx.pack_dir)),
+        ("plugin_dir", path(&ctx.plugin_dir)),
+        ("config_file", path(&ctx.config_file)),
+        ("store_dir", path(&ctx.store_dir)),
+        ("cache_dir", path(&ctx.cache_dir)),
+        ("state_dir", path(&ctx.state_dir)),
+        ("editor", editor),
+        ("git", Value::Str(git::binary().display().to_string())),
+        (
+            "git_version",
+            git::version().map_or(Value::Unset, Value::Str),
+        ),
+        ("ui", Value::Str(ui.to_string())),
+    ];
+
+    let s = Settings::load(ctx)?;
+    let glyphs = |chars: &[char]| Value::Str(chars.iter().collect());
+    let settings = vec![
+        ("spinner", glyphs(&s.theme.spinner)),
+        ("bar", glyphs(&[s.theme.bar.0, s.theme.bar.1])),
+        ("success_glyph", glyphs(&[s.theme.success])),
+        ("failure_glyph", glyphs(&[s.theme.failure])),
+        ("success_color", color(s.colors.success)),
+        ("failure_color", color(s.colors.failure)),
+        ("category_color", color(s.colors.category)),
+        ("opt_color", color(s.colors.opt)),
+        ("notify", Value::Bool(s.notify)),
+        ("default_category", Value::Str(s.default_category)),
+        ("prune_categories", Value::Bool(s.prune_categories)),
+        ("ignore", Value::List(s.ignore)),
+        ("git_binary", s.git_binary.map_or(Value::Unset, Value::Str)),
+        (
+            "sync_reminder",
+            s.sync_reminder
+                .map_or(Value::Unset, |d| Value::Str(format!("{} days", d))),
+        ),
+        (
+            "ref_cache",
+            match s.ref_cache {
+                0 => Value::Bool(false),
+                m => Value::Str(format!("{} minutes", m)),
+            },
+        ),
+        ("retries", Value::Str(s.retries.to_string())),
+    ];
+
+    let p = Policy::load(ctx)?;
+    let policy = vec![
+        ("https_only", Value::Bool(p.https_only)),
+        ("allow_build", Value::Bool(p.allow_build)),
+        (
+            "allowed_hosts",
+            p.allowed_hosts.map_or(Value::Unset, Value::List),
+        ),
+    ];
+    Ok(vec![
+        (None, paths),
+        (Some("settings"), settings),
+        (Some("policy"), policy),
+    ])
+}
+
+/// The name `colors` takes, like `dark_green` or `#rrggbb`
+fn color(c: Color) -> Value {
+    let name = match c {
+        Color::Rgb { r, g, b } => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        Color::AnsiValue(v) => v.to_string(),
+        c => {
+            let mut name = String::new();
+            for ch in format!("{:?}", c).chars() {
+                if ch.is_uppercase() && !name.is_empty() {
+                    name.push('_');
+                }
+                name.push(ch.to_ascii_lowercase());
+            }
+            name
+        }
+    };
+    Value::Str(name)
+}
+
+/// `export PAC_<SECTION>_<KEY>='value'` lines, lists are joined by commas
+fn to_sh(sections: &[Section]) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "'\\''"));
+    let mut out = String::new();
+    for (section, values) in sections {
+        for (key, value) in values {
+            let name = match section {
+                Some(s) => format!("PAC_{}_{}", s, key),
+                None => format!("PAC_{}", key),
+            };
+            let value = match value {
+                Value::Str(s) => quote(s),
+                Value::Bool(b) => b.to_string(),
+                Value::List(l) => quote(&l.join(",")),
+                Value::Unset => "''".to_string(),
+            };
+            out += &format!("export {}={}\n", name.to_uppercase(), value);
+        }
+    }
+    out
+}
+
+fn to_json(sections: &[Section]) -> String {
+    let value = |v: &Value| match v {
+        Value::Str(s) => json_string(s),
+        Value::Bool(b) => b.to_string(),
+        Value::List(l) => {
+            let items: Vec<String> = l.iter().map(|i| json_string(i)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Unset => "null".to_string(),
+    };
+    let object = |values: &[(&str, Value)]| {
+        let fields: Vec<String> = values
+            .iter()
+            .map(|(k, v)| format!("{}: {}", json_string(k), value(v)))
+            .collect();
+        fields.join(", ")
+    };
+    let fields: Vec<String> = sections
+        .iter()
+        .map(|(section, values)| match section {
+            Some(s) => format!("{}: {{{}}}", json_string(s), object(values)),
+            None => object(values),
+        })
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_output() {
+        let sections = vec![
+            (
+                None,
+                vec![("pack_dir", Value::Str("/it's/pack".to_string()))],
+            ),
+            (
+                Some("policy"),
+                vec![
+                    ("https_only", Value::Bool(true)),
+                    (
+                        "allowed_hosts",
+                        Value::List(vec!["a.org".into(), "b.org".into()]),
+                    ),
+                ],
+            ),
+        ];
+        assert_eq!(
+            to_sh(&sections),
+            "export PAC_PACK_DIR='/it'\\''s/pack'\n\
+             export PAC_POLICY_HTTPS_ONLY=true\n\
+             export PAC_POLICY_ALLOWED_HOSTS='a.org,b.org'\n"
+        );
+        assert_eq!(
+            to_json(&sections),
+            "{\"pack_dir\": \"/it's/pack\", \"policy\": {\"https_only\": true, \
+             \"allowed_hosts\": [\"a.org\", \"b.org\"]}}"
+        );
+        assert_eq!(
+            color(Color::DarkGreen),
+            Value::Str("dark_green".to_string())
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use crate::context::Context;
+use crate::package::{self, Package};
+use crate::task::{TaskManager, TaskType};
+use crate::{Error, Result};
+
+use clap::{value_t, ArgMatches};
+use std::process;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+struct ExecArgs {
+    plugins: Vec<String>,
+    category: Option<String>,
+    command: Vec<String>,
+    threads: Option<usize>,
+}
+
+impl ExecArgs {
+    fn from_matches(m: &ArgMatches) -> ExecArgs {
+        ExecArgs {
+            plugins: m.values_of_lossy("package").unwrap_or_default(),
+            category: value_t!(m, "category", String).ok(),
+            command: m.values_of_lossy("command").unwrap_or_default(),
+            threads: value_t!(m, "threads", usize).ok(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = ExecArgs::from_matches(matches);
+
+    let threads = args.threads.unwrap_or_else(num_cpus::get);
+    if threads < 1 {
+        die!("Threads should be greater than 0");
+    }
+
+    if let Err(e) = exec_plugins(ctx, &args, threads) {
+        die!("Err: {}", e);
+    }
+}
+
+/// Run the command in every matching package directory and print the
+/// collected output of each package once all of them are done.
+fn exec_plugins(ctx: &Context, args: &ExecArgs, threads: usize) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+
+    let mut manager = TaskManager::new(ctx, TaskType::Exec, threads);
+    for pack in packs.iter().filter(|p| p.is_installed(ctx)) {
+        if !args.plugins.is_empty() && !args.plugins.contains(&pack.idname) {
+            continue;
+        }
+        if let Some(ref c) = args.category {
+            if &pack.category != c {
+                continue;
+            }
+        }
+        manager.add(pack.clone());
+    }
+
+    let outputs = Arc::new(Mutex::new(Vec::new()));
+    let runner = {
+        let command = Arc::new(args.command.clone());
+        let outputs = outputs.clone();
+        move |ctx: &Context, pack: &Package| {
+            let res = run_command(ctx, pack, &command).map(|out| {
+                outputs.lock().unwrap().push((pack.idname.clone(), out));
+            });
+            (res, false)
+        }
+    };
+    let failures = manager.run(runner);
+
+    let mut outputs = outputs.lock().unwrap();
+    outputs.sort();
+    for (idname, out) in outputs.iter() {
+        println!("{}:", idname);
+        for line in out.lines() {
+            println!("    {}", line);
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(Error::command(format!("failed in {}", failures.join(", "))));
+    }
+    Ok(())
+}
+
+fn run_command(ctx: &Context, pack: &Package, command: &[String]) -> Result<String> {
+    let output = process::Command::new(&command[0])
+        .args(&command[1..])
+        .current_dir(pack.path(ctx))
+        .stdin(process::Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::command(
+            err.lines().next().unwrap_or("non zero exit status"),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
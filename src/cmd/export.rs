@@ -0,0 +1,195 @@
+use crate::context::Context;
+use crate::git::GitRepo;
+use crate::package::{self, Package};
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+
+#[derive(Debug)]
+struct ExportArgs {
+    format: String,
+    target: String,
+}
+
+impl ExportArgs {
+    fn from_matches(m: &ArgMatches) -> ExportArgs {
+        ExportArgs {
+            format: value_t!(m, "format", String).unwrap(),
+            target: value_t!(m, "target", String).unwrap_or_else(|_| "neovim".to_string()),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = ExportArgs::from_matches(matches);
+
+    if let Err(e) = export(ctx, &args) {
+        die!("Err: {}", e);
+    }
+}
+
+fn export(ctx: &Context, args: &ExportArgs) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    // pinned to what is checked out now
+    let pinned: Vec<(&Package, Option<String>)> = packs
+        .iter()
+        .map(|p| (p, p.git_head(ctx).ok().map(|oid| oid.to_string())))
+        .collect();
+    match args.format.as_str() {
+        "lazy" => print!("{}", lazy(&pinned)),
+        _ => print!("{}", home_manager(&pinned, &args.target)),
+    }
+    Ok(())
+}
+
+fn nix_string(s: &str) -> String {
+    let s = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${");
+    format!("\"{}\"", s)
+}
+
+/// A home-manager module setting `programs.<target>.plugins`. Sources are
+/// fetched with `builtins.fetchGit` at the installed commit, which needs no
+/// hash. Packages which are not installed follow their remote's default branch.
+fn home_manager(packs: &[(&Package, Option<String>)], target: &str) -> String {
+    let mut out = String::from(
+        "# Generated by pac export
+{ pkgs, ... }:
+let
+  plugin = name: src: pkgs.vimUtils.buildVimPlugin {
+    pname = name;
+    version = src.shortRev or \"unstable\";
+    inherit src;
+  };
+in
+{
+",
+    );
+    out += &format!("  programs.{}.plugins = [\n", target);
+    for (pack, commit) in packs {
+        let mut src = format!("url = {};", nix_string(&pack.remote));
+        match commit {
+            Some(rev) => src += &format!(" rev = {}; allRefs = true;", nix_string(rev)),
+            None => out += &format!("    # {} is not installed, not pinned\n", pack.idname),
+        }
+        let drv = format!(
+            "(plugin {} (builtins.fetchGit {{ {} }}))",
+            nix_string(&pack.name),
+            src
+        );
+        // vim's plugins are all loaded on startup
+        if pack.opt && target == "neovim" {
+            out += &format!("    {{ plugin = {}; optional = true; }}\n", drv);
+        } else {
+            out += &format!("    {}\n", drv);
+        }
+    }
+    out += "  ];\n}\n";
+    out
+}
+
+fn lua_string(s: &str) -> String {
+    let s = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", s)
+}
+
+fn lua_list(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|s| lua_string(s)).collect();
+    format!("{{ {} }}", items.join(", "))
+}
+
+/// A lazy.nvim spec to return from `lua/plugins.lua`. Github remotes are
+/// written as `owner/repo`, commands and filetypes become `cmd` and `ft`
+/// triggers and opt packages without one are `lazy`.
+fn lazy(packs: &[(&Package, Option<String>)]) -> String {
+    let mut out = String::from("-- Generated by pac export\nreturn {\n");
+    for (pack, commit) in packs {
+        let mut fields = vec![];
+        match pack.remote.strip_prefix("https://github.com/") {
+            Some(repo) if repo.matches('/').count() == 1 => {
+                fields.push(lua_string(repo.trim_end_matches(".git")))
+            }
+            _ => fields.push(format!("url = {}", lua_string(&pack.remote))),
+        }
+        if Package::name_from_remote(&pack.remote) != pack.name {
+            fields.push(format!("name = {}", lua_string(&pack.name)));
+        }
+        match commit {
+            Some(rev) => fields.push(format!("commit = {}", lua_string(rev))),
+            None => out += &format!("  -- {} is not installed, not pinned\n", pack.idname),
+        }
+        if !pack.load_commands.is_empty() {
+            fields.push(format!("cmd = {}", lua_list(&pack.load_commands)));
+        }
+        if !pack.for_types.is_empty() {
+            fields.push(format!("ft = {}", lua_list(&pack.for_types)));
+        }
+        if pack.opt && !pack.is_lazy() {
+            fields.push("lazy = true".to_string());
+        }
+        // build commands run in a shell one after another
+        if !pack.build_commands.is_empty() {
+            let build = pack.build_commands.join(" && ");
+            fields.push(format!("build = {}", lua_string(&build)));
+        }
+        if !pack.requires.is_empty() {
+            fields.push(format!("dependencies = {}", lua_list(&pack.requires)));
+        }
+        out += &format!("  {{ {} }},\n", fields.join(", "));
+    }
+    out += "}\n";
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_home_manager() {
+        let pack = Package::new(
+            "vim-${x}",
+            "https://github.com/me/vim-${x}",
+            "default",
+            true,
+        );
+        let packs = vec![(&pack, Some("abc123".to_string()))];
+        let out = home_manager(&packs, "neovim");
+        assert!(out.contains("  programs.neovim.plugins = [\n"));
+        assert!(out.contains(
+            "    { plugin = (plugin \"vim-\\${x}\" (builtins.fetchGit { \
+             url = \"https://github.com/me/vim-\\${x}\"; rev = \"abc123\"; allRefs = true; \
+             })); optional = true; }\n"
+        ));
+    }
+
+    #[test]
+    fn export_lazy() {
+        let mut pack = Package::new(
+            "fzf",
+            "https://github.com/junegunn/fzf.vim",
+            "default",
+            true,
+        );
+        pack.load_commands = vec!["Files".to_string()];
+        pack.build_commands = vec!["./install".to_string(), "make".to_string()];
+        pack.requires = vec!["junegunn/fzf".to_string()];
+        let other = Package::new("x", "https://git.sr.ht/~me/x", "default", true);
+        let packs = vec![(&pack, Some("abc123".to_string())), (&other, None)];
+        assert_eq!(
+            lazy(&packs),
+            "-- Generated by pac export\n\
+             return {\n  \
+             { \"junegunn/fzf.vim\", name = \"fzf\", commit = \"abc123\", cmd = { \"Files\" }, \
+             build = \"./install && make\", dependencies = { \"junegunn/fzf\" } },\n  \
+             -- ~me/x is not installed, not pinned\n  \
+             { url = \"https://git.sr.ht/~me/x\", lazy = true },\n\
+             }\n"
+        );
+    }
+}
@@ -1,17 +1,18 @@
+use crate::context::Context;
 use crate::package;
 use crate::Result;
 use clap::ArgMatches;
 
-pub fn exec(_matches: &ArgMatches) {
-    let _ = update_paconfig();
+pub fn exec(ctx: &Context, _matches: &ArgMatches) {
+    let _ = update_paconfig(ctx);
 }
 
 // TODO: code repetition, refactor
-fn update_paconfig() -> Result<()> {
-    let mut packs = package::fetch()?;
+fn update_paconfig(ctx: &Context) -> Result<()> {
+    let mut packs = package::fetch(ctx)?;
 
-    packs.sort_by(|a, b| a.idname.cmp(&b.idname));
-    package::update_pac_plugin(&packs)?;
+    package::sort(&mut packs);
+    package::update_pac_plugin(ctx, &packs)?;
 
     Ok(())
 }
@@ -0,0 +1,140 @@
+use crate::context::Context;
+use crate::package::{self, Package};
+use crate::utils::json_string;
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+
+#[derive(Debug)]
+struct GraphArgs {
+    format: String,
+}
+
+impl GraphArgs {
+    fn from_matches(m: &ArgMatches) -> GraphArgs {
+        GraphArgs {
+            format: value_t!(m, "format", String).unwrap_or_else(|_| "dot".to_string()),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = GraphArgs::from_matches(matches);
+
+    if let Err(e) = graph(ctx, args) {
+        die!("Err: {}", e);
+    }
+}
+
+fn graph(ctx: &Context, args: GraphArgs) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    let out = match args.format.as_str() {
+        "json" => to_json(&packs),
+        _ => to_dot(&packs),
+    };
+    println!("{}", out);
+    Ok(())
+}
+
+/// Required idnames which are not in paconfig
+fn missing(packs: &[Package]) -> Vec<&str> {
+    let mut missing: Vec<&str> = packs
+        .iter()
+        .flat_map(|p| p.requires.iter())
+        .filter(|r| !packs.iter().any(|p| &p.idname == *r))
+        .map(|r| r.as_str())
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+    missing
+}
+
+/// Graphviz digraph with a cluster per category, missing dependencies are
+/// dashed nodes outside of any cluster.
+fn to_dot(packs: &[Package]) -> String {
+    let mut categories: Vec<&str> = packs.iter().map(|p| p.category.as_str()).collect();
+    categories.sort_unstable();
+    categories.dedup();
+
+    let mut out = String::from("digraph pac {\n");
+    for category in categories {
+        out.push_str(&format!(
+            "    subgraph {} {{\n",
+            json_string(&format!("cluster_{}", category))
+        ));
+        out.push_str(&format!("        label={};\n", json_string(category)));
+        for p in packs.iter().filter(|p| p.category == category) {
+            let style = if p.opt { " [style=dashed]" } else { "" };
+            out.push_str(&format!("        {}{};\n", json_string(&p.idname), style));
+        }
+        out.push_str("    }\n");
+    }
+    for m in missing(packs) {
+        out.push_str(&format!(
+            "    {} [color=red, style=dotted];\n",
+            json_string(m)
+        ));
+    }
+    for p in packs {
+        for r in p.requires.iter() {
+            out.push_str(&format!(
+                "    {} -> {};\n",
+                json_string(&p.idname),
+                json_string(r)
+            ));
+        }
+    }
+    out.push('}');
+    out
+}
+
+/// `{"packages": [...], "missing": [...]}`, each package with its edges in
+/// `requires`
+fn to_json(packs: &[Package]) -> String {
+    let list = |items: &mut dyn Iterator<Item = &str>| -> String {
+        let items: Vec<String> = items.map(json_string).collect();
+        format!("[{}]", items.join(", "))
+    };
+    let packages: Vec<String> = packs
+        .iter()
+        .map(|p| {
+            format!(
+                "{{\"idname\": {}, \"category\": {}, \"opt\": {}, \"requires\": {}}}",
+                json_string(&p.idname),
+                json_string(&p.category),
+                p.opt,
+                list(&mut p.requires.iter().map(|r| r.as_str()))
+            )
+        })
+        .collect();
+    format!(
+        "{{\"packages\": [{}], \"missing\": {}}}",
+        packages.join(", "),
+        list(&mut missing(packs).into_iter())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graph_formats() {
+        let mut pack = Package::new("one", "https://github.com/a/one", "lang", true);
+        pack.requires = vec!["b/two".to_string()];
+        let packs = vec![pack];
+
+        let dot = to_dot(&packs);
+        assert!(dot.contains("subgraph \"cluster_lang\" {"));
+        assert!(dot.contains("\"a/one\" [style=dashed];"));
+        assert!(dot.contains("\"b/two\" [color=red, style=dotted];"));
+        assert!(dot.contains("\"a/one\" -> \"b/two\";"));
+
+        let json = to_json(&packs);
+        assert_eq!(
+            json,
+            "{\"packages\": [{\"idname\": \"a/one\", \"category\": \"lang\", \"opt\": true, \
+             \"requires\": [\"b/two\"]}], \"missing\": [\"b/two\"]}"
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use crate::cmd::install::is_short_name;
+use crate::context::Context;
+use crate::dry_run;
+use crate::lua::{self, Parser, Table, Token, Value};
+use crate::package::{self, Channel, Package};
+use crate::settings::Settings;
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+struct ImportArgs {
+    file: PathBuf,
+    category: Option<String>,
+}
+
+impl ImportArgs {
+    fn from_matches(m: &ArgMatches) -> ImportArgs {
+        ImportArgs {
+            file: value_t!(m, "file", PathBuf).unwrap(),
+            category: value_t!(m, "category", String).ok(),
+        }
+    }
+}
+
+/// A package read from a spec with the keys that have no paconfig
+/// equivalent, like `event` or `config`
+#[derive(Debug)]
+struct Imported {
+    pack: Package,
+    ignored: Vec<String>,
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = ImportArgs::from_matches(matches);
+
+    if let Err(e) = import(ctx, &args) {
+        die!("Err: {}", e);
+    }
+}
+
+fn import(ctx: &Context, args: &ImportArgs) -> Result<()> {
+    let src = fs::read_to_string(&args.file)?;
+    let category = match args.category {
+        Some(ref c) => c.clone(),
+        None => Settings::load(ctx)?.default_category,
+    };
+    package::validate_category(&category)?;
+    let imported = parse(&src, &category);
+    if imported.is_empty() {
+        die!("Err: No plugin specs found in {}", args.file.display());
+    }
+
+    let mut packs = package::fetch(ctx)?;
+    let mut added = 0;
+    for Imported { pack, ignored } in imported {
+        if packs.iter().any(|p| p.idname == pack.idname) {
+            println!("{} is already in paconfig", pack.idname);
+            continue;
+        }
+        println!("Imported {}", pack);
+        if !ignored.is_empty() {
+            println!("    ignored: {}", ignored.join(", "));
+        }
+        packs.push(pack);
+        added += 1;
+    }
+    if dry_run::enabled() || added == 0 {
+        return Ok(());
+    }
+    package::save(ctx, packs)?;
+    println!("Run `pac install` to install them");
+    Ok(())
+}
+
+/// Every plugin spec in a packer.nvim or lazy.nvim config: `use 'a/b'`,
+/// `use { 'a/b', ... }` and the tables given to `require('lazy').setup`.
+/// The first spec of a package wins.
+fn parse(src: &str, category: &str) -> Vec<Imported> {
+    let tokens = lua::tokenize(src);
+    let mut parser = Parser::new(&tokens);
+    let mut out = vec![];
+    while let Some(t) = parser.peek() {
+        match t {
+            Token::Sym('{') => {
+                let table = parser.table();
+                collect(&Value::Table(table), category, &mut out);
+            }
+            Token::Name(n) if n == "use" => {
+                parser.next();
+                if let Some(Token::Str(s)) = parser.peek() {
+                    parser.next();
+                    collect(&Value::Str(s.clone()), category, &mut out);
+                }
+            }
+            _ => {
+                parser.next();
+            }
+        }
+    }
+
+    let mut seen: Vec<String> = vec![];
+    out.retain(|i| {
+        let first = !seen.contains(&i.pack.idname);
+        seen.push(i.pack.idname.clone());
+        first
+    });
+    out
+}
+
+/// Git remote of a spec's first item, `owner/repo` is on github
+fn remote(repo: &str) -> Option<String> {
+    // git would read a remote starting with a dash as an option
+    if repo.starts_with('-') {
+        return None;
+    }
+    if repo.contains("://") || repo.starts_with("git@") {
+        return Some(repo.to_string());
+    }
+    let parts: Vec<&str> = repo.split('/').collect();
+    let valid =
+        |p: &str| !p.is_empty() && p != "~" && p != ".." && !p.contains(char::is_whitespace);
+    if parts.len() == 2 && parts.iter().all(|p| valid(p)) && !is_short_name(repo) {
+        Some(format!("https://github.com/{}", repo))
+    } else {
+        None
+    }
+}
+
+/// Add the specs in `value`, returning the idnames of those added directly
+/// so that `requires` can point at them
+fn collect(value: &Value, category: &str, out: &mut Vec<Imported>) -> Vec<String> {
+    match value {
+        Value::Str(s) => match remote(s) {
+            Some(remote) => {
+                let pack = Package::new(
+                    &Package::name_from_remote(&remote),
+                    &remote,
+                    category,
+                    false,
+                );
+                let idname = pack.idname.clone();
+                out.push(Imported {
+                    pack,
+                    ignored: vec![],
+                });
+                vec![idname]
+            }
+            None => vec![],
+        },
+        Value::Table(t) => {
+            let first = t.items.first().and_then(Value::as_str);
+            if first.and_then(remote).is_some() || t.get("url").is_some() {
+                return spec(t, category, out).into_iter().collect();
+            }
+            // a list of specs, lazy.nvim also takes it as `spec = {...}`
+            let mut ids = vec![];
+            for v in t.items.iter().chain(t.get("spec")) {
+                ids.extend(collect(v, category, out));
+            }
+            ids
+        }
+        _ => vec![],
+    }
+}
+
+fn spec(t: &Table, category: &str, out: &mut Vec<Imported>) -> Option<String> {
+    let first = t.items.first().and_then(Value::as_str);
+    let remote = match t.get("url").and_then(Value::as_str) {
+        Some(url) => url.to_string(),
+        None => remote(first?)?,
+    };
+    let disabled = |key: &str, off: bool| t.get(key) == Some(&Value::Bool(off));
+    if disabled("disable", true) || disabled("enabled", false) {
+        return None;
+    }
+
+    let name = t
+        .get("as")
+        .or_else(|| t.get("name"))
+        .and_then(Value::as_str)
+        .map(String::from)
+        .unwrap_or_else(|| Package::name_from_remote(&remote));
+    let mut pack = Package::new(&name, &remote, category, false);
+    let mut ignored = vec![];
+    let mut requires = vec![];
+    // dependencies are pushed while reading the fields, the package goes
+    // before them
+    let at = out.len();
+    for (key, value) in &t.fields {
+        match key.as_str() {
+            "url" | "as" | "name" | "disable" | "enabled" => (),
+            "opt" | "lazy" => pack.opt = pack.opt || *value == Value::Bool(true),
+            "cmd" => pack.load_commands = value.as_list(),
+            "ft" => pack.set_types(value.as_list()),
+            // vim commands like `:TSUpdate` and lua functions can't run
+            // from a shell
+            "run" | "build" => {
+                let cmds = value.as_list();
+                if cmds.is_empty() || cmds.iter().any(|c| c.starts_with(':')) {
+                    ignored.push(key.clone());
+                } else {
+                    pack.build_commands = cmds;
+                }
+            }
+            "commit" | "tag" | "branch" => (),
+            // semver ranges are the closest to following releases
+            "version" if value.as_str().is_some() => pack.channel = Some(Channel::Stable),
+            "requires" | "dependencies" => requires = collect(value, category, out),
+            _ => ignored.push(key.clone()),
+        }
+    }
+    pack.revision = ["commit", "tag", "branch"]
+        .iter()
+        .find_map(|k| t.get(k).and_then(Value::as_str))
+        .map(String::from);
+    if pack.revision.is_some() {
+        pack.channel = None;
+    }
+    pack.set_opt(pack.opt || pack.is_lazy());
+    pack.requires = requires;
+
+    let idname = pack.idname.clone();
+    out.insert(at, Imported { pack, ignored });
+    Some(idname)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_specs() {
+        let src = r#"
+            return require('packer').startup(function(use)
+              use 'wbthomason/packer.nvim'
+              use { 'junegunn/fzf.vim', requires = { 'junegunn/fzf', run = './install --bin' } }
+              use { 'tpope/vim-fugitive', cmd = { 'Git', 'G' }, setup = function() end }
+            end)
+            require("lazy").setup({
+              { "nvim-treesitter/nvim-treesitter", build = ":TSUpdate", event = "BufRead" },
+              { "rust-lang/rust.vim", ft = "rust", tag = "v1", enabled = true },
+              { "folke/tokyonight.nvim", enabled = false },
+              { "L3MON4D3/LuaSnip", version = "v2.*", lazy = true, name = "luasnip" },
+            })
+        "#;
+        let imported = parse(src, "default");
+        let ids: Vec<&str> = imported.iter().map(|i| i.pack.idname.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "wbthomason/packer.nvim",
+                "junegunn/fzf.vim",
+                "junegunn/fzf",
+                "tpope/vim-fugitive",
+                "nvim-treesitter/nvim-treesitter",
+                "rust-lang/rust.vim",
+                "L3MON4D3/LuaSnip",
+            ]
+        );
+        let fzf = &imported[2].pack;
+        assert_eq!(imported[1].pack.requires, vec!["junegunn/fzf"]);
+        assert_eq!(fzf.build_commands, vec!["./install --bin"]);
+        assert_eq!(imported[3].pack.load_commands, vec!["Git", "G"]);
+        assert!(imported[3].pack.opt);
+        assert_eq!(imported[4].ignored, vec!["build", "event"]);
+        assert_eq!(imported[5].pack.revision.as_deref(), Some("v1"));
+        assert_eq!(imported[5].pack.for_types, vec!["rust"]);
+        let snip = &imported[6].pack;
+        assert_eq!((snip.name.as_str(), snip.opt), ("luasnip", true));
+        assert_eq!(snip.channel, Some(Channel::Stable));
+        assert!(remote("--upload-pack=touch x://y").is_none());
+    }
+}
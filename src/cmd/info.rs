@@ -0,0 +1,90 @@
+use crate::context::Context;
+use crate::fuzzy;
+use crate::package::{self, Package};
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+
+#[derive(Debug)]
+struct InfoArgs {
+    query: String,
+}
+
+impl InfoArgs {
+    fn from_matches(m: &ArgMatches) -> InfoArgs {
+        InfoArgs {
+            query: value_t!(m, "query", String).unwrap(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = InfoArgs::from_matches(matches);
+
+    if let Err(e) = info(ctx, args) {
+        die!("Err: {}", e);
+    }
+}
+
+fn info(ctx: &Context, args: InfoArgs) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    match fuzzy::search(&args.query, &packs).first() {
+        Some(pack) => print_info(ctx, pack),
+        None => die!("Err: No package matches `{}`", args.query),
+    }
+    Ok(())
+}
+
+fn print_info(ctx: &Context, pack: &Package) {
+    let field = |key: &str, val: &str| println!("  {:<14}{}", format!("{}:", key), val);
+    let list = |key: &str, vals: &[String]| {
+        if !vals.is_empty() {
+            field(key, &vals.join(", "));
+        }
+    };
+
+    println!("{}", pack.idname);
+    if let Some(ref d) = pack.description {
+        field("description", d);
+    }
+    field("remote", &pack.remote);
+    if pack.fork.is_some() {
+        field("fork", &pack.clone_url());
+    }
+    if let Some(ref since) = pack.history_since {
+        field("history", &format!("since {}", since));
+    }
+    if !pack.submodules {
+        field("submodules", "not checked out");
+    }
+    if let Some(ref rev) = pack.revision {
+        field("revision", rev);
+    } else if let Some(channel) = pack.channel {
+        field("channel", channel.as_str());
+    }
+    if let Some(ref branch) = pack.branch {
+        field("branch", branch);
+    }
+    field("category", &pack.category);
+    field("opt", if pack.opt { "yes" } else { "no" });
+    if !pack.enabled {
+        field("enabled", "no");
+    }
+    if pack.priority != 0 {
+        field("priority", &pack.priority.to_string());
+    }
+    field("path", &pack.path(ctx).display().to_string());
+    field("store", &pack.repo_path(ctx).display().to_string());
+    field(
+        "installed",
+        if pack.is_installed(ctx) { "yes" } else { "no" },
+    );
+    list("on", &pack.load_commands);
+    list("for", &pack.for_types);
+    if let Some(ref c) = pack.condition {
+        field("if", c);
+    }
+    list("build", &pack.build_commands);
+    list("post_install", &pack.post_install);
+    list("requires", &pack.requires);
+}
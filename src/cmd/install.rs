@@ -1,9 +1,30 @@
-use crate::git::GitRepo;
-use crate::package::{self, Package};
+use crate::cli;
+use crate::cmd::reinstall;
+use crate::context::{self, Context};
+use crate::dry_run;
+use crate::filetypes;
+use crate::git;
+use crate::hosts;
+use crate::notify;
+use crate::package::{self, Channel, Package};
+use crate::prompt;
+use crate::registry::{Entry, Registry};
+use crate::settings::Settings;
+use crate::source;
+use crate::space;
+use crate::stats::{self, Event};
 use crate::task::{TaskManager, TaskType};
+use crate::utils;
+use crate::warnings::{self, Kind};
 use crate::{Error, Result};
 
-use clap::{value_t, ArgMatches};
+use clap::{value_t, AppSettings, ArgMatches};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
 
 #[derive(Debug)]
 struct InstallArgs {
@@ -11,11 +32,19 @@ struct InstallArgs {
     on: Option<String>,
     for_: Option<String>,
     as_: Option<String>,
+    fork: Option<String>,
+    history_since: Option<String>,
     threads: Option<usize>,
+    build_threads: Option<usize>,
+    options: InstallOptions,
+    verify_load: bool,
     opt: bool,
     category: String,
-    build: Option<String>,
+    category_given: bool,
+    build: Vec<String>,
     rev: Option<String>,
+    channel: Option<Channel>,
+    file: Option<String>,
 }
 
 impl InstallArgs {
@@ -25,16 +54,27 @@ impl InstallArgs {
             on: value_t!(m, "on", String).ok(),
             for_: value_t!(m, "for", String).ok(),
             as_: value_t!(m, "as", String).ok(),
+            fork: value_t!(m, "fork", String).ok(),
+            history_since: value_t!(m, "history-since", String).ok(),
             threads: value_t!(m, "threads", usize).ok(),
+            build_threads: value_t!(m, "build-threads", usize).ok(),
+            options: InstallOptions {
+                auto_build: m.is_present("auto-build"),
+                force: m.is_present("force"),
+            },
+            verify_load: m.is_present("verify-load"),
             opt: m.is_present("opt"),
             category: value_t!(m, "category", String).unwrap_or_default(),
-            build: value_t!(m, "build", String).ok(),
+            category_given: m.occurrences_of("category") > 0,
+            build: m.values_of_lossy("build").unwrap_or_default(),
             rev: value_t!(m, "rev", String).ok(),
+            channel: m.value_of("channel").and_then(Channel::parse),
+            file: value_t!(m, "file", String).ok(),
         }
     }
 }
 
-pub fn exec(matches: &ArgMatches) {
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
     let args = InstallArgs::from_matches(matches);
 
     // This check cannot be done with clap
@@ -47,109 +87,863 @@ pub fn exec(matches: &ArgMatches) {
         _ => num_cpus::get(),
     };
 
-    if threads < 1 {
+    let build_threads = args.build_threads.unwrap_or_else(num_cpus::get);
+    if threads < 1 || build_threads < 1 {
         die!("Threads should be greater than 0");
     }
 
+    let mut plugins = packages(ctx, &args).unwrap_or_else(|e| die!("Err: {}", e));
+    if let Some(ref file) = args.file {
+        let specs = read_specs(ctx, file).unwrap_or_else(|e| die!("Err: {}", e));
+        // nothing to install would install all of paconfig
+        if specs.is_empty() && plugins.is_empty() {
+            println!("No plugins in {}", file);
+            return;
+        }
+        plugins.extend(specs);
+    }
+
+    // only what this run clones is test loaded
+    let cloning: Vec<String> = if plugins.is_empty() {
+        package::fetch(ctx)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| p.enabled && !p.is_installed(ctx))
+            .map(|p| p.idname)
+            .collect()
+    } else {
+        plugins
+            .iter()
+            .filter(|p| args.options.force || !p.is_installed(ctx))
+            .map(|p| p.idname.clone())
+            .collect()
+    };
+
+    if let Err(e) = install_plugins(ctx, plugins, threads, build_threads, args.options) {
+        die!("Err: {}", e);
+    }
+    if args.verify_load && !dry_run::enabled() {
+        if let Err(e) = verify_load(ctx, &cloning) {
+            die!("Err: {}", e);
+        }
+    }
+}
+
+/// Start the editor headless on a packpath of only the packages among
+/// `idnames` in turn, and what they require, reporting the errors of
+/// `:packadd`ing them
+fn verify_load(ctx: &Context, idnames: &[String]) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    let editor = context::editors()
+        .into_iter()
+        .find(|e| utils::on_path(e))
+        .ok_or_else(|| Error::command("neither vim nor nvim is on PATH to load plugins"))?;
+    let mut broken = vec![];
+    for pack in packs
+        .iter()
+        .filter(|p| idnames.contains(&p.idname) && p.is_installed(ctx) && !p.disabled)
+    {
+        if let Some(errors) = load_errors(ctx, &editor, pack, &packs)? {
+            broken.push((pack.idname.clone(), errors));
+        }
+    }
+    if broken.is_empty() {
+        return Ok(());
+    }
+    println!("\nPackages failing to load in {}:", editor);
+    for (idname, errors) in &broken {
+        println!("  {}:", idname);
+        for line in errors.lines().filter(|l| !l.trim().is_empty()) {
+            println!("    {}", line);
+        }
+    }
+    let idnames: Vec<&str> = broken.iter().map(|(i, _)| i.as_str()).collect();
+    Err(Error::command(format!(
+        "{} failed to load",
+        idnames.join(", ")
+    )))
+}
+
+/// The packages `pack` requires, and what they require, before it
+fn load_order<'a>(pack: &'a Package, packs: &'a [Package], out: &mut Vec<&'a Package>) {
+    if out.iter().any(|p| std::ptr::eq(*p, pack)) {
+        return;
+    }
+    // taken as done already, a cycle stops here
+    out.push(pack);
+    let at = out.len() - 1;
+    for req in &pack.requires {
+        if let Some(dep) = packs.iter().find(|p| p.idname == *req) {
+            load_order(dep, packs, out);
+        }
+    }
+    let pack = out.remove(at);
+    out.push(pack);
+}
+
+/// What `:packadd` of `pack` printed if it failed, `None` if it loaded
+fn load_errors(
+    ctx: &Context,
+    editor: &str,
+    pack: &Package,
+    packs: &[Package],
+) -> Result<Option<String>> {
+    let root = env::temp_dir().join(format!("pac-verify-{}", process::id()));
+    let opt = root.join("pack/verify/opt");
+    if root.exists() {
+        fs::remove_dir_all(&root)?;
+    }
+    fs::create_dir_all(&opt)?;
+
+    let quote = |p: &Path| format!("'{}'", p.display().to_string().replace('\'', "''"));
+    let mut order = vec![];
+    load_order(pack, packs, &mut order);
+    let mut adds = vec![];
+    for (i, p) in order.iter().filter(|p| p.is_installed(ctx)).enumerate() {
+        // numbered, two packages may share a name
+        let name = format!("{}-{}", i, p.name);
+        utils::link_dir(p.path(ctx), opt.join(&name))?;
+        adds.push(format!("packadd {}", name));
+    }
+    let log = root.join("messages");
+    let rc = root.join("vimrc");
+    fs::write(
+        &rc,
+        format!(
+            "set nocompatible\nset runtimepath=$VIMRUNTIME\n\
+             execute 'set packpath=' . fnameescape({})\n\
+             let v:errmsg = ''\nexecute 'redir! >' fnameescape({})\n{}\nredir END\n\
+             if empty(v:errmsg) | qa! | else | cquit | endif\n",
+            quote(&root),
+            quote(&log),
+            adds.join("\n")
+        ),
+    )?;
+
+    let mut cmd = process::Command::new(editor);
+    if editor.ends_with("nvim") {
+        cmd.arg("--headless");
+    } else {
+        cmd.args(["-N", "-Es"]);
+    }
+    let status = cmd
+        .arg("-u")
+        .arg(&rc)
+        .args(["-i", "NONE"])
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .status();
+    let messages = fs::read_to_string(&log).unwrap_or_default();
+    fs::remove_dir_all(&root)?;
+    if status?.success() {
+        Ok(None)
+    } else {
+        Ok(Some(messages))
+    }
+}
+
+/// The packages `args` asks for, with the options given along with them
+fn packages(ctx: &Context, args: &InstallArgs) -> Result<Vec<Package>> {
+    let category = if args.category_given {
+        args.category.clone()
+    } else {
+        Settings::load(ctx)?.default_category
+    };
+    package::validate_category(&category)?;
+    if let Some(ref name) = args.as_ {
+        package::validate_name(name)?;
+    }
+
     let opt = args.on.is_some() || args.for_.is_some() || args.opt;
-    let types = args
-        .for_
-        .clone() // map consumes value but we need it in next block
-        .map(|e| e.split(',').map(|e| e.to_string()).collect::<Vec<String>>())
-        .unwrap_or_default();
-
-    let plugins = args
-        .plugins
+    let split = |val: &Option<String>| {
+        val.as_ref()
+            .map(|e| e.split(',').map(|e| e.to_string()).collect::<Vec<String>>())
+            .unwrap_or_default()
+    };
+    let types = split(&args.for_);
+    let cmds = split(&args.on);
+
+    // Short names like `fzf` are resolved through the registry index
+    let registry = if args.plugins.iter().any(|p| is_short_name(p)) {
+        Registry::load(ctx)?
+    } else {
+        Registry::default()
+    };
+
+    args.plugins
         .iter()
         .map(|plug| {
-            // URL to git clone from
-            let remote = if !plug.starts_with("https://") {
-                format!("https://github.com/{}", plug)
-            } else {
-                plug.clone()
+            // a short name the registry lacks may still be found on github
+            let (remote, entry) = match resolve(plug, &registry) {
+                Err(e) if is_short_name(plug) => (search_github(plug).ok_or(e)?, None),
+                res => res?,
             };
 
             // Install package under this name. Defaults to repo name
             let name = args
                 .as_
-                // unwrap_or_else consumes self so deref to Option<&str>
-                .as_deref()
-                .unwrap_or_else(|| remote.rsplitn(2, '/').next().unwrap());
+                .clone()
+                .unwrap_or_else(|| Package::name_from_remote(&remote));
 
             // FIXME: too many clones
-            Package {
-                name: name.to_string(),
-                idname: Package::idname_from_remote(&remote),
-                remote,
-                revision: args.rev.clone(),
-                category: args.category.clone(),
-                opt,
-                for_types: types.clone(),
-                load_command: args.on.clone(),
-                build_command: args.build.clone(),
+            let mut pack = Package::new(&name, &remote, &category, opt);
+            pack.revision = args.rev.clone();
+            pack.channel = args.channel;
+            pack.fork = args.fork.clone();
+            pack.history_since = args.history_since.clone();
+            pack.set_types(types.clone());
+            pack.load_commands = cmds.clone();
+            pack.build_commands = args.build.clone();
+
+            // options given on the command line win over recommended ones
+            if let Some(e) = entry {
+                if !args.category_given {
+                    if let Some(ref c) = e.category {
+                        pack.set_category(c.as_str());
+                    }
+                }
+                if pack.load_commands.is_empty() {
+                    pack.load_commands = e.load_commands.clone();
+                }
+                if pack.for_types.is_empty() {
+                    pack.set_types(e.for_types.clone());
+                }
+                if pack.build_commands.is_empty() {
+                    pack.build_commands = e.build_commands.clone();
+                }
+                pack.set_opt(pack.opt || e.opt || pack.is_lazy());
+                pack.description = e.description.clone();
             }
+            Ok(pack)
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
 
-    if let Err(e) = install_plugins(plugins, threads) {
-        die!("Err: {}", e);
+/// The packages listed in `file`, or on stdin for `-`. Every line is a
+/// plugin with the options of `pac install`, like `tpope/vim-fugitive --on
+/// Git`, blank lines and lines starting with `#` are skipped.
+fn read_specs(ctx: &Context, file: &str) -> Result<Vec<Package>> {
+    let text = if file == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(file)?
+    };
+    let mut packs = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let at = |e: String| Error::spec(format!("{} line {}: {}", file, i + 1, e));
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let args = parse_spec(line).map_err(at)?;
+        packs.extend(packages(ctx, &args).map_err(|e| at(e.to_string()))?);
     }
+    Ok(packs)
 }
 
+/// The install arguments of a line of an install file, parsed like those
+/// of the command line
+fn parse_spec(line: &str) -> std::result::Result<InstallArgs, String> {
+    let mut argv = vec!["pac".to_string(), "install".to_string()];
+    argv.extend(words(line)?);
+    let matches = cli::build_cli()
+        .setting(AppSettings::ColorNever)
+        .get_matches_from_safe(argv)
+        .map_err(|e| {
+            let message = e.message.lines().next().unwrap_or_default();
+            message.trim_start_matches("error: ").to_string()
+        })?;
+    let args = matches
+        .subcommand_matches("install")
+        .map(InstallArgs::from_matches)
+        .ok_or_else(|| "no plugin given".to_string())?;
+    if args.plugins.len() != 1 {
+        return Err("give one plugin per line".to_string());
+    }
+    Ok(args)
+}
+
+/// Split `line` at whitespace outside of single and double quotes, like a
+/// shell without its expansions
+fn words(line: &str) -> std::result::Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("unclosed quote".to_string());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// What an install does besides cloning, all off by default
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InstallOptions {
+    /// Run and keep the suggested build command of a new package without one
+    pub auto_build: bool,
+    /// Clone the packages which are installed already again, `--force`
+    pub force: bool,
+}
+
+/// Install `toinstall_packs`, or every package in paconfig if empty, cloning
+/// on `threads` and then building on `build_threads`. Packages required by
+/// paconfig entries but missing from it are installed as well. Returns the
+/// idnames of the packages which failed.
+pub fn install_plugins(
+    ctx: &Context,
+    toinstall_packs: Vec<Package>,
+    threads: usize,
+    build_threads: usize,
+    options: InstallOptions,
+) -> Result<Vec<String>> {
+    let mut installed_packs = package::fetch(ctx)?;
+    let mut fails = install_into(
+        ctx,
+        &mut installed_packs,
+        toinstall_packs,
+        threads,
+        build_threads,
+        options,
+    )?;
+    // manifests of the new clones may require more, as may what they require
+    let mut tried = vec![];
+    loop {
+        let deps = missing_requires(ctx, &installed_packs, &mut tried)?;
+        if deps.is_empty() {
+            break;
+        }
+        let idnames: Vec<&str> = deps.iter().map(|p| p.idname.as_str()).collect();
+        println!(
+            "\nInstalling what the packages require: {}",
+            idnames.join(", ")
+        );
+        fails.extend(install_into(
+            ctx,
+            &mut installed_packs,
+            deps,
+            threads,
+            build_threads,
+            InstallOptions {
+                force: false,
+                ..options
+            },
+        )?);
+    }
+
+    if dry_run::enabled() {
+        dry_run::would("save paconfig and update the loader");
+        return Ok(fails);
+    }
+    package::sort(&mut installed_packs);
+
+    package::update_pac_plugin(ctx, &installed_packs)?;
+    package::save(ctx, installed_packs)?;
+    Ok(fails)
+}
+
+/// The packages `packs` require which are not among them, each in the
+/// category of the first package requiring it and opt if that one is. Every
+/// requirement looked at is added to `tried`, so none is installed twice.
+fn missing_requires(
+    ctx: &Context,
+    packs: &[Package],
+    tried: &mut Vec<String>,
+) -> Result<Vec<Package>> {
+    let mut wanted: Vec<(&str, &Package)> = vec![];
+    for pack in packs.iter().filter(|p| p.enabled) {
+        for req in &pack.requires {
+            if !packs.iter().any(|p| p.idname == *req)
+                && !tried.contains(req)
+                && !wanted.iter().any(|(r, _)| r == req)
+            {
+                wanted.push((req, pack));
+            }
+        }
+    }
+    let registry = if wanted.iter().any(|(r, _)| is_short_name(r)) {
+        Registry::load(ctx)?
+    } else {
+        Registry::default()
+    };
+
+    let mut deps = vec![];
+    for (req, by) in wanted {
+        tried.push(req.to_string());
+        match resolve(req, &registry) {
+            Ok((remote, _)) => deps.push(Package::new(
+                &Package::name_from_remote(&remote),
+                &remote,
+                &by.category,
+                by.opt,
+            )),
+            Err(e) => {
+                let message = format!("required by {}, {}", by.idname, e);
+                warnings::push(Kind::Skipped, Some(req), message);
+            }
+        }
+    }
+    Ok(deps)
+}
+
+/// Install into `installed_packs`, the packages of paconfig, leaving out the
+/// ones which failed and merging in their manifests. With `force` the
+/// checkouts of the ones installed already are set aside as `pac reinstall`
+/// does, and put back for those which fail. Nothing is saved.
 // FIXME: refactor this
-fn install_plugins(toinstall_packs: Vec<Package>, threads: usize) -> Result<()> {
-    let mut installed_packs = package::fetch()?;
-    {
-        let mut manager = TaskManager::new(TaskType::Install, threads);
+pub fn install_into(
+    ctx: &Context,
+    installed_packs: &mut Vec<Package>,
+    toinstall_packs: Vec<Package>,
+    threads: usize,
+    build_threads: usize,
+    options: InstallOptions,
+) -> Result<Vec<String>> {
+    let mut manager = TaskManager::new(ctx, TaskType::Install, threads);
+    manager.build_threads(build_threads);
+    let mut synced = Vec::new();
+    // only packages cloned now get a build command suggested
+    let mut fresh = Vec::new();
+    let mut forced = Vec::new();
+    let mut moved = Vec::new();
 
-        if toinstall_packs.is_empty() {
-            for pack in &installed_packs {
-                manager.add(pack.clone());
+    let reinstalling: Vec<String> = toinstall_packs
+        .iter()
+        .filter(|p| options.force && p.is_installed(ctx))
+        .map(|p| p.idname.clone())
+        .collect();
+    // always asked, as `--force` is easily added to an install command line;
+    // declined, the installed ones are left as they are
+    let force = !reinstalling.is_empty()
+        && (dry_run::enabled() || reinstall::confirm(&reinstalling, true)?);
+
+    if toinstall_packs.is_empty() {
+        for pack in installed_packs.iter_mut().filter(|p| p.enabled) {
+            if !clear_way(ctx, pack) {
+                continue;
             }
-        } else {
-            for mut toins_pack in toinstall_packs {
-                let having = match installed_packs
-                    .iter_mut()
-                    .find(|ins_pack| ins_pack.idname == toins_pack.idname)
-                {
-                    Some(ins_pack) => {
-                        // plugin in config file but not installed
-                        if !ins_pack.is_installed() {
-                            ins_pack.set_category(toins_pack.category.as_str());
-                            ins_pack.set_opt(toins_pack.opt);
-                            ins_pack.set_types(toins_pack.for_types.clone());
-
-                            ins_pack.load_command = toins_pack.load_command.clone();
-                            ins_pack.build_command = toins_pack.build_command.clone();
-                        } else {
-                            toins_pack.set_category(ins_pack.category.as_str());
-                            toins_pack.set_opt(ins_pack.opt);
+            if !pack.is_installed(ctx) {
+                fresh.push(pack.idname.clone());
+            }
+            synced.push(pack.idname.clone());
+            manager.add(pack.clone());
+        }
+    } else {
+        for mut toins_pack in toinstall_packs {
+            let having = match installed_packs
+                .iter_mut()
+                .find(|ins_pack| ins_pack.idname == toins_pack.idname)
+            {
+                Some(ins_pack) => {
+                    // plugin in config file but not installed
+                    if !ins_pack.is_installed(ctx) {
+                        ins_pack.set_category(toins_pack.category.as_str());
+                        ins_pack.set_opt(toins_pack.opt);
+                        ins_pack.set_types(toins_pack.for_types.clone());
+
+                        ins_pack.load_commands = toins_pack.load_commands.clone();
+                        ins_pack.build_commands = toins_pack.build_commands.clone();
+                        if toins_pack.fork.is_some() {
+                            ins_pack.fork = toins_pack.fork.clone();
+                        }
+                        if toins_pack.history_since.is_some() {
+                            ins_pack.history_since = toins_pack.history_since.clone();
                         }
-                        true
+                    } else if force {
+                        // cloned again as paconfig has it, build commands and all
+                        let name = toins_pack.name.clone();
+                        toins_pack = ins_pack.clone();
+                        toins_pack.name = name;
+                    } else {
+                        toins_pack.set_category(ins_pack.category.as_str());
+                        toins_pack.set_opt(ins_pack.opt);
                     }
-                    None => false,
-                };
-                if !having {
-                    // not yet installed, but add it anyway
-                    installed_packs.push(toins_pack.clone());
+                    true
                 }
-                manager.add(toins_pack.clone());
+                None => false,
+            };
+            let enabled = installed_packs
+                .iter()
+                .find(|p| p.idname == toins_pack.idname)
+                .is_none_or(|p| p.enabled);
+            if !enabled {
+                let message = "disabled in paconfig, set `enabled: true` to install it";
+                warnings::push(Kind::Skipped, Some(&toins_pack.idname), message);
+                continue;
+            }
+            if !clear_way(ctx, &mut toins_pack) {
+                continue;
+            }
+            // set aside once the space check passed, the clone is fresh
+            if force && reinstalling.contains(&toins_pack.idname) {
+                fresh.push(toins_pack.idname.clone());
+                forced.push(toins_pack.clone());
             }
+            if let Some(ins_pack) = installed_packs
+                .iter_mut()
+                .find(|p| p.idname == toins_pack.idname)
+            {
+                // it may have been renamed
+                ins_pack.name = toins_pack.name.clone();
+            }
+            if !toins_pack.is_installed(ctx) {
+                fresh.push(toins_pack.idname.clone());
+            }
+            if !having {
+                // not yet installed, but add it anyway
+                installed_packs.push(toins_pack.clone());
+            }
+            // the manager syncs a package given twice once
+            if !synced.contains(&toins_pack.idname) {
+                synced.push(toins_pack.idname.clone());
+            }
+            manager.add(toins_pack.clone());
+        }
+    }
+
+    // packages sharing a clone, or already installed, take no space. The
+    // forced ones do, their old checkouts stay until the new clones are in.
+    let to_clone: Vec<&Package> = installed_packs
+        .iter()
+        .filter(|p| fresh.contains(&p.idname))
+        .filter(|p| !p.store_path(ctx).is_dir() || forced.iter().any(|f| f.idname == p.idname))
+        .collect();
+    space::check_install(ctx, &to_clone)?;
+
+    for pack in &forced {
+        if dry_run::enabled() {
+            let repo = pack.repo_path(ctx);
+            dry_run::would(format!("set {} aside and clone it again", repo.display()));
+        } else if let Err(e) = reinstall::set_aside(ctx, pack, &mut moved) {
+            // still installed, the manager leaves it as it is
+            warnings::push(Kind::Skipped, Some(&pack.idname), e.to_string());
+        }
+    }
+
+    let fails = manager.run(install_plugin);
+    // the old checkouts of the packages which failed are put back
+    reinstall::restore(ctx, &forced, &moved)?;
+    // manifests and build suggestions come with the clones
+    if dry_run::enabled() {
+        return Ok(fails);
+    }
+    let installed = synced.len() - fails.len();
+    notify::report(
+        Some(format!("Installed {}", notify::packages(installed))),
+        &fails,
+    );
+    installed_packs.retain(|e| !fails.contains(&e.idname));
+
+    for pack in installed_packs
+        .iter_mut()
+        .filter(|p| synced.contains(&p.idname))
+    {
+        if let Err(e) = apply_manifest(ctx, pack) {
+            warnings::push(Kind::Manifest, Some(&pack.idname), e.to_string());
+        }
+        for (ft, closest) in filetypes::unknown(ctx, pack) {
+            let message = match closest {
+                Some(c) => format!("unknown filetype `{}` in `for`, did you mean `{}`?", ft, c),
+                None => format!("unknown filetype `{}` in `for`", ft),
+            };
+            warnings::push(Kind::Filetype, Some(&pack.idname), message);
         }
+    }
+    for pack in installed_packs
+        .iter_mut()
+        .filter(|p| fresh.contains(&p.idname) && p.build_commands.is_empty())
+    {
+        suggest_build(ctx, pack, options.auto_build);
+    }
 
-        for fail in manager.run(install_plugin) {
-            installed_packs.retain(|e| e.idname != fail);
+    let installed: Vec<&Package> = installed_packs
+        .iter()
+        .filter(|p| synced.contains(&p.idname))
+        .collect();
+    let missing = hosts::report(ctx, &installed);
+    if !missing.is_empty() {
+        println!("\nRemote plugin hosts not working:");
+        for line in missing {
+            println!("  {}", line);
         }
     }
+    Ok(fails)
+}
 
-    installed_packs.sort_by(|a, b| a.idname.cmp(&b.idname));
+/// What is in the way of installing `pack`: a directory under pack/ which is
+/// no clone of its remote or a link to another clone. A clone of the remote
+/// right under pack/ is a package installed before the store existed.
+fn collision(ctx: &Context, pack: &Package) -> Option<String> {
+    let path = pack.path(ctx);
+    if pack.is_linked(ctx) {
+        let target = fs::read_link(&path).ok()?;
+        return Some(format!("links to {}", target.display()))
+            .filter(|_| target != pack.store_path(ctx));
+    }
+    if !path.is_dir() {
+        return None;
+    }
+    match git::origin_url(&path) {
+        Some(url) if url == pack.clone_url() || url == pack.remote => None,
+        Some(url) => Some(format!("is a clone of {}", url)),
+        None => Some("is not a git clone".to_string()),
+    }
+}
 
-    package::update_pac_plugin(&installed_packs)?;
-    package::save(installed_packs)
+/// Whether `pack` can be installed, asking what to do with whatever is in
+/// its way: adopt the directory as its clone, overwrite it, install the
+/// package under another name or skip it. Without a terminal it is skipped.
+fn clear_way(ctx: &Context, pack: &mut Package) -> bool {
+    match resolve_collision(ctx, pack) {
+        Ok(install) => install,
+        Err(e) => {
+            warnings::push(Kind::Skipped, Some(&pack.idname), e.to_string());
+            false
+        }
+    }
+}
+
+fn resolve_collision(ctx: &Context, pack: &mut Package) -> Result<bool> {
+    let settings = Settings::load(ctx)?;
+    while let Some(problem) = collision(ctx, pack) {
+        let path = pack.path(ctx);
+        if let Ok(relpath) = path.strip_prefix(&ctx.pack_dir) {
+            if settings.ignores(relpath) {
+                let message = format!("{} {} and is ignored", path.display(), problem);
+                warnings::push(Kind::Skipped, Some(&pack.idname), message);
+                return Ok(false);
+            }
+        }
+        if dry_run::enabled() {
+            dry_run::would(format!(
+                "ask what to do about {}, which {}",
+                path.display(),
+                problem
+            ));
+            return Ok(false);
+        }
+        let question = format!("{} {}, install {}?", path.display(), problem, pack.idname);
+        // a link belongs to the clone of another package
+        let choices: &[&str] = if pack.is_linked(ctx) {
+            &["overwrite", "rename", "skip"]
+        } else {
+            &["adopt", "overwrite", "rename", "skip"]
+        };
+        match prompt::choose(&question, choices)?.map(|i| choices[i]) {
+            Some("adopt") => {
+                git::adopt(&path, &pack.clone_url())?;
+                println!("Adopted {} as {}", path.display(), pack.idname);
+            }
+            Some("overwrite") => pack.unlink(ctx)?,
+            Some("rename") => {
+                let question = format!("Install {} as:", pack.idname);
+                match prompt::input(&question)? {
+                    Some(name) => match package::validate_name(&name) {
+                        Ok(()) => pack.name = name,
+                        Err(e) => println!("{}", e),
+                    },
+                    None => println!("Names can't be empty"),
+                }
+            }
+            _ => {
+                let message = format!("{} {}", path.display(), problem);
+                warnings::push(Kind::Skipped, Some(&pack.idname), message);
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
 }
 
-fn install_plugin(pack: &Package) -> (Result<()>, bool) {
-    let res = do_install(pack);
+/// Point out the build command a package without one likely needs, or with
+/// `auto_build` run it and keep it as the package's `build`
+fn suggest_build(ctx: &Context, pack: &mut Package, auto_build: bool) {
+    let cmd = match pack.suggested_build(ctx) {
+        Some(c) => c,
+        None => return,
+    };
+    if !auto_build {
+        println!(
+            "{} has no build command, `{}` looks like it: add it as `build:` or \
+             install with --auto-build",
+            pack.idname, cmd
+        );
+        return;
+    }
+    pack.build_commands = vec![cmd];
+    match pack.try_build(ctx) {
+        Ok(()) => println!("Built {} with `{}`", pack.idname, pack.build_commands[0]),
+        Err(e) => println!("Failed to build {}: {}", pack.idname, e),
+    }
+}
+
+/// Merge the defaults from the plugin's own manifest, which came with the
+/// clone, as `package::fetch` does for the installed ones. They are not
+/// saved to paconfig. A plugin asking to be lazy loaded is moved under opt/,
+/// which is saved, so that the generated commands and autocmds have
+/// something to packadd.
+fn apply_manifest(ctx: &Context, pack: &mut Package) -> Result<()> {
+    let manifest = match pack.manifest(ctx)? {
+        Some(m) => m,
+        None => return Ok(()),
+    };
+    let was_lazy = pack.is_lazy();
+    pack.merge_manifest(&manifest);
+
+    if !pack.opt && !was_lazy && manifest.is_lazy() {
+        let origin_path = pack.path(ctx);
+        pack.set_opt(true);
+        let path = pack.path(ctx);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&origin_path, &path)?;
+    }
+    Ok(())
+}
+
+/// A plugin given without a `username/` part or a full url
+pub fn is_short_name(plug: &str) -> bool {
+    !plug.contains('/') && !is_local_path(plug)
+}
+
+/// Whether `plug` is a path on this machine, `./vim-foo`, `~/dev/vim-foo`
+/// or `/src/vim-foo`, rather than `username/repo`
+fn is_local_path(plug: &str) -> bool {
+    plug == "." || ["/", "./", "../", "~/"].iter().any(|p| plug.starts_with(p))
+}
+
+/// URL to git clone `plug` from, with its registry entry if it is a short
+/// name. `username/repo` is looked up on github, `host/username/repo` over
+/// https on that host and local paths, of directories or archives, are made
+/// absolute. Full URLs and scp like `git@host:username/repo` remotes are
+/// kept as they are.
+pub fn resolve<'a>(plug: &str, registry: &'a Registry) -> Result<(String, Option<&'a Entry>)> {
+    package::validate_remote(plug)?;
+    if is_local_path(plug) {
+        let path = match plug.strip_prefix("~/") {
+            Some(rest) => env::var_os("HOME")
+                .map(|home| Path::new(&home).join(rest))
+                .unwrap_or_else(|| PathBuf::from(plug)),
+            None => PathBuf::from(plug),
+        };
+        let dir = fs::canonicalize(&path)
+            .ok()
+            .filter(|d| d.is_dir() || package::is_archive(&d.to_string_lossy()))
+            .ok_or_else(|| Error::copy_dir(&format!("no directory or archive at {}", plug)))?;
+        return Ok((dir.to_string_lossy().into_owned(), None));
+    }
+    if is_short_name(plug) {
+        return match registry.lookup(plug) {
+            Some(e) => package::validate_remote(&e.remote_url()).map(|_| (e.remote_url(), Some(e))),
+            None => Err(Error::registry(format!(
+                "`{}` not found in registry (try `pac registry update`)",
+                plug
+            ))),
+        };
+    }
+    let plug = plug.trim_end_matches('/');
+    let remote = if plug.contains("://") || is_scp_remote(plug) {
+        plug.to_string()
+    } else if plug
+        .split('/')
+        .next()
+        .is_some_and(|host| host.contains('.'))
+    {
+        format!("https://{}", plug)
+    } else {
+        format!("https://github.com/{}", plug)
+    };
+    Ok((remote, None))
+}
+
+/// Repos on github named like `name`, most starred first, to pick the one to
+/// install from on the terminal. `None` without a terminal, when the search
+/// fails or nothing is picked.
+fn search_github(name: &str) -> Option<String> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+    let query: String = name
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' => (b as char).to_string(),
+            b => format!("%{:02X}", b),
+        })
+        .collect();
+    let url = format!(
+        "https://api.github.com/search/repositories?q={}+in:name&sort=stars&per_page=8",
+        query
+    );
+    println!("`{}` is not in the registry, searching github", name);
+    let body = match utils::download_within(&url, Duration::from_secs(10)) {
+        Ok(b) => b,
+        Err(e) => {
+            log::info!("fail to search github: {}", e);
+            return None;
+        }
+    };
+    let repos = search_results(&String::from_utf8_lossy(&body));
+    if repos.is_empty() {
+        return None;
+    }
+    let listed: Vec<String> = repos
+        .iter()
+        .map(|(name, stars)| format!("{} ({} stars)", name, stars))
+        .collect();
+    let question = format!("Install which one as `{}`?", name);
+    let picked = prompt::pick(&question, &listed).ok()??;
+    Some(format!("https://github.com/{}", repos[picked].0))
+}
+
+/// The `full_name` and `stargazers_count` of every repo in the response of
+/// a github repository search
+fn search_results(json: &str) -> Vec<(String, u64)> {
+    json.split("\"full_name\":")
+        .skip(1)
+        .filter_map(|item| {
+            let name = item.trim_start().strip_prefix('"')?.split('"').next()?;
+            let stars = item
+                .find("\"stargazers_count\":")
+                .map(|at| &item[at + 19..])
+                .and_then(|rest| {
+                    let digits: String = rest
+                        .trim_start()
+                        .chars()
+                        .take_while(char::is_ascii_digit)
+                        .collect();
+                    digits.parse().ok()
+                })
+                .unwrap_or(0);
+            Some((name.to_string(), stars))
+        })
+        .collect()
+}
+
+/// `git@host:username/repo`, where the host ends at a colon before any slash
+fn is_scp_remote(plug: &str) -> bool {
+    match (plug.find(':'), plug.find('/')) {
+        (Some(colon), Some(slash)) => colon < slash,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Clone and link a package, one already installed counts as done
+pub fn install_plugin(ctx: &Context, pack: &Package) -> (Result<()>, bool) {
+    let res = do_install(ctx, pack);
     let status = match res {
         Err(Error::PluginInstalled(_)) => true,
         Err(_) => false,
@@ -158,11 +952,68 @@ fn install_plugin(pack: &Package) -> (Result<()>, bool) {
     (res, status)
 }
 
-fn do_install(pack: &Package) -> Result<()> {
-    let path = pack.path();
+fn do_install(ctx: &Context, pack: &Package) -> Result<()> {
+    let path = pack.path(ctx);
+    // packages checked out directly under pack/ before the store existed may
+    // have been cut short too, directories without .git are left alone
+    if path.is_dir()
+        && !pack.is_linked(ctx)
+        && path.join(".git").exists()
+        && git::is_partial_clone(&path)
+    {
+        log::info!("remove the partial clone {}", path.display());
+        fs::remove_dir_all(&path)?;
+    }
     if path.is_dir() {
         Err(Error::plugin_installed(&path))
     } else {
-        pack.git_clone()
+        let source = source::of(pack);
+        source.install(ctx, pack)?;
+        source.verify(ctx, pack)?;
+        pack.link(ctx)?;
+        stats::count(&pack.idname, Event::Install);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn install_specs() {
+        assert_eq!(
+            words("a/b --build 'npm ci' --on \"Foo Bar\"  --as=''").unwrap(),
+            vec!["a/b", "--build", "npm ci", "--on", "Foo Bar", "--as="]
+        );
+        assert!(words("a/b --build 'make").is_err());
+
+        let args = parse_spec("junegunn/fzf.vim --opt --for go,rust --build make").unwrap();
+        assert_eq!(args.plugins, vec!["junegunn/fzf.vim"]);
+        assert!(args.opt);
+        assert_eq!(args.for_.as_deref(), Some("go,rust"));
+        assert_eq!(args.build, vec!["make"]);
+        assert_eq!(
+            parse_spec("a/b c/d").err().unwrap(),
+            "give one plugin per line"
+        );
+        assert!(parse_spec("a/b --bad").unwrap_err().contains("--bad"));
+        assert!(resolve("--upload-pack=touch x://y", &Registry::default()).is_err());
+    }
+
+    #[test]
+    fn install_search_results() {
+        let json = r#"{"total_count": 2, "items": [
+            {"id": 1, "name": "vim-fugitive", "full_name": "tpope/vim-fugitive",
+             "owner": {"login": "tpope"}, "stargazers_count": 19800},
+            {"id": 2, "full_name": "me/fugitive.nvim", "stargazers_count":3}]}"#;
+        assert_eq!(
+            search_results(json),
+            vec![
+                ("tpope/vim-fugitive".to_string(), 19800),
+                ("me/fugitive.nvim".to_string(), 3)
+            ]
+        );
+        assert!(search_results(r#"{"total_count": 0, "items": []}"#).is_empty());
     }
 }
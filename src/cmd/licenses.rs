@@ -0,0 +1,301 @@
+use crate::context::Context;
+use crate::git::GitRepo;
+use crate::package::{self, Package};
+use crate::utils::{json_string, utc_timestamp};
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug)]
+struct LicensesArgs {
+    format: String,
+}
+
+impl LicensesArgs {
+    fn from_matches(m: &ArgMatches) -> LicensesArgs {
+        LicensesArgs {
+            format: value_t!(m, "format", String).unwrap_or_else(|_| "text".to_string()),
+        }
+    }
+}
+
+/// License and origin of an installed package
+#[derive(Debug)]
+struct Report {
+    idname: String,
+    remote: String,
+    commit: Option<String>,
+    /// SPDX identifier, `None` if it could not be told
+    license: Option<String>,
+    /// Where the license was found, relative to the package
+    source: Option<String>,
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = LicensesArgs::from_matches(matches);
+
+    if let Err(e) = licenses(ctx, &args) {
+        die!("Err: {}", e);
+    }
+}
+
+fn licenses(ctx: &Context, args: &LicensesArgs) -> Result<()> {
+    let reports: Vec<Report> = package::fetch(ctx)?
+        .iter()
+        .filter(|p| p.is_installed(ctx))
+        .map(|p| report(ctx, p))
+        .collect();
+    if reports.is_empty() {
+        println!("No packages installed");
+        return Ok(());
+    }
+    match args.format.as_str() {
+        "json" => println!("{}", to_json(&reports)),
+        "spdx" => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64);
+            print!("{}", to_spdx(&reports, &utc_timestamp(now)));
+        }
+        _ => print_text(&reports),
+    }
+    Ok(())
+}
+
+fn report(ctx: &Context, pack: &Package) -> Report {
+    let dir = pack.repo_path(ctx);
+    // the plugin's own declaration comes first
+    let declared = pack
+        .manifest(ctx)
+        .ok()
+        .flatten()
+        .and_then(|m| m.license)
+        .map(|l| (l, "pac.yaml".to_string()));
+    let (license, source) = match declared.or_else(|| detect(&dir)) {
+        Some((l, s)) => (Some(l), Some(s)),
+        None => (None, None),
+    };
+    Report {
+        idname: pack.idname.clone(),
+        remote: pack.remote.clone(),
+        commit: pack.git_head(ctx).ok().map(|oid| oid.to_string()),
+        license,
+        source,
+    }
+}
+
+/// Look for a license file in the root of `dir`, then for a license
+/// statement in the readme and the vim help files
+fn detect(dir: &Path) -> Option<(String, String)> {
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect();
+    files.sort();
+    let is_license = |name: &str| {
+        let name = name.to_uppercase();
+        ["LICENSE", "LICENCE", "COPYING", "UNLICENSE"]
+            .iter()
+            .any(|l| name.starts_with(l))
+    };
+    let mut candidates: Vec<String> = files.iter().filter(|f| is_license(f)).cloned().collect();
+    candidates.extend(
+        files
+            .iter()
+            .filter(|f| f.to_uppercase().starts_with("README"))
+            .cloned(),
+    );
+    let mut docs: Vec<String> = fs::read_dir(dir.join("doc"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.file_name().into_string().ok())
+                .filter(|f| f.ends_with(".txt"))
+                .map(|f| format!("doc/{}", f))
+                .collect()
+        })
+        .unwrap_or_default();
+    docs.sort();
+    candidates.extend(docs);
+
+    candidates.into_iter().find_map(|file| {
+        let text = fs::read_to_string(dir.join(&file)).ok()?;
+        classify(&text).map(|l| (l.to_string(), file))
+    })
+}
+
+/// SPDX identifier of the license in `text` from phrases of the common
+/// licenses, most specific first
+fn classify(text: &str) -> Option<&'static str> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let upper = text.to_uppercase();
+    let has = |phrase: &str| upper.contains(&phrase.to_uppercase());
+    let version = |v: &str| has(&format!("Version {}", v));
+
+    let id = if has("GNU AFFERO GENERAL PUBLIC LICENSE") {
+        "AGPL-3.0"
+    } else if has("GNU LESSER GENERAL PUBLIC LICENSE") {
+        if version("3") {
+            "LGPL-3.0"
+        } else {
+            "LGPL-2.1"
+        }
+    } else if has("GNU GENERAL PUBLIC LICENSE") {
+        if version("3") {
+            "GPL-3.0"
+        } else {
+            "GPL-2.0"
+        }
+    } else if has("Apache License") && version("2.0") {
+        "Apache-2.0"
+    } else if has("Mozilla Public License") && version("2.0") {
+        "MPL-2.0"
+    } else if has("This is free and unencumbered software released into the public domain") {
+        "Unlicense"
+    } else if has("DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE") {
+        "WTFPL"
+    } else if has("Permission is hereby granted, free of charge") {
+        "MIT"
+    } else if has("Permission to use, copy, modify, and/or distribute this software") {
+        "ISC"
+    } else if has("Redistribution and use in source and binary forms") {
+        if has("Neither the name") {
+            "BSD-3-Clause"
+        } else {
+            "BSD-2-Clause"
+        }
+    } else if has("same terms as Vim") || has("VIM LICENSE") {
+        "Vim"
+    } else {
+        return None;
+    };
+    Some(id)
+}
+
+fn print_text(reports: &[Report]) {
+    let width = reports
+        .iter()
+        .map(|r| r.idname.chars().count())
+        .max()
+        .unwrap_or(0);
+    let license = |r: &Report| r.license.clone().unwrap_or_else(|| "unknown".to_string());
+    for r in reports {
+        println!(
+            "{:<width$} {:<12} {}",
+            r.idname,
+            license(r),
+            r.source.as_deref().unwrap_or("-"),
+            width = width
+        );
+    }
+
+    // how many packages use each license, most used first
+    let mut counts: Vec<(String, usize)> = vec![];
+    for r in reports {
+        let l = license(r);
+        match counts.iter_mut().find(|(name, _)| *name == l) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((l, 1)),
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let counts: Vec<String> = counts.iter().map(|(l, n)| format!("{} {}", l, n)).collect();
+    println!("\n{}", counts.join(", "));
+}
+
+fn to_json(reports: &[Report]) -> String {
+    let opt = |s: &Option<String>| s.as_deref().map_or("null".to_string(), json_string);
+    let packages: Vec<String> = reports
+        .iter()
+        .map(|r| {
+            format!(
+                "{{\"idname\": {}, \"remote\": {}, \"commit\": {}, \"license\": {}, \
+                 \"source\": {}}}",
+                json_string(&r.idname),
+                json_string(&r.remote),
+                opt(&r.commit),
+                opt(&r.license),
+                opt(&r.source)
+            )
+        })
+        .collect();
+    format!("{{\"packages\": [{}]}}", packages.join(", "))
+}
+
+/// An SPDX tag-value document with a package per plugin. Licenses are only
+/// declared, nothing is concluded from the files.
+fn to_spdx(reports: &[Report], created: &str) -> String {
+    let mut out = format!(
+        "SPDXVersion: SPDX-2.3\n\
+         DataLicense: CC0-1.0\n\
+         SPDXID: SPDXRef-DOCUMENT\n\
+         DocumentName: pac-packages\n\
+         DocumentNamespace: https://spdx.org/spdxdocs/pac-packages-{}\n\
+         Creator: Tool: pac-{}\n\
+         Created: {}\n",
+        created.replace(':', ""),
+        env!("CARGO_PKG_VERSION"),
+        created
+    );
+    for r in reports {
+        let id: String = r
+            .idname
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let location = match r.commit {
+            Some(ref commit) => format!("git+{}@{}", r.remote, commit),
+            None => "NOASSERTION".to_string(),
+        };
+        out += &format!(
+            "\nPackageName: {}\n\
+             SPDXID: SPDXRef-{}\n\
+             PackageDownloadLocation: {}\n\
+             FilesAnalyzed: false\n\
+             PackageLicenseConcluded: NOASSERTION\n\
+             PackageLicenseDeclared: {}\n\
+             PackageCopyrightText: NOASSERTION\n",
+            r.idname,
+            id,
+            location,
+            r.license.as_deref().unwrap_or("NOASSERTION")
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn licenses_classify() {
+        let mit = "MIT License\n\nPermission is hereby granted, free of\ncharge, to any person";
+        assert_eq!(classify(mit), Some("MIT"));
+        let gpl = "GNU GENERAL PUBLIC LICENSE\n   Version 3, 29 June 2007";
+        assert_eq!(classify(gpl), Some("GPL-3.0"));
+        let vim = "License: Distributable under the same terms as Vim itself.";
+        assert_eq!(classify(vim), Some("Vim"));
+        assert_eq!(classify("Just a plugin"), None);
+
+        let report = Report {
+            idname: "tpope/vim-surround".to_string(),
+            remote: "https://github.com/tpope/vim-surround".to_string(),
+            commit: Some("abc123".to_string()),
+            license: None,
+            source: None,
+        };
+        let spdx = to_spdx(&[report], &utc_timestamp(1_700_000_000));
+        assert!(spdx.contains("Created: 2023-11-14T22:13:20Z\n"));
+        assert!(spdx.contains("SPDXID: SPDXRef-tpope-vim-surround\n"));
+        assert!(spdx.contains(
+            "PackageDownloadLocation: git+https://github.com/tpope/vim-surround@abc123\n"
+        ));
+        assert!(spdx.contains("PackageLicenseDeclared: NOASSERTION\n"));
+    }
+}
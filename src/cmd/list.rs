@@ -1,14 +1,22 @@
-use crate::package::{self, Package};
+use crate::context::Context;
+use crate::fuzzy;
+use crate::package::{self, InstalledPackage, Package};
+use crate::registry::Registry;
+use crate::settings::Settings;
 use crate::Result;
 
 use clap::{value_t, ArgMatches};
+use std::io::{self, IsTerminal};
 
 #[derive(Debug)]
 struct ListArgs {
     start: bool,
     opt: bool,
     detached: bool,
+    tree: bool,
+    porcelain: bool,
     category: Option<String>,
+    query: Option<String>,
 }
 
 impl ListArgs {
@@ -17,58 +25,264 @@ impl ListArgs {
             start: m.is_present("start"),
             opt: m.is_present("opt"),
             detached: m.is_present("detached"),
+            tree: m.is_present("tree"),
+            porcelain: m.is_present("porcelain"),
             category: value_t!(m, "category", String).ok(),
+            query: value_t!(m, "query", String).ok(),
         }
     }
+
+    fn matches(&self, pack: &Package) -> bool {
+        let mut status = true;
+        if let Some(ref c) = self.category {
+            status &= &pack.category == c;
+        }
+        if self.start {
+            status &= !pack.opt;
+        }
+        if self.opt {
+            status &= pack.opt;
+        }
+        status
+    }
 }
 
-pub fn exec(matches: &ArgMatches) {
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
     let args = ListArgs::from_matches(matches);
 
-    if let Err(e) = list_packages(args) {
+    if let Err(e) = list_packages(ctx, args) {
         die!("Err: {}", e);
     }
 }
 
-fn list_packages(args: ListArgs) -> Result<()> {
-    let f = if args.detached {
-        list_detached
+fn list_packages(ctx: &Context, args: ListArgs) -> Result<()> {
+    if args.detached {
+        list_detached(ctx, &args)
+    } else if args.tree {
+        list_tree(ctx, &args)
+    } else if args.porcelain {
+        list_porcelain(ctx, &args)
     } else {
-        list_installed
+        list_installed(ctx, &args)
+    }
+}
+
+fn list_installed(ctx: &Context, args: &ListArgs) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    // packages installed by url may still have a description in the registry
+    let registry = Registry::load(ctx)?;
+
+    let colors = Settings::load(ctx)?.colors;
+    let colors = Some(&colors).filter(|_| io::stdout().is_terminal());
+    let packs: Vec<Package> = match args.query {
+        Some(ref q) => fuzzy::search(q, &packs).into_iter().cloned().collect(),
+        None => packs,
     };
-    f(&args.category, args.start, args.opt)
+    let installed = package::installed(ctx)?;
+    for mut p in packs.into_iter().filter(|p| args.matches(p)) {
+        if p.description.is_none() {
+            p.description = registry
+                .lookup_remote(&p.remote)
+                .and_then(|e| e.description.clone());
+        }
+        let path = p.path(ctx);
+        let dirty = installed.iter().any(|i| i.path == path && i.dirty);
+        println!(
+            "{}{}",
+            p.describe(colors),
+            if dirty { " [modified]" } else { "" }
+        );
+    }
+    Ok(())
 }
 
-fn list_installed(category: &Option<String>, start: bool, opt: bool) -> Result<()> {
-    let packs = package::fetch()?;
+/// `--porcelain`, the stable format for scripts. Reads paconfig and pack/
+/// only, like the rest of `pac list`, so it is safe next to a running pac.
+fn list_porcelain(ctx: &Context, args: &ListArgs) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    let packs: Vec<&Package> = match args.query {
+        Some(ref q) => fuzzy::search(q, &packs),
+        None => packs.iter().collect(),
+    };
+    let installed = package::installed(ctx)?;
+    for p in packs.into_iter().filter(|p| args.matches(p)) {
+        let path = p.path(ctx);
+        println!(
+            "{}",
+            porcelain_line(p, installed.iter().find(|i| i.path == path))
+        );
+    }
+    Ok(())
+}
 
-    let filter = |x: &Package| -> bool {
-        let mut status = true;
-        if let Some(ref c) = *category {
-            status &= &x.category == c;
+/// A package in version 1 of the porcelain format, its fields separated by
+/// tabs: idname, name, category, `start` or `opt`, `installed`, `missing` or
+/// `disabled`, `modified` or `clean`, the checked out commit, the pinned
+/// rev and the remote. Fields which don't apply are `-`. Version 1 only
+/// ever gets fields appended.
+fn porcelain_line(pack: &Package, installed: Option<&InstalledPackage>) -> String {
+    let field = |s: &str| s.replace(['\t', '\n'], " ");
+    let state = if !pack.enabled || pack.disabled {
+        "disabled"
+    } else if installed.is_some() {
+        "installed"
+    } else {
+        "missing"
+    };
+    let modified = match installed {
+        Some(i) if i.dirty => "modified",
+        Some(i) if i.head.is_some() => "clean",
+        _ => "-",
+    };
+    let commit = installed
+        .and_then(|i| i.head)
+        .map_or("-".to_string(), |h| h.to_string());
+    [
+        field(&pack.idname),
+        field(&pack.name),
+        field(&pack.category),
+        (if pack.opt { "opt" } else { "start" }).to_string(),
+        state.to_string(),
+        modified.to_string(),
+        commit,
+        field(pack.revision.as_deref().unwrap_or("-")),
+        field(&pack.remote),
+    ]
+    .join("\t")
+}
+
+fn list_tree(ctx: &Context, args: &ListArgs) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    for line in tree(&packs, |p| args.matches(p)) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Lines of the dependency tree: every category followed by its packages,
+/// each package followed by what it requires, indented one level deeper.
+fn tree<F: Fn(&Package) -> bool>(packs: &[Package], filter: F) -> Vec<String> {
+    fn walk<'a>(
+        packs: &'a [Package],
+        idname: &'a str,
+        depth: usize,
+        path: &mut Vec<&'a str>,
+        lines: &mut Vec<String>,
+    ) {
+        let indent = "  ".repeat(depth);
+        if path.contains(&idname) {
+            lines.push(format!("{}{} (cycle)", indent, idname));
+            return;
+        }
+        let pack = match packs.iter().find(|p| p.idname == idname) {
+            Some(p) => p,
+            None => {
+                lines.push(format!("{}{} (missing)", indent, idname));
+                return;
+            }
+        };
+        lines.push(format!("{}{}", indent, idname));
+        path.push(idname);
+        for dep in pack.requires.iter() {
+            walk(packs, dep, depth + 1, path, lines);
         }
-        if start {
-            status &= !x.opt;
+        path.pop();
+    }
+
+    let mut categories: Vec<&str> = packs.iter().map(|p| p.category.as_str()).collect();
+    categories.sort_unstable();
+    categories.dedup();
+
+    let mut lines = vec![];
+    for category in categories {
+        let members: Vec<&Package> = packs
+            .iter()
+            .filter(|p| p.category == category && filter(p))
+            .collect();
+        if members.is_empty() {
+            continue;
         }
-        if opt {
-            status &= x.opt;
+        lines.push(category.to_string());
+        for pack in members {
+            walk(packs, &pack.idname, 1, &mut vec![], &mut lines);
         }
-        status
-    };
+    }
+    lines
+}
 
-    for p in packs.into_iter().filter(filter) {
-        println!("{}", p);
+fn list_detached(ctx: &Context, args: &ListArgs) -> Result<()> {
+    let listed = package::fetch(ctx)?;
+    let pack_names: Vec<&str> = listed.iter().map(|p| &*p.name).collect();
+
+    let detached = package::installed(ctx)?.into_iter().filter(|p| {
+        args.category.as_ref().is_none_or(|c| *c == p.category)
+            && (!args.start || !p.opt)
+            && (!args.opt || p.opt)
+            && !pack_names.contains(&p.name.as_str())
+    });
+    for pack in detached {
+        // copied in by hand, pac can't update it
+        let note = if pack.head.is_none() {
+            " (not a git repository)"
+        } else {
+            ""
+        };
+        println!("{}{}", pack.relpath().display(), note);
     }
     Ok(())
 }
 
-fn list_detached(category: &Option<String>, start: bool, opt: bool) -> Result<()> {
-    let installed = package::fetch()?;
-    let pack_names: Vec<&str> = installed.iter().map(|p| &*p.name).collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    package::walk_packs(category, start, opt, |cate, option, name| {
-        if !pack_names.contains(&name) {
-            println!("{}/{}/{}", cate, option, name);
-        }
-    })
+    #[test]
+    fn list_tree_flags_cycles_and_missing() {
+        let pack = |remote: &str, category: &str, requires: &[&str]| {
+            let mut p = Package::new(&Package::name_from_remote(remote), remote, category, false);
+            p.requires = requires.iter().map(|r| r.to_string()).collect();
+            p
+        };
+        let packs = vec![
+            pack("https://github.com/a/one", "default", &["b/two"]),
+            pack("https://github.com/b/two", "lang", &["a/one", "c/gone"]),
+        ];
+        let lines = tree(&packs, |_| true);
+        let expected = vec![
+            "default",
+            "  a/one",
+            "    b/two",
+            "      a/one (cycle)",
+            "      c/gone (missing)",
+            "lang",
+            "  b/two",
+            "    a/one",
+            "      b/two (cycle)",
+            "    c/gone (missing)",
+        ];
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn list_porcelain_fields() {
+        let mut pack = Package::new("fzf", "https://github.com/junegunn/fzf", "default", true);
+        assert_eq!(
+            porcelain_line(&pack, None),
+            "junegunn/fzf\tfzf\tdefault\topt\tmissing\t-\t-\t-\thttps://github.com/junegunn/fzf"
+        );
+        pack.revision = Some("v0.4\t2".to_string());
+        let installed = InstalledPackage {
+            name: "fzf".to_string(),
+            category: "default".to_string(),
+            opt: true,
+            path: "/vim/pack/default/opt/fzf".into(),
+            head: git2::Oid::from_str("0123456789abcdef0123456789abcdef01234567").ok(),
+            dirty: true,
+        };
+        assert_eq!(
+            porcelain_line(&pack, Some(&installed)),
+            "junegunn/fzf\tfzf\tdefault\topt\tinstalled\tmodified\t\
+             0123456789abcdef0123456789abcdef01234567\tv0.4 2\thttps://github.com/junegunn/fzf"
+        );
+    }
 }
@@ -0,0 +1,27 @@
+use crate::context::Context;
+use crate::lockfile;
+use crate::notify;
+use crate::package;
+use crate::Result;
+
+pub fn exec(ctx: &Context) {
+    if let Err(e) = lock(ctx) {
+        die!("Err: {}", e);
+    }
+}
+
+/// Pin the enabled packages to what is checked out, packages which are not
+/// installed are left out of the lockfile
+fn lock(ctx: &Context) -> Result<()> {
+    let packs: Vec<_> = package::fetch(ctx)?
+        .into_iter()
+        .filter(|p| p.enabled)
+        .collect();
+    let locked = lockfile::save(ctx, &packs)?;
+    println!(
+        "Locked {} in {}",
+        notify::packages(locked),
+        ctx.lock_file().display()
+    );
+    Ok(())
+}
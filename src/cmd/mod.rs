@@ -1,6 +1,30 @@
+pub mod auth;
+pub mod category;
+pub mod env;
+pub mod exec;
+pub mod export;
 pub mod generate;
+pub mod graph;
+pub mod import;
+pub mod info;
 pub mod install;
+pub mod licenses;
 pub mod list;
+pub mod lock;
 pub mod move_cmd;
+pub mod outdated;
+pub mod registry;
+pub mod reinstall;
+pub mod report;
+pub mod schedule;
+pub mod sets;
+pub mod stale;
+pub mod stats;
+pub mod sync;
+pub mod sync_config;
+pub mod test;
+pub mod try_cmd;
 pub mod uninstall;
 pub mod update;
+pub mod vendor;
+pub mod why;
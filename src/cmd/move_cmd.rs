@@ -1,4 +1,5 @@
-use crate::package;
+use crate::context::Context;
+use crate::package::{self, Package};
 use crate::utils;
 use crate::{Error, Result};
 
@@ -8,7 +9,7 @@ use std::fs;
 #[derive(Debug)]
 struct MoveArgs {
     plugin: String,
-    category: String,
+    category: Option<String>,
     opt: bool,
 }
 
@@ -16,50 +17,74 @@ impl MoveArgs {
     fn from_matches(m: &ArgMatches) -> MoveArgs {
         MoveArgs {
             plugin: value_t!(m, "package", String).unwrap_or_default(),
-            category: value_t!(m, "category", String).unwrap_or_default(),
+            category: value_t!(m, "category", String).ok(),
             opt: m.is_present("opt"),
         }
     }
 }
 
-pub fn exec(matches: &ArgMatches) {
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
     let args = MoveArgs::from_matches(matches);
 
-    if let Err(e) = move_plugin(&args.plugin, &args.category, args.opt) {
+    if let Err(e) = move_plugin(ctx, &args.plugin, args.category.as_deref(), args.opt) {
         die!("{}", e);
     }
 }
 
-fn move_plugin(plugin: &str, category: &str, opt: bool) -> Result<()> {
-    let mut packs = package::fetch()?;
+/// Move to `category`, or within its category to opt/ if only `opt` is given
+fn move_plugin(ctx: &Context, plugin: &str, category: Option<&str>, opt: bool) -> Result<()> {
+    let mut packs = package::fetch(ctx)?;
     let changed = {
         let pack = match packs.iter_mut().find(|p| p.idname == plugin) {
             Some(p) => p,
             None => return Err(Error::plugin_not_installed(plugin)),
         };
-
-        let origin_path = pack.path();
-        if !origin_path.is_dir() {
-            return Err(Error::plugin_not_installed(plugin));
-        }
-
-        let path =
-            package::Package::new(plugin, &pack.remote, category, opt)
-                .path();
-        if origin_path != path {
-            utils::copy_directory(&origin_path, &path)?;
-            fs::remove_dir_all(&origin_path)?;
-            pack.set_category(category as &str);
-            pack.set_opt(opt);
-            true
-        } else {
-            false
-        }
+        let category = category.unwrap_or(&pack.category).to_string();
+        relocate(ctx, pack, &category, opt)?
     };
 
     if changed {
-        packs.sort_by(|a, b| a.idname.cmp(&b.idname));
-        package::save(packs)?;
+        package::sort(&mut packs);
+        package::save(ctx, packs)?;
+        package::prune_categories(ctx)?;
     }
     Ok(())
 }
+
+/// Move an installed package to `pack/<category>/<start|opt>`, returns
+/// whether anything moved. paconfig is left for the caller to save.
+pub fn relocate(ctx: &Context, pack: &mut Package, category: &str, opt: bool) -> Result<bool> {
+    package::validate_category(category)?;
+    let origin_path = pack.path(ctx);
+    if !origin_path.is_dir() {
+        return Err(Error::plugin_not_installed(&pack.idname));
+    }
+
+    let mut moved = pack.clone();
+    moved.set_category(category);
+    moved.set_opt(opt);
+    if origin_path == moved.path(ctx) {
+        return Ok(false);
+    }
+    move_entry(ctx, pack, &moved)?;
+    *pack = moved;
+    Ok(true)
+}
+
+/// Move the entry under pack/ of the installed `from` to where `to` goes
+pub fn move_entry(ctx: &Context, from: &Package, to: &Package) -> Result<()> {
+    let origin_path = from.path(ctx);
+    let path = to.path(ctx);
+    if path.exists() {
+        return Err(Error::plugin_installed(&path));
+    }
+    if from.is_linked(ctx) {
+        // only the link has to change, the clone stays in the store
+        from.unlink(ctx)?;
+        to.link(ctx)
+    } else {
+        utils::copy_directory(&origin_path, &path)?;
+        fs::remove_dir_all(&origin_path)?;
+        Ok(())
+    }
+}
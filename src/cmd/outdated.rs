@@ -0,0 +1,146 @@
+use crate::context::Context;
+use crate::git::{GitRepo, RemoteStatus};
+use crate::notify;
+use crate::package::{self, Package};
+use crate::refcache;
+use crate::source;
+use crate::state;
+use crate::task::{TaskManager, TaskType};
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+struct OutdatedArgs {
+    plugins: Vec<String>,
+    threads: Option<usize>,
+}
+
+impl OutdatedArgs {
+    fn from_matches(m: &ArgMatches) -> OutdatedArgs {
+        OutdatedArgs {
+            plugins: m.values_of_lossy("package").unwrap_or_default(),
+            threads: value_t!(m, "threads", usize).ok(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = OutdatedArgs::from_matches(matches);
+
+    let threads = args.threads.unwrap_or_else(num_cpus::get);
+    if threads < 1 {
+        die!("Threads should be greater than 0");
+    }
+
+    if let Err(e) = list_outdated(ctx, &args.plugins, threads) {
+        die!("Err: {}", e);
+    }
+}
+
+/// Check every package against its remote and list the ones with updates
+fn list_outdated(ctx: &Context, plugins: &[String], threads: usize) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+
+    let mut manager = TaskManager::new(ctx, TaskType::Check, threads);
+    for pack in packs
+        .iter()
+        .filter(|p| p.enabled && source::of(p).versioned())
+        .filter(|p| p.is_installed(ctx))
+    {
+        if plugins.is_empty() || plugins.contains(&pack.idname) {
+            manager.add(pack.clone());
+        }
+    }
+
+    let outdated = Arc::new(Mutex::new(Vec::new()));
+    let tracking = Arc::new(Mutex::new(Vec::new()));
+    let forks = Arc::new(Mutex::new(Vec::new()));
+    let checker = {
+        let outdated = outdated.clone();
+        let tracking = tracking.clone();
+        let forks = forks.clone();
+        move |ctx: &Context, pack: &Package| {
+            let res = pack.git_remote_status(ctx).map(|status| match status {
+                RemoteStatus::Outdated { local, remote } => {
+                    let mut o = outdated.lock().unwrap();
+                    o.push((pack.idname.clone(), local, remote));
+                }
+                RemoteStatus::Tracking {
+                    branch,
+                    local,
+                    remote,
+                    behind,
+                } => {
+                    let mut t = tracking.lock().unwrap();
+                    t.push((pack.idname.clone(), branch, local, remote, behind));
+                }
+                _ => (),
+            });
+            // a fork is compared with its origin above and with the repo it
+            // was forked from here
+            let res = match res {
+                Ok(()) if pack.fork.is_some() => pack.git_upstream(ctx).map(|u| {
+                    let mut f = forks.lock().unwrap();
+                    f.push((pack.idname.clone(), u.ahead, u.behind));
+                }),
+                res => res,
+            };
+            (res, false)
+        }
+    };
+    let fails = manager.run(checker);
+    if let Err(e) = refcache::save(ctx) {
+        log::info!("fail to save the ref cache: {}", e);
+    }
+
+    let mut outdated = outdated.lock().unwrap();
+    outdated.sort();
+    let mut tracking = tracking.lock().unwrap();
+    tracking.sort();
+    if outdated.is_empty() && tracking.is_empty() {
+        println!("All packages are up to date");
+    }
+    for (idname, local, remote) in outdated.iter() {
+        println!("{} {:.7} -> {:.7}", idname, local, remote);
+    }
+    // updates keep these at their rev, they are not counted as updates
+    for (idname, branch, local, remote, behind) in tracking.iter() {
+        println!(
+            "{} {:.7} (pinned), {} is {} commit{} ahead at {:.7}",
+            idname,
+            local,
+            branch,
+            behind,
+            if *behind == 1 { "" } else { "s" },
+            remote
+        );
+    }
+    let mut forks = forks.lock().unwrap();
+    forks.sort();
+    for (idname, ahead, behind) in forks.iter() {
+        let state = match (ahead, behind) {
+            (0, 0) => "even with upstream".to_string(),
+            (a, 0) => format!("{} ahead of upstream", a),
+            (0, b) => format!(
+                "{} behind upstream, `pac update --from upstream {}`",
+                b, idname
+            ),
+            (a, b) => format!("{} ahead of and {} behind upstream", a, b),
+        };
+        println!("{} (fork) {}", idname, state);
+    }
+    let available = match outdated.len() {
+        0 => None,
+        1 => Some("1 plugin update available".to_string()),
+        n => Some(format!("{} plugin updates available", n)),
+    };
+    notify::report(available, &fails);
+    if plugins.is_empty() {
+        if let Err(e) = state::record_check(ctx, outdated.len()) {
+            log::info!("fail to save sync state: {}", e);
+        }
+    }
+    Ok(())
+}
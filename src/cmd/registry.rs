@@ -0,0 +1,42 @@
+use crate::context::Context;
+use crate::registry::Registry;
+use crate::Result;
+
+use clap::ArgMatches;
+
+#[derive(Debug)]
+struct UpdateArgs {
+    urls: Vec<String>,
+}
+
+impl UpdateArgs {
+    fn from_matches(m: &ArgMatches) -> UpdateArgs {
+        UpdateArgs {
+            urls: m.values_of_lossy("url").unwrap_or_default(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let res = match matches.subcommand() {
+        ("update", Some(m)) => update_registry(ctx, UpdateArgs::from_matches(m)),
+        _ => list_registry(ctx),
+    };
+    if let Err(e) = res {
+        die!("Err: {}", e);
+    }
+}
+
+fn update_registry(ctx: &Context, args: UpdateArgs) -> Result<()> {
+    let mut registry = Registry::load(ctx)?;
+    registry.update(args.urls)?;
+    println!("Registry updated with {} packages", registry.entries.len());
+    registry.save(ctx)
+}
+
+fn list_registry(ctx: &Context) -> Result<()> {
+    for e in Registry::load(ctx)?.entries {
+        println!("{} => {}", e.name, e.remote);
+    }
+    Ok(())
+}
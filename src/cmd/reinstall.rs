@@ -0,0 +1,141 @@
+use crate::cmd::install;
+use crate::context::Context;
+use crate::notify;
+use crate::package::{self, Package};
+use crate::prompt;
+use crate::task::{TaskManager, TaskType};
+use crate::{Error, Result};
+
+use clap::{value_t, ArgMatches};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+struct ReinstallArgs {
+    plugins: Vec<String>,
+    threads: Option<usize>,
+    build_threads: Option<usize>,
+}
+
+impl ReinstallArgs {
+    fn from_matches(m: &ArgMatches) -> ReinstallArgs {
+        ReinstallArgs {
+            plugins: m.values_of_lossy("package").unwrap_or_default(),
+            threads: value_t!(m, "threads", usize).ok(),
+            build_threads: value_t!(m, "build-threads", usize).ok(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = ReinstallArgs::from_matches(matches);
+    let threads = args.threads.unwrap_or_else(num_cpus::get);
+    let build_threads = args.build_threads.unwrap_or_else(num_cpus::get);
+    if threads < 1 || build_threads < 1 {
+        die!("Threads should be greater than 0");
+    }
+
+    if let Err(e) = reinstall_plugins(ctx, &args.plugins, threads, build_threads) {
+        die!("Err: {}", e);
+    }
+}
+
+/// Where a checkout waits while its package is cloned again, next to it so
+/// moving it is a rename
+fn aside(repo: &Path) -> PathBuf {
+    let mut name = repo.file_name().unwrap_or_default().to_os_string();
+    name.push(".pac-reinstall");
+    repo.with_file_name(name)
+}
+
+/// Clone the packages again as paconfig has them, at their rev and with
+/// their build commands. The old checkouts are moved aside until then and
+/// put back for the packages which fail, so a failed reinstall leaves them
+/// as they were.
+fn reinstall_plugins(
+    ctx: &Context,
+    plugins: &[String],
+    threads: usize,
+    build_threads: usize,
+) -> Result<()> {
+    let mut packs = package::fetch(ctx)?;
+    let chosen = plugins
+        .iter()
+        .map(|plugin| match packs.iter().find(|p| p.idname == *plugin) {
+            Some(p) => Ok(p.clone()),
+            None => Err(Error::plugin_not_installed(plugin)),
+        })
+        .collect::<Result<Vec<Package>>>()?;
+    if !confirm(plugins, false)? {
+        return Ok(());
+    }
+
+    let mut manager = TaskManager::new(ctx, TaskType::Install, threads);
+    manager.build_threads(build_threads);
+    let mut moved: Vec<(PathBuf, PathBuf)> = vec![];
+    for pack in &chosen {
+        if let Err(e) = set_aside(ctx, pack, &mut moved) {
+            restore(ctx, &chosen, &moved)?;
+            return Err(e);
+        }
+        manager.add(pack.clone());
+    }
+
+    let fails = manager.run(install::install_plugin);
+    restore(ctx, &chosen, &moved)?;
+    notify::report(
+        Some(format!(
+            "Reinstalled {}",
+            notify::packages(chosen.len() - fails.len())
+        )),
+        &fails,
+    );
+
+    package::sort(&mut packs);
+    package::update_pac_plugin(ctx, &packs)
+}
+
+/// Ask before cloning `plugins` again, their files are replaced. `asks` as
+/// for `prompt::confirm`.
+pub fn confirm(plugins: &[String], asks: bool) -> Result<bool> {
+    let question = format!(
+        "Reinstall {}? Changes to their files are lost",
+        plugins.join(", ")
+    );
+    prompt::confirm(&question, asks)
+}
+
+/// Move the checkout of `pack` aside so it is cloned again, adding it to
+/// `moved` for `restore`. Packages sharing a clone get it back with the
+/// first of them.
+pub fn set_aside(ctx: &Context, pack: &Package, moved: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
+    let repo = pack.repo_path(ctx);
+    if moved.iter().any(|(r, _)| *r == repo) || !repo.is_dir() {
+        return Ok(());
+    }
+    let backup = aside(&repo);
+    if backup.exists() {
+        fs::remove_dir_all(&backup)?;
+    }
+    fs::rename(&repo, &backup)?;
+    moved.push((repo, backup));
+    Ok(())
+}
+
+/// Drop the old checkouts which were cloned again and put back the others,
+/// linking packages to them again if an interrupted run unlinked them
+pub fn restore(ctx: &Context, chosen: &[Package], moved: &[(PathBuf, PathBuf)]) -> Result<()> {
+    for (repo, backup) in moved {
+        if repo.is_dir() {
+            fs::remove_dir_all(backup)?;
+        } else {
+            fs::rename(backup, repo)?;
+        }
+    }
+    for pack in chosen {
+        if !pack.path(ctx).exists() && pack.store_path(ctx).is_dir() {
+            pack.link(ctx)?;
+        }
+    }
+    Ok(())
+}
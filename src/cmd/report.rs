@@ -0,0 +1,268 @@
+use crate::context::Context;
+use crate::git;
+use crate::package::{self, Package};
+use crate::registry::Registry;
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+
+#[derive(Debug)]
+struct ReportArgs {
+    format: String,
+}
+
+impl ReportArgs {
+    fn from_matches(m: &ArgMatches) -> ReportArgs {
+        ReportArgs {
+            format: value_t!(m, "format", String).unwrap_or_else(|_| "markdown".to_string()),
+        }
+    }
+}
+
+/// What the report shows of a package
+#[derive(Debug)]
+struct Entry {
+    idname: String,
+    /// Page of the plugin, for remotes which can be opened in a browser
+    url: Option<String>,
+    /// Tag or commit checked out, `None` if it is not installed
+    version: Option<String>,
+    description: Option<String>,
+    category: String,
+    /// When vim loads it, the `:` commands and filetypes of lazy packages
+    loading: Loading,
+}
+
+#[derive(Debug, PartialEq)]
+enum Loading {
+    Startup,
+    /// Only loaded with `:packadd`
+    Manual,
+    Lazy {
+        commands: Vec<String>,
+        types: Vec<String>,
+    },
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = ReportArgs::from_matches(matches);
+
+    if let Err(e) = report(ctx, &args) {
+        die!("Err: {}", e);
+    }
+}
+
+fn report(ctx: &Context, args: &ReportArgs) -> Result<()> {
+    let registry = Registry::load(ctx)?;
+    let entries: Vec<Entry> = package::fetch(ctx)?
+        .iter()
+        .filter(|p| p.enabled && !p.disabled)
+        .map(|p| {
+            let mut entry = entry(p);
+            if p.is_installed(ctx) {
+                let version = git::describe(&p.repo_path(ctx));
+                entry.version = Some(version.unwrap_or_else(|| "unknown".to_string()));
+            }
+            // packages installed by url may still be described by the registry
+            entry.description = entry.description.or_else(|| {
+                registry
+                    .lookup_remote(&p.remote)
+                    .and_then(|e| e.description.clone())
+            });
+            entry
+        })
+        .collect();
+    match args.format.as_str() {
+        "html" => print!("{}", to_html(&entries)),
+        _ => print!("{}", to_markdown(&entries)),
+    }
+    Ok(())
+}
+
+fn entry(pack: &Package) -> Entry {
+    let url = Some(pack.remote.trim_end_matches(".git"))
+        .filter(|r| r.starts_with("https://") || r.starts_with("http://"))
+        .map(String::from);
+    let loading = if pack.is_lazy() {
+        Loading::Lazy {
+            commands: pack.load_commands.clone(),
+            types: pack.for_types.clone(),
+        }
+    } else if pack.opt {
+        Loading::Manual
+    } else {
+        Loading::Startup
+    };
+    Entry {
+        idname: pack.idname.clone(),
+        url,
+        version: None,
+        description: pack.description.clone(),
+        category: pack.category.clone(),
+        loading,
+    }
+}
+
+/// The categories in the order their first packages come in
+fn categories(entries: &[Entry]) -> Vec<&str> {
+    let mut categories: Vec<&str> = vec![];
+    for e in entries {
+        if !categories.contains(&e.category.as_str()) {
+            categories.push(&e.category);
+        }
+    }
+    categories
+}
+
+/// How the package is loaded, with `code` wrapping commands and filetypes
+fn describe_loading(loading: &Loading, code: fn(&str) -> String) -> String {
+    match loading {
+        Loading::Startup => "at startup".to_string(),
+        Loading::Manual => format!("with {}", code(":packadd")),
+        Loading::Lazy { commands, types } => {
+            let mut rules = vec![];
+            if !commands.is_empty() {
+                let commands: Vec<String> =
+                    commands.iter().map(|c| code(&format!(":{}", c))).collect();
+                rules.push(format!("on {}", commands.join(", ")));
+            }
+            if !types.is_empty() {
+                let types: Vec<String> = types.iter().map(|t| code(t)).collect();
+                rules.push(format!("for {}", types.join(", ")));
+            }
+            rules.join("; ")
+        }
+    }
+}
+
+fn summary(entries: &[Entry]) -> String {
+    let lazy = entries
+        .iter()
+        .filter(|e| e.loading != Loading::Startup)
+        .count();
+    format!(
+        "{} plugins, {} of them loaded at startup. Generated by pac {}.",
+        entries.len(),
+        entries.len() - lazy,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+fn to_markdown(entries: &[Entry]) -> String {
+    let cell = |s: &str| s.replace('|', "\\|").replace('\n', " ");
+    let code = |s: &str| format!("`{}`", s);
+    let mut out = format!("# Vim plugins\n\n{}\n", summary(entries));
+    for category in categories(entries) {
+        out += &format!(
+            "\n## {}\n\n| Plugin | Version | Loaded | Description |\n| --- | --- | --- | --- |\n",
+            category
+        );
+        for e in entries.iter().filter(|e| e.category == category) {
+            let name = match e.url {
+                Some(ref url) => format!("[{}]({})", e.idname, url),
+                None => e.idname.clone(),
+            };
+            out += &format!(
+                "| {} | {} | {} | {} |\n",
+                name,
+                e.version
+                    .as_deref()
+                    .map_or("not installed".to_string(), code),
+                describe_loading(&e.loading, code),
+                cell(e.description.as_deref().unwrap_or(""))
+            );
+        }
+    }
+    out
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A standalone page with a table per category
+fn to_html(entries: &[Entry]) -> String {
+    let code = |s: &str| format!("<code>{}</code>", html_escape(s));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Vim plugins</title>\n</head>\n<body>\n<h1>Vim plugins</h1>\n<p>{}</p>\n",
+        html_escape(&summary(entries))
+    );
+    for category in categories(entries) {
+        out += &format!(
+            "<h2>{}</h2>\n<table>\n<tr><th>Plugin</th><th>Version</th><th>Loaded</th>\
+             <th>Description</th></tr>\n",
+            html_escape(category)
+        );
+        for e in entries.iter().filter(|e| e.category == category) {
+            let name = match e.url {
+                Some(ref url) => format!(
+                    "<a href=\"{}\">{}</a>",
+                    html_escape(url),
+                    html_escape(&e.idname)
+                ),
+                None => html_escape(&e.idname),
+            };
+            out += &format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                name,
+                e.version
+                    .as_deref()
+                    .map_or("not installed".to_string(), code),
+                describe_loading(&e.loading, code),
+                html_escape(e.description.as_deref().unwrap_or(""))
+            );
+        }
+        out += "</table>\n";
+    }
+    out + "</body>\n</html>\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_markdown() {
+        let mut go = Package::new(
+            "vim-go",
+            "https://github.com/fatih/vim-go.git",
+            "lang",
+            true,
+        );
+        go.load_commands = vec!["GoBuild".to_string()];
+        go.for_types = vec!["go".to_string()];
+        let mut surround = Package::new(
+            "vim-surround",
+            "git@example.com:tpope/vim-surround",
+            "default",
+            false,
+        );
+        surround.description = Some("quoting | parenthesizing".to_string());
+        let mut entries = vec![entry(&go), entry(&surround)];
+        entries[0].version = Some("v1.28-4-gabc1234".to_string());
+
+        assert_eq!(
+            to_markdown(&entries),
+            format!(
+                "# Vim plugins\n\n2 plugins, 1 of them loaded at startup. Generated by pac {}.\n\
+                 \n## lang\n\n| Plugin | Version | Loaded | Description |\n\
+                 | --- | --- | --- | --- |\n\
+                 | [fatih/vim-go](https://github.com/fatih/vim-go) | `v1.28-4-gabc1234` | \
+                 on `:GoBuild`; for `go` |  |\n\
+                 \n## default\n\n| Plugin | Version | Loaded | Description |\n\
+                 | --- | --- | --- | --- |\n\
+                 | tpope/vim-surround | not installed | at startup | \
+                 quoting \\| parenthesizing |\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        assert!(to_html(&entries).contains(
+            "<tr><td>tpope/vim-surround</td><td>not installed</td><td>at startup</td>\
+             <td>quoting | parenthesizing</td></tr>\n"
+        ));
+    }
+}
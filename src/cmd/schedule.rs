@@ -0,0 +1,262 @@
+use crate::context::Context;
+use crate::{Error, Result};
+
+use clap::ArgMatches;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Name of the systemd units and the launchd label
+const UNIT: &str = "pac-update";
+const LABEL: &str = "io.github.pac.update";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Interval {
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug)]
+struct ScheduleArgs {
+    interval: Interval,
+    update: bool,
+    notify: bool,
+    print: bool,
+    remove: bool,
+}
+
+impl ScheduleArgs {
+    fn from_matches(m: &ArgMatches) -> ScheduleArgs {
+        ScheduleArgs {
+            interval: if m.is_present("daily") {
+                Interval::Daily
+            } else {
+                Interval::Weekly
+            },
+            update: m.is_present("update"),
+            notify: m.is_present("notify"),
+            print: m.is_present("print"),
+            remove: m.is_present("remove"),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = ScheduleArgs::from_matches(matches);
+
+    let res = if args.remove {
+        remove()
+    } else {
+        schedule(ctx, &args)
+    };
+    if let Err(e) = res {
+        die!("Err: {}", e);
+    }
+}
+
+/// The pac invocation run by the timer, with the paths of this run so that
+/// it manages the same directories
+fn command(ctx: &Context, args: &ScheduleArgs) -> Result<Vec<String>> {
+    let exe = env::current_exe()?;
+    let root = ctx.pack_dir.parent().unwrap_or(&ctx.pack_dir);
+    let mut cmd = vec![
+        exe.display().to_string(),
+        "--ui".to_string(),
+        "simple".to_string(),
+        "--root".to_string(),
+        root.display().to_string(),
+        "--config".to_string(),
+        ctx.config_file.display().to_string(),
+    ];
+    if args.notify {
+        cmd.push("--notify".to_string());
+    }
+    cmd.push(if args.update { "update" } else { "outdated" }.to_string());
+    Ok(cmd)
+}
+
+fn schedule(ctx: &Context, args: &ScheduleArgs) -> Result<()> {
+    let cmd = command(ctx, args)?;
+    let files = if cfg!(target_os = "macos") {
+        vec![(
+            launchd_dir()?.join(format!("{}.plist", LABEL)),
+            launchd_plist(&cmd, args.interval),
+        )]
+    } else {
+        let (service, timer) = systemd_units(&cmd, args.interval);
+        let dir = systemd_dir()?;
+        vec![
+            (dir.join(format!("{}.service", UNIT)), service),
+            (dir.join(format!("{}.timer", UNIT)), timer),
+        ]
+    };
+
+    if args.print {
+        for (path, content) in files {
+            println!("# {}\n{}", path.display(), content);
+        }
+        return Ok(());
+    }
+    for (path, content) in &files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+        println!("Wrote {}", path.display());
+    }
+    if cfg!(target_os = "macos") {
+        let plist = files[0].0.display().to_string();
+        // reloading picks up a changed plist
+        let _ = run("launchctl", &["unload", &plist]);
+        run("launchctl", &["load", "-w", &plist])?;
+    } else {
+        run("systemctl", &["--user", "daemon-reload"])?;
+        run(
+            "systemctl",
+            &["--user", "enable", "--now", &format!("{}.timer", UNIT)],
+        )?;
+    }
+    println!("Scheduled `{}`", cmd[1..].join(" "));
+    Ok(())
+}
+
+fn remove() -> Result<()> {
+    let files = if cfg!(target_os = "macos") {
+        let plist = launchd_dir()?.join(format!("{}.plist", LABEL));
+        let _ = run("launchctl", &["unload", "-w", &plist.display().to_string()]);
+        vec![plist]
+    } else {
+        let timer = format!("{}.timer", UNIT);
+        let _ = run("systemctl", &["--user", "disable", "--now", &timer]);
+        let dir = systemd_dir()?;
+        vec![dir.join(format!("{}.service", UNIT)), dir.join(timer)]
+    };
+    for path in files.iter().filter(|p| p.is_file()) {
+        fs::remove_file(path)?;
+        println!("Removed {}", path.display());
+    }
+    Ok(())
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program).args(args).status()?;
+    if !status.success() {
+        return Err(Error::command(format!("{} {}", program, args.join(" "))));
+    }
+    Ok(())
+}
+
+fn home() -> Result<PathBuf> {
+    dirs::home_dir().ok_or_else(|| Error::command("No home directory found"))
+}
+
+/// `$XDG_CONFIG_HOME/systemd/user`
+fn systemd_dir() -> Result<PathBuf> {
+    let config = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+        _ => home()?.join(".config"),
+    };
+    Ok(config.join("systemd").join("user"))
+}
+
+fn launchd_dir() -> Result<PathBuf> {
+    Ok(home()?.join("Library").join("LaunchAgents"))
+}
+
+/// Words are quoted for systemd's own command line splitting, `%` would
+/// start a specifier
+fn systemd_units(cmd: &[String], interval: Interval) -> (String, String) {
+    let exec = cmd
+        .iter()
+        .map(|w| {
+            let w = w.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("\"{}\"", w.replace('%', "%%"))
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let service = format!(
+        "[Unit]\n\
+         Description=pac {}\n\n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={}\n",
+        cmd.last().map_or("", String::as_str),
+        exec
+    );
+    let calendar = match interval {
+        Interval::Daily => "daily",
+        Interval::Weekly => "weekly",
+    };
+    let timer = format!(
+        "[Unit]\n\
+         Description=Run pac {}\n\n\
+         [Timer]\n\
+         OnCalendar={}\n\
+         Persistent=true\n\
+         RandomizedDelaySec=1h\n\n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        calendar, calendar
+    );
+    (service, timer)
+}
+
+fn launchd_plist(cmd: &[String], interval: Interval) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let args: String = cmd
+        .iter()
+        .map(|w| format!("        <string>{}</string>\n", escape(w)))
+        .collect();
+    // weekly runs on mondays
+    let weekday = match interval {
+        Interval::Daily => "",
+        Interval::Weekly => "        <key>Weekday</key>\n        <integer>1</integer>\n",
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">
+<plist version=\"1.0\">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+{}    </array>
+    <key>StartCalendarInterval</key>
+    <dict>
+{}        <key>Hour</key>
+        <integer>9</integer>
+    </dict>
+</dict>
+</plist>
+",
+        LABEL, args, weekday
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_units() {
+        let cmd = vec![
+            "/bin/pac".to_string(),
+            "--root".to_string(),
+            "/my vim".to_string(),
+        ];
+        let (service, timer) = systemd_units(&cmd, Interval::Weekly);
+        assert!(service.contains("ExecStart=\"/bin/pac\" \"--root\" \"/my vim\"\n"));
+        assert!(timer.contains("OnCalendar=weekly\n"));
+
+        let plist = launchd_plist(&cmd, Interval::Daily);
+        assert!(plist.contains("<string>/my vim</string>"));
+        assert!(!plist.contains("Weekday"));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::cmd::move_cmd;
+use crate::context::Context;
+use crate::notify;
+use crate::package;
+use crate::sets::Sets;
+use crate::{Error, Result};
+
+use clap::{value_t, ArgMatches};
+use yaml_rust::Yaml;
+
+pub fn exec(ctx: &Context) {
+    if let Err(e) = list(ctx) {
+        die!("Err: {}", e);
+    }
+}
+
+/// `pac enable-set` and `pac disable-set`
+pub fn toggle(ctx: &Context, matches: &ArgMatches, enable: bool) {
+    let name = value_t!(matches, "set", String).unwrap();
+    if let Err(e) = set_enabled(ctx, &name, enable) {
+        die!("Err: {}", e);
+    }
+}
+
+fn list(ctx: &Context) -> Result<()> {
+    let sets = Sets::load(ctx)?;
+    if sets.sets.is_empty() {
+        println!("No package sets in {}", ctx.config_file.display());
+        return Ok(());
+    }
+    let width = sets.sets.iter().map(|(n, _)| n.len()).max().unwrap_or(0);
+    for (name, members) in &sets.sets {
+        let state = if sets.is_disabled(name) {
+            "disabled"
+        } else {
+            "enabled"
+        };
+        println!(
+            "{:<width$} {:<8} {}",
+            name,
+            state,
+            members.join(", "),
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// Record the set as enabled or disabled, move the packages whose state
+/// changes between start/ and opt/ and regenerate the loader. A package
+/// stays disabled while any of its sets is.
+fn set_enabled(ctx: &Context, name: &str, enable: bool) -> Result<()> {
+    let mut sets = Sets::load(ctx)?;
+    let members = match sets.members(name) {
+        Some(m) => m.to_vec(),
+        None => return Err(Error::set(format!("no set named `{}` in paconfig", name))),
+    };
+    let state = if enable { "enabled" } else { "disabled" };
+    if sets.is_disabled(name) != enable {
+        println!("Set {} is already {}", name, state);
+        return Ok(());
+    }
+    if enable {
+        sets.disabled.retain(|n| n != name);
+    } else {
+        sets.disabled.push(name.to_string());
+    }
+
+    let mut packs = package::fetch(ctx)?;
+    for m in &members {
+        if !packs.iter().any(|p| p.idname == *m || p.name == *m) {
+            println!("{} of set {} is not in paconfig", m, name);
+        }
+    }
+    let mut moved = 0;
+    for pack in packs.iter_mut() {
+        let disabled = sets.disables(pack);
+        if disabled == pack.disabled {
+            continue;
+        }
+        let from = pack.clone();
+        pack.disabled = disabled;
+        if from.is_installed(ctx) && from.path(ctx) != pack.path(ctx) {
+            move_cmd::move_entry(ctx, &from, pack)?;
+            moved += 1;
+        }
+    }
+
+    let disabled = if sets.disabled.is_empty() {
+        None
+    } else {
+        Some(Yaml::Array(
+            sets.disabled.iter().cloned().map(Yaml::String).collect(),
+        ))
+    };
+    package::save_section(ctx, "disabled_sets", disabled)?;
+    package::update_pac_plugin(ctx, &packs)?;
+    println!("Set {} {}, moved {}", name, state, notify::packages(moved));
+    Ok(())
+}
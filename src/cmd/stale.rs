@@ -0,0 +1,149 @@
+use crate::context::Context;
+use crate::git::{GitRepo, Upstream};
+use crate::package::{self, Package};
+use crate::source;
+use crate::task::{TaskManager, TaskType};
+use crate::utils::utc_timestamp;
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug)]
+struct StaleArgs {
+    plugins: Vec<String>,
+    threads: Option<usize>,
+}
+
+impl StaleArgs {
+    fn from_matches(m: &ArgMatches) -> StaleArgs {
+        StaleArgs {
+            plugins: m.values_of_lossy("package").unwrap_or_default(),
+            threads: value_t!(m, "threads", usize).ok(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = StaleArgs::from_matches(matches);
+
+    let threads = args.threads.unwrap_or_else(num_cpus::get);
+    if threads < 1 {
+        die!("Threads should be greater than 0");
+    }
+
+    if let Err(e) = list_stale(ctx, &args.plugins, threads) {
+        die!("Err: {}", e);
+    }
+}
+
+/// Packages with the date of their last upstream commit, least recently
+/// changed first
+fn list_stale(ctx: &Context, plugins: &[String], threads: usize) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+
+    let mut manager = TaskManager::new(ctx, TaskType::Check, threads);
+    for pack in packs
+        .iter()
+        .filter(|p| p.enabled && source::of(p).versioned())
+        .filter(|p| p.is_installed(ctx))
+    {
+        if plugins.is_empty() || plugins.contains(&pack.idname) {
+            manager.add(pack.clone());
+        }
+    }
+
+    let found = Arc::new(Mutex::new(Vec::new()));
+    let checker = {
+        let found = found.clone();
+        move |ctx: &Context, pack: &Package| {
+            let res = pack.git_upstream(ctx).map(|upstream| {
+                let mut f = found.lock().unwrap();
+                f.push((pack.idname.clone(), upstream));
+            });
+            (res, false)
+        }
+    };
+    manager.run(checker);
+
+    let mut found = found.lock().unwrap();
+    found.sort_by_key(|(idname, u)| (u.last_commit, idname.clone()));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    print!("{}", table(&found, now));
+    Ok(())
+}
+
+/// `3 days`, `5 months` or `2 years`
+fn age(secs: i64) -> String {
+    let days = secs.max(0) / 86400;
+    let (n, unit) = match days {
+        0..=59 => (days, "day"),
+        60..=729 => (days / 30, "month"),
+        _ => (days / 365, "year"),
+    };
+    format!("{} {}{}", n, unit, if n == 1 { "" } else { "s" })
+}
+
+fn table(found: &[(String, Upstream)], now: i64) -> String {
+    let width = found
+        .iter()
+        .map(|(idname, _)| idname.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("package".len());
+    let mut out = format!(
+        "{:<width$} {:<11} {:>10} {:>7} {:>10}\n",
+        "package",
+        "last commit",
+        "age",
+        "behind",
+        "lagging",
+        width = width
+    );
+    for (idname, u) in found {
+        // how much older the checkout is than the tip
+        let lagging = if u.behind == 0 {
+            "-".to_string()
+        } else {
+            age(u.last_commit - u.checked_out)
+        };
+        out += &format!(
+            "{:<width$} {:<11} {:>10} {:>7} {:>10}\n",
+            idname,
+            &utc_timestamp(u.last_commit)[..10],
+            age(now - u.last_commit),
+            u.behind,
+            lagging,
+            width = width
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_table() {
+        assert_eq!(age(86400), "1 day");
+        assert_eq!(age(90 * 86400), "3 months");
+        assert_eq!(age(800 * 86400), "2 years");
+
+        let day = 86400;
+        let u = Upstream {
+            last_commit: 1_700_000_000,
+            checked_out: 1_700_000_000 - 10 * day,
+            behind: 4,
+            ahead: 0,
+        };
+        let out = table(&[("me/plugin".to_string(), u)], 1_700_000_000 + 400 * day);
+        assert_eq!(
+            out.lines().nth(1),
+            Some("me/plugin 2023-11-14   13 months       4    10 days")
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::context::Context;
+use crate::stats::{self, Counts, Phase, Run, Sample, Tally};
+use crate::utils::{self, format_duration};
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+use std::time::Duration;
+
+#[derive(Debug)]
+struct StatsArgs {
+    runs: Option<usize>,
+    top: usize,
+}
+
+impl StatsArgs {
+    fn from_matches(m: &ArgMatches) -> StatsArgs {
+        StatsArgs {
+            runs: value_t!(m, "runs", usize).ok(),
+            top: value_t!(m, "top", usize).unwrap_or(10),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = StatsArgs::from_matches(matches);
+
+    if let Err(e) = show_stats(ctx, args) {
+        die!("Err: {}", e);
+    }
+}
+
+fn show_stats(ctx: &Context, args: StatsArgs) -> Result<()> {
+    let counts = stats::load_counts(ctx)?;
+    let mut runs = stats::load(ctx)?;
+    if let Some(n) = args.runs {
+        runs.drain(..runs.len().saturating_sub(n));
+    }
+    if runs.is_empty() && counts.packages.is_empty() {
+        println!("No runs recorded yet, stats are kept for install and update");
+        return Ok(());
+    }
+    if !counts.packages.is_empty() {
+        for line in count_lines(&counts, args.top) {
+            println!("{}", line);
+        }
+        if runs.is_empty() {
+            return Ok(());
+        }
+        println!();
+    }
+
+    let totals = cumulative(&runs);
+    let width = totals
+        .iter()
+        .take(args.top)
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("package".len());
+
+    let all: Duration = totals.iter().map(|(_, t)| t.iter().sum::<Duration>()).sum();
+    println!(
+        "{} runs, {} in total\n",
+        runs.len(),
+        format_duration(all).trim()
+    );
+
+    print!("{:<width$}", "package", width = width);
+    for phase in Phase::ALL.iter() {
+        print!(" {:>8}", phase.as_str());
+    }
+    println!(" {:>8}", "total");
+    for (name, times) in totals.iter().take(args.top) {
+        print!("{:<width$}", name, width = width);
+        for t in times.iter() {
+            print!(" {:>8}", format_duration(*t).trim());
+        }
+        println!(" {:>8}", format_duration(times.iter().sum()).trim());
+    }
+
+    println!("\nSlowest single steps");
+    let mut samples: Vec<(&Run, &Sample)> = runs
+        .iter()
+        .flat_map(|r| r.samples.iter().map(move |s| (r, s)))
+        .collect();
+    samples.sort_by_key(|(_, s)| std::cmp::Reverse(s.duration));
+    for (run, s) in samples.into_iter().take(args.top) {
+        println!(
+            "{:<width$} {:>8} {:>8}  ({})",
+            s.idname,
+            s.phase.as_str(),
+            format_duration(s.duration).trim(),
+            run.command,
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// All time installs, updates and failures, with the packages which failed
+/// most often
+fn count_lines(counts: &Counts, top: usize) -> Vec<String> {
+    let total = |f: fn(&Tally) -> u64| counts.packages.iter().map(f).sum::<u64>();
+    let since = utils::utc_timestamp(counts.since as i64);
+    let mut lines = vec![format!(
+        "Since {}: {} installs, {} updates, {} failures",
+        &since[..10],
+        total(|t| t.installs),
+        total(|t| t.updates),
+        total(|t| t.failures)
+    )];
+    let mut failing: Vec<&Tally> = counts.packages.iter().filter(|t| t.failures > 0).collect();
+    failing.sort_by_key(|t| std::cmp::Reverse(t.failures));
+    let width = failing
+        .iter()
+        .take(top)
+        .map(|t| t.idname.chars().count())
+        .max()
+        .unwrap_or(0);
+    if !failing.is_empty() {
+        lines.push("\nFailed most often".to_string());
+        for t in failing.into_iter().take(top) {
+            lines.push(format!(
+                "{:<width$} {:>8}",
+                t.idname,
+                t.failures,
+                width = width
+            ));
+        }
+    }
+    lines
+}
+
+/// Time per phase (in `Phase::ALL` order) summed for each package, slowest
+/// package first
+fn cumulative(runs: &[Run]) -> Vec<(String, Vec<Duration>)> {
+    let mut totals: Vec<(String, Vec<Duration>)> = vec![];
+    for s in runs.iter().flat_map(|r| r.samples.iter()) {
+        let index = match totals.iter().position(|(name, _)| name == &s.idname) {
+            Some(i) => i,
+            None => {
+                let zero = vec![Duration::default(); Phase::ALL.len()];
+                totals.push((s.idname.clone(), zero));
+                totals.len() - 1
+            }
+        };
+        let phase = Phase::ALL.iter().position(|p| *p == s.phase).unwrap();
+        totals[index].1[phase] += s.duration;
+    }
+    totals.sort_by_key(|(_, t)| std::cmp::Reverse(t.iter().sum::<Duration>()));
+    totals
+}
@@ -0,0 +1,256 @@
+use crate::cmd::{install, move_cmd};
+use crate::context::Context;
+use crate::echo::{self, Ui};
+use crate::git::{self, GitRepo};
+use crate::lockfile;
+use crate::notify;
+use crate::package::{self, Package};
+use crate::prompt;
+use crate::task::{TaskManager, TaskType};
+use crate::utils;
+use crate::warnings::{self, Kind};
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+struct SyncArgs {
+    no_ui: bool,
+    threads: Option<usize>,
+    build_threads: Option<usize>,
+    locked: bool,
+    prune: bool,
+}
+
+impl SyncArgs {
+    fn from_matches(m: &ArgMatches) -> SyncArgs {
+        SyncArgs {
+            no_ui: m.is_present("no-ui"),
+            threads: value_t!(m, "threads", usize).ok(),
+            build_threads: value_t!(m, "build-threads", usize).ok(),
+            locked: m.is_present("locked"),
+            prune: m.is_present("prune"),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = SyncArgs::from_matches(matches);
+    if args.no_ui {
+        echo::set_ui(Ui::Simple);
+    }
+
+    let threads = args.threads.unwrap_or_else(num_cpus::get);
+    let build_threads = args.build_threads.unwrap_or_else(num_cpus::get);
+    if threads < 1 || build_threads < 1 {
+        die!("Threads should be greater than 0");
+    }
+
+    if args.locked && !ctx.lock_file().exists() {
+        die!(
+            "Err: No lockfile at {}, write one with `pac lock`",
+            ctx.lock_file().display()
+        );
+    }
+
+    match sync(ctx, &args, threads, build_threads) {
+        Ok(fails) if fails.is_empty() => (),
+        Ok(fails) => die!("Err: Failed to sync {}", fails.join(", ")),
+        Err(e) => die!("Err: {}", e),
+    }
+}
+
+/// Install every enabled package in paconfig, move the ones whose lazy
+/// loading changed between start/ and opt/ and remove the ones under pack/
+/// that are no longer listed or disabled. Returns the packages which failed.
+/// paconfig is only read, it may well be on a read-only mount, unless
+/// `--prune` removes the packages the lockfile does not list.
+fn sync(
+    ctx: &Context,
+    args: &SyncArgs,
+    threads: usize,
+    build_threads: usize,
+) -> Result<Vec<String>> {
+    let mut packs = package::fetch(ctx)?;
+    // an empty paconfig would otherwise make every package an orphan
+    if packs.is_empty() {
+        println!("No packages in {}", ctx.config_file.display());
+        return Ok(vec![]);
+    }
+    let mut fails = vec![];
+    if args.locked {
+        pin_locked(ctx, &mut packs, args.prune)?;
+        fails = checkout_locked(ctx, &packs, threads, build_threads);
+    }
+    relocate_lazy(ctx, &packs)?;
+    let missing: Vec<Package> = packs
+        .iter()
+        .filter(|p| p.enabled && !p.is_installed(ctx))
+        .cloned()
+        .collect();
+    let mut synced = packs.clone();
+    if missing.is_empty() {
+        println!(
+            "All {} are installed",
+            notify::packages(packs.iter().filter(|p| p.enabled).count())
+        );
+    } else {
+        fails.extend(install::install_into(
+            ctx,
+            &mut synced,
+            missing,
+            threads,
+            build_threads,
+            install::InstallOptions::default(),
+        )?);
+    }
+    package::sort(&mut synced);
+    package::update_pac_plugin(ctx, &synced)?;
+
+    let orphans = orphans(ctx, &packs)?;
+    if orphans.is_empty() {
+        return Ok(fails);
+    }
+    for dir in &orphans {
+        let path = ctx.pack_dir.join(dir);
+        if packs.iter().any(|p| p.path(ctx) == path) {
+            println!("Disabled in paconfig: {}", dir.display());
+        } else {
+            println!("Not in paconfig: {}", dir.display());
+        }
+    }
+    if prompt::confirm(&format!("Remove {} packages?", orphans.len()), true)? {
+        for dir in &orphans {
+            let path = ctx.pack_dir.join(dir);
+            // links into the store leave the clone for other packages
+            if fs::symlink_metadata(&path)?.file_type().is_symlink() {
+                utils::remove_link(&path)?;
+            } else {
+                fs::remove_dir_all(&path)?;
+            }
+            println!("Removed {}", dir.display());
+        }
+        package::prune_categories(ctx)?;
+    }
+    Ok(fails)
+}
+
+/// Set the packages of the lockfile to their locked commits. The ones it
+/// does not list are left as they are, those not installed are not
+/// installed. With `prune` they are removed from paconfig and `packs`
+/// instead, and so from disk as orphans.
+fn pin_locked(ctx: &Context, packs: &mut Vec<Package>, prune: bool) -> Result<()> {
+    let locked = lockfile::load(ctx)?.unwrap_or_default();
+    let unlocked: Vec<String> = packs
+        .iter()
+        .filter(|p| !locked.iter().any(|l| l.idname == p.idname))
+        .map(|p| p.idname.clone())
+        .collect();
+    if prune && !unlocked.is_empty() {
+        packs.retain(|p| !unlocked.contains(&p.idname));
+        package::save(ctx, packs.clone())?;
+        for idname in &unlocked {
+            println!(
+                "Removed {} from paconfig, the lockfile does not list it",
+                idname
+            );
+        }
+    } else {
+        for pack in packs
+            .iter()
+            .filter(|p| p.enabled && unlocked.contains(&p.idname))
+        {
+            let message = "not in the lockfile, left as it is";
+            warnings::push(Kind::Skipped, Some(&pack.idname), message);
+        }
+        packs.retain(|p| !unlocked.contains(&p.idname) || p.is_installed(ctx));
+    }
+    for pack in packs.iter_mut() {
+        if let Some(l) = locked.iter().find(|l| l.idname == pack.idname) {
+            pack.revision = Some(l.commit.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Check out the locked commit of the installed packages which have
+/// another one, returns the packages which failed. Local changes keep a
+/// package where it is.
+fn checkout_locked(
+    ctx: &Context,
+    packs: &[Package],
+    threads: usize,
+    build_threads: usize,
+) -> Vec<String> {
+    let mut manager = TaskManager::new(ctx, TaskType::Update, threads);
+    manager.build_threads(build_threads);
+    let mut queued = 0;
+    for pack in packs.iter().filter(|p| p.enabled && p.is_installed(ctx)) {
+        let locked = match pack.revision {
+            Some(ref r) => r,
+            None => continue,
+        };
+        match git::checkout(&pack.repo_path(ctx)) {
+            Some((head, _)) if head.to_string() == *locked => (),
+            Some((_, true)) => {
+                let message = "has local changes, not moved to the locked commit";
+                warnings::push(Kind::Skipped, Some(&pack.idname), message);
+            }
+            Some(_) => {
+                manager.add(pack.clone());
+                queued += 1;
+            }
+            None => (),
+        }
+    }
+    if queued == 0 {
+        return vec![];
+    }
+    let fails = manager.run(|ctx: &Context, pack: &Package| {
+        let res = pack.git_pull(ctx).map(|_| ());
+        let status = res.is_ok();
+        (res, status)
+    });
+    notify::report(
+        Some(format!(
+            "Checked out the locked commits of {}",
+            notify::packages(queued - fails.len())
+        )),
+        &fails,
+    );
+    fails
+}
+
+/// Move the packages which gained commands or filetypes to load on to opt/,
+/// and those which lost all of them back to start/ unless `opt` keeps them
+fn relocate_lazy(ctx: &Context, packs: &[Package]) -> Result<()> {
+    for pack in packs.iter().filter(|p| p.enabled && !p.disabled) {
+        let mut was = pack.clone();
+        was.set_opt(!pack.opt);
+        if pack.path(ctx).exists() || !was.path(ctx).is_dir() {
+            continue;
+        }
+        move_cmd::move_entry(ctx, &was, pack)?;
+        if pack.opt {
+            println!("Moved {} to opt/, it is loaded on demand", pack.idname);
+        } else {
+            println!(
+                "Moved {} to start/, nothing loads it on demand",
+                pack.idname
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Directories under pack/ no enabled package in paconfig lives in,
+/// relative to it
+fn orphans(ctx: &Context, packs: &[Package]) -> Result<Vec<PathBuf>> {
+    Ok(package::installed(ctx)?
+        .into_iter()
+        .filter(|i| !packs.iter().any(|p| p.enabled && p.path(ctx) == i.path))
+        .map(|i| i.relpath())
+        .collect())
+}
@@ -0,0 +1,217 @@
+use crate::cmd::install;
+use crate::context::Context;
+use crate::git;
+use crate::prompt;
+use crate::{Error, Result};
+
+use clap::{value_t, ArgMatches};
+use std::fs;
+use std::path::Path;
+
+/// Clones are machine local and refetched by the sync after a pull
+const GITIGNORE: &str = "store/\n";
+
+/// Set in the config of the repos pac initializes, it only syncs those
+const CREATED_KEY: &str = "pac.created";
+
+#[derive(Debug)]
+struct SyncArgs {
+    remote: Option<String>,
+    message: String,
+    threads: usize,
+    no_sync: bool,
+}
+
+impl SyncArgs {
+    fn from_matches(m: &ArgMatches) -> SyncArgs {
+        SyncArgs {
+            remote: value_t!(m, "remote", String).ok(),
+            message: value_t!(m, "message", String)
+                .unwrap_or_else(|_| "Update pac config".to_string()),
+            threads: value_t!(m, "threads", usize).unwrap_or_else(|_| num_cpus::get()),
+            no_sync: m.is_present("no-sync"),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let res = match matches.subcommand() {
+        ("push", Some(m)) => push(ctx, SyncArgs::from_matches(m)),
+        ("pull", Some(m)) => pull(ctx, SyncArgs::from_matches(m)),
+        _ => status(ctx),
+    };
+    if let Err(e) = res {
+        die!("Err: {}", e);
+    }
+}
+
+/// The files of the config directory pac commits, relative to it: paconfig,
+/// the lockfile and the .gitignore
+fn owned_files(ctx: &Context) -> Vec<String> {
+    let mut files = vec![".gitignore".to_string()];
+    files.extend(
+        [&ctx.config_file, &ctx.lock_file()]
+            .iter()
+            .filter(|f| f.is_file())
+            .filter_map(|f| f.file_name())
+            .map(|f| f.to_string_lossy().into_owned()),
+    );
+    files
+}
+
+/// Commit pac's files in the config directory and push them to the remote
+fn push(ctx: &Context, args: SyncArgs) -> Result<()> {
+    let dir = &ctx.config_dir;
+    init_repo(ctx, args.remote.as_deref())?;
+    let ignore = dir.join(".gitignore");
+    if !ignore.is_file() {
+        fs::write(ignore, GITIGNORE)?;
+    }
+    let files = owned_files(ctx);
+    let paths = |cmd: &[&'static str]| {
+        let mut args: Vec<&str> = cmd.to_vec();
+        args.push("--");
+        args.extend(files.iter().map(String::as_str));
+        args
+    };
+    git::run(dir, &paths(&["add"]))?;
+    if git::run(dir, &paths(&["status", "--porcelain"]))?.is_empty() {
+        println!("Nothing to commit");
+    } else {
+        let mut commit = vec!["commit", "--quiet", "-m", &args.message];
+        commit.extend(paths(&[]));
+        git::run(dir, &commit)?;
+    }
+    git::run(
+        dir,
+        &["push", "--quiet", "--set-upstream", "origin", "HEAD"],
+    )?;
+    println!("Pushed {} to {}", dir.display(), origin(dir)?);
+    Ok(())
+}
+
+/// Bring the config directory up to date with the remote and install what
+/// the new paconfig tracks
+fn pull(ctx: &Context, args: SyncArgs) -> Result<()> {
+    let dir = &ctx.config_dir;
+    init_repo(ctx, args.remote.as_deref())?;
+    // nothing was committed or pulled here yet
+    let fresh = git::run(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_err();
+    if !fresh && !git::run(dir, &["status", "--porcelain"])?.is_empty() {
+        return Err(Error::Git(
+            "Config directory has uncommitted changes, push or discard them first".to_string(),
+        ));
+    }
+    git::run(dir, &["fetch", "--quiet", "origin"])?;
+    git::run(dir, &["remote", "set-head", "origin", "--auto"])
+        .map_err(|_| Error::Git("Remote has no branch to pull".to_string()))?;
+    let branch = git::run(dir, &["rev-parse", "--abbrev-ref", "origin/HEAD"])?;
+    let local = branch.trim_start_matches("origin/");
+    if fresh {
+        // an untracked paconfig written before the first pull is replaced
+        let question = format!("Replace {} with the remote's?", ctx.config_file.display());
+        if ctx.config_file.is_file() && !prompt::confirm(&question, false)? {
+            return Ok(());
+        }
+        git::run(
+            dir,
+            &["checkout", "--quiet", "--force", "-B", local, &branch],
+        )?;
+    } else {
+        git::run(dir, &["merge", "--quiet", "--ff-only", &branch])?;
+    }
+    println!("Pulled {} from {}", dir.display(), origin(dir)?);
+
+    if args.no_sync {
+        return Ok(());
+    }
+    install::install_plugins(
+        ctx,
+        vec![],
+        args.threads,
+        num_cpus::get(),
+        Default::default(),
+    )?;
+    Ok(())
+}
+
+fn status(ctx: &Context) -> Result<()> {
+    let dir = &ctx.config_dir;
+    if !dir.join(".git").is_dir() {
+        println!("Not synced, run `pac sync-config push --remote URL` to start");
+        return Ok(());
+    }
+    println!("{} => {}", dir.display(), origin(dir)?);
+    let changes = git::run(dir, &["status", "--short"])?;
+    if !changes.is_empty() {
+        println!("{}", changes);
+    }
+    Ok(())
+}
+
+/// Whether pac may sync the repo at `dir`: one it initialized, or one of an
+/// earlier version which only tracks pac's files
+fn owned_repo(ctx: &Context, dir: &Path) -> Result<bool> {
+    if git::run(dir, &["config", "--get", CREATED_KEY]).is_ok() {
+        return Ok(true);
+    }
+    let files = owned_files(ctx);
+    let tracked = git::run(dir, &["ls-files"])?;
+    Ok(tracked.lines().all(|f| files.iter().any(|o| o == f)))
+}
+
+/// Make the config directory a repository with `remote` as origin, keeping
+/// the current origin if `remote` is not given. Home directories and repos
+/// pac did not create are refused, they hold far more than paconfig.
+fn init_repo(ctx: &Context, remote: Option<&str>) -> Result<()> {
+    let dir = &ctx.config_dir;
+    let refuse = |why: String| {
+        Err(Error::Git(format!(
+            "{} {}, keep paconfig in a directory of its own (see --config)",
+            dir.display(),
+            why
+        )))
+    };
+    if dirs::home_dir().is_some_and(|h| fs::canonicalize(&h).ok() == fs::canonicalize(dir).ok()) {
+        return refuse("is the home directory".to_string());
+    }
+    fs::create_dir_all(dir)?;
+    match git::run(dir, &["rev-parse", "--show-toplevel"]) {
+        Ok(top) if fs::canonicalize(&top).ok() != fs::canonicalize(dir).ok() => {
+            return refuse(format!("is inside the git repo {}", top));
+        }
+        Ok(_) if !owned_repo(ctx, dir)? => {
+            return refuse("is a git repo pac did not create".to_string());
+        }
+        Ok(_) => {}
+        Err(_) => {
+            git::run(dir, &["init", "--quiet"])?;
+            git::run(dir, &["config", CREATED_KEY, "true"])?;
+        }
+    }
+    let current = origin(dir).ok();
+    match (remote, current) {
+        (Some(url), None) => {
+            git::run(dir, &["remote", "add", "--end-of-options", "origin", url]).map(|_| ())
+        }
+        (Some(url), Some(cur)) if url != cur => {
+            let question = format!("Replace origin {} with {}?", cur, url);
+            if !prompt::confirm(&question, true)? {
+                return Err(Error::Git(format!("origin is still {}", cur)));
+            }
+            git::run(
+                dir,
+                &["remote", "set-url", "--end-of-options", "origin", url],
+            )
+            .map(|_| ())
+        }
+        (None, None) => Err(Error::Git(
+            "No remote configured, pass one with --remote".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn origin(dir: &Path) -> Result<String> {
+    git::run(dir, &["remote", "get-url", "origin"])
+}
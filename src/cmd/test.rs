@@ -0,0 +1,273 @@
+use crate::context::{self, Context};
+use crate::package::{self, Package};
+use crate::task::{TaskManager, TaskType};
+use crate::utils;
+use crate::{Error, Result};
+
+use clap::{value_t, ArgMatches};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use walkdir::WalkDir;
+
+#[derive(Debug)]
+struct TestArgs {
+    plugins: Vec<String>,
+    threads: Option<usize>,
+}
+
+impl TestArgs {
+    fn from_matches(m: &ArgMatches) -> TestArgs {
+        TestArgs {
+            plugins: m.values_of_lossy("package").unwrap_or_default(),
+            threads: value_t!(m, "threads", usize).ok(),
+        }
+    }
+}
+
+/// Test frameworks a plugin's suite is written for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Framework {
+    /// `*.vimspec` files or a `.themisrc`, run by vim-themis
+    Themis,
+    /// `*.vader` files, run by vader.vim
+    Vader,
+    /// `*_spec.lua` files, run by plenary.nvim's busted in neovim
+    Busted,
+}
+
+impl Framework {
+    /// Name of the package running the suite
+    fn runner(self) -> &'static str {
+        match self {
+            Framework::Themis => "thinca/vim-themis",
+            Framework::Vader => "junegunn/vader.vim",
+            Framework::Busted => "nvim-lua/plenary.nvim",
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = TestArgs::from_matches(matches);
+    let threads = args.threads.unwrap_or_else(num_cpus::get);
+    if threads < 1 {
+        die!("Threads should be greater than 0");
+    }
+
+    if let Err(e) = test_plugins(ctx, &args.plugins, threads) {
+        die!("Err: {}", e);
+    }
+}
+
+/// The framework of the suite in `dir` and the directory with its tests,
+/// from `test/`, `tests/` or `spec/`
+fn detect(dir: &Path) -> Option<(Framework, PathBuf)> {
+    let suites = ["test", "tests", "spec"]
+        .iter()
+        .map(|d| dir.join(d))
+        .filter(|d| d.is_dir());
+    for suite in suites {
+        let files: Vec<String> = WalkDir::new(&suite)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.file_name().to_str().map(String::from))
+            .collect();
+        let found =
+            if dir.join(".themisrc").is_file() || files.iter().any(|f| f.ends_with(".vimspec")) {
+                Framework::Themis
+            } else if files.iter().any(|f| f.ends_with(".vader")) {
+                Framework::Vader
+            } else if files.iter().any(|f| f.ends_with("_spec.lua")) {
+                Framework::Busted
+            } else {
+                continue;
+            };
+        return Some((found, suite));
+    }
+    None
+}
+
+/// A vimrc with only `dirs` and the editor's own runtime files, so neither
+/// the user's config nor the other packages get in the way
+fn vimrc(dirs: &[PathBuf]) -> String {
+    let mut rc = String::from("set nocompatible\nset runtimepath=$VIMRUNTIME\nset packpath=\n");
+    for dir in dirs {
+        let dir = dir.to_string_lossy().replace(' ', "\\ ");
+        rc += &format!("set runtimepath^={}\nset runtimepath+={}/after\n", dir, dir);
+    }
+    rc + "filetype plugin indent on\nsyntax enable\n"
+}
+
+/// Run the test suites of the packages headless, against the installed
+/// versions of them and of the packages they require
+fn test_plugins(ctx: &Context, plugins: &[String], threads: usize) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    let mut manager = TaskManager::new(ctx, TaskType::Exec, threads);
+    for plugin in plugins {
+        match packs
+            .iter()
+            .find(|p| p.idname == *plugin || p.name == *plugin)
+        {
+            Some(p) if p.is_installed(ctx) => manager.add(p.clone()),
+            _ => return Err(Error::plugin_not_installed(plugin)),
+        }
+    }
+
+    let logs = Arc::new(Mutex::new(Vec::new()));
+    let runner = {
+        let packs = Arc::new(packs.clone());
+        let logs = logs.clone();
+        move |ctx: &Context, pack: &Package| {
+            let res = run_suite(ctx, pack, &packs).map_err(|(e, log)| {
+                logs.lock().unwrap().push((pack.idname.clone(), log));
+                e
+            });
+            (res, false)
+        }
+    };
+    let failures = manager.run(runner);
+
+    let mut logs = logs.lock().unwrap();
+    logs.sort();
+    for (idname, log) in logs.iter().filter(|(_, l)| !l.is_empty()) {
+        println!("{}:", idname);
+        for line in log.lines() {
+            println!("    {}", line);
+        }
+    }
+    if !failures.is_empty() {
+        return Err(Error::command(format!(
+            "tests failed for {}",
+            failures.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Run the suite of `pack`, failing with the editor's output
+fn run_suite(
+    ctx: &Context,
+    pack: &Package,
+    packs: &[Package],
+) -> std::result::Result<(), (Error, String)> {
+    let plain = |e: Error| (e, String::new());
+    let dir = pack.path(ctx);
+    let (framework, suite) = detect(&dir)
+        .ok_or_else(|| plain(Error::command("no vader, themis or busted tests found")))?;
+    let runner = packs
+        .iter()
+        .find(|p| p.idname == framework.runner() && p.is_installed(ctx))
+        .ok_or_else(|| {
+            let message = format!("the tests need {}, install it first", framework.runner());
+            plain(Error::command(message))
+        })?;
+
+    // prepended in turn, the plugin ends up first and its own files win
+    let mut dirs: Vec<PathBuf> = packs
+        .iter()
+        .filter(|p| pack.requires.contains(&p.idname) && p.is_installed(ctx))
+        .map(|p| p.path(ctx))
+        .collect();
+    dirs.push(runner.path(ctx));
+    dirs.push(dir.clone());
+    let rc = env::temp_dir().join(format!("pac-test-{}-{}.vim", process::id(), pack.name));
+    fs::write(&rc, vimrc(&dirs)).map_err(|e| plain(e.into()))?;
+
+    let editor = match framework {
+        Framework::Busted => "nvim".to_string(),
+        _ => context::editors()
+            .into_iter()
+            .find(|e| utils::on_path(e))
+            .unwrap_or_else(|| "vim".to_string()),
+    };
+    let suite = suite.to_string_lossy().into_owned();
+    let mut cmd = match framework {
+        Framework::Themis => {
+            let mut cmd = process::Command::new(runner.path(ctx).join("bin/themis"));
+            cmd.env("THEMIS_VIM", &editor);
+            for d in &dirs {
+                cmd.arg("--runtimepath").arg(d);
+            }
+            cmd.arg(&suite);
+            cmd
+        }
+        Framework::Vader => {
+            let mut cmd = process::Command::new(&editor);
+            if editor.ends_with("nvim") {
+                cmd.arg("--headless");
+            } else {
+                cmd.arg("-Es");
+            }
+            cmd.arg("-u").arg(&rc).arg("-i").arg("NONE");
+            cmd.arg("-c").arg(format!("Vader! {}/**/*.vader", suite));
+            cmd
+        }
+        Framework::Busted => {
+            let mut cmd = process::Command::new(&editor);
+            cmd.arg("--headless")
+                .arg("-u")
+                .arg(&rc)
+                .arg("-c")
+                .arg(format!(
+                    "PlenaryBustedDirectory {} {{minimal_init = '{}', sequential = true}}",
+                    suite,
+                    rc.display()
+                ));
+            cmd
+        }
+    };
+    let output = cmd.current_dir(&dir).stdin(process::Stdio::null()).output();
+    let _ = fs::remove_file(&rc);
+    let output = output.map_err(|e| plain(e.into()))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let log = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let message = format!("{:?} tests failed", framework).to_lowercase();
+    Err((Error::command(message), log))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let root = env::temp_dir().join(format!("pac-test-detect-{}", process::id()));
+        let plugin = |name: &str, file: &str| {
+            let dir = root.join(name);
+            let file = dir.join(file);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, "").unwrap();
+            dir
+        };
+        let vader = plugin("vader", "test/basic.vader");
+        let busted = plugin("busted", "tests/unit/config_spec.lua");
+        let themis = plugin("themis", "test/a.vimspec");
+        let none = plugin("none", "test/README");
+
+        let found = [&vader, &busted, &themis, &none].map(|d| detect(d).map(|(f, _)| f));
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            found,
+            [
+                Some(Framework::Vader),
+                Some(Framework::Busted),
+                Some(Framework::Themis),
+                None
+            ]
+        );
+        assert_eq!(
+            vimrc(&[PathBuf::from("/p/my plugin")]),
+            "set nocompatible\nset runtimepath=$VIMRUNTIME\nset packpath=\n\
+             set runtimepath^=/p/my\\ plugin\nset runtimepath+=/p/my\\ plugin/after\n\
+             filetype plugin indent on\nsyntax enable\n"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use crate::cmd::install;
+use crate::context::{self, Context};
+use crate::package::Package;
+use crate::registry::Registry;
+use crate::{Error, Result};
+
+use clap::{value_t, ArgMatches};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{self, Command};
+
+#[derive(Debug)]
+struct TryArgs {
+    plugin: String,
+    with_config: bool,
+    keep: bool,
+    editor: Option<String>,
+    files: Vec<String>,
+}
+
+impl TryArgs {
+    fn from_matches(m: &ArgMatches) -> TryArgs {
+        TryArgs {
+            plugin: value_t!(m, "package", String).unwrap_or_default(),
+            with_config: m.is_present("with-config"),
+            keep: m.is_present("keep"),
+            editor: value_t!(m, "editor", String).ok(),
+            files: m.values_of_lossy("file").unwrap_or_default(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = TryArgs::from_matches(matches);
+
+    let root = env::temp_dir().join(format!("pac-try-{}", process::id()));
+    let res = try_plugin(ctx, &root, &args);
+    if args.keep {
+        println!("Kept {}", root.display());
+    } else if let Err(e) = fs::remove_dir_all(&root) {
+        if e.kind() != io::ErrorKind::NotFound {
+            eprintln!("Fail to remove {}: {}", root.display(), e);
+        }
+    }
+    if let Err(e) = res {
+        die!("Err: {}", e);
+    }
+}
+
+/// Install the plugin under `root` on its own and open the editor with it
+fn try_plugin(ctx: &Context, root: &Path, args: &TryArgs) -> Result<()> {
+    let registry = if install::is_short_name(&args.plugin) {
+        Registry::load(ctx)?
+    } else {
+        Registry::default()
+    };
+    let (remote, entry) = install::resolve(&args.plugin, &registry)?;
+    let name = Package::name_from_remote(&remote);
+    let mut pack = Package::new(&name, &remote, "try", false);
+    if let Some(e) = entry {
+        pack.build_commands = e.build_commands.clone();
+    }
+
+    // nothing may end up in the real config, stats or store
+    let mut sandbox = Context::with_paths(root, &root.join(".pac").join("paconfig.yaml"));
+    sandbox.state_dir = root.join("state");
+    install::install_plugins(
+        &sandbox,
+        vec![pack.clone()],
+        1,
+        1,
+        install::InstallOptions::default(),
+    )?;
+    if !pack.is_installed(&sandbox) {
+        return Err(Error::plugin_not_installed(&pack.idname));
+    }
+
+    let editors = match args.editor {
+        Some(ref e) => vec![e.clone()],
+        None => context::editors(),
+    };
+    for editor in editors {
+        match launch(&editor, root, &pack.path(&sandbox), args) {
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => continue,
+            res => return res,
+        }
+    }
+    Err(Error::Editor)
+}
+
+fn launch(editor: &str, root: &Path, path: &Path, args: &TryArgs) -> Result<()> {
+    let root = root.display().to_string();
+    let mut cmd = Command::new(editor);
+    if !args.with_config {
+        cmd.arg("--clean");
+    }
+    // `root` holds pack/ like a vim config directory and plugin/ with the
+    // generated _pac.vim
+    cmd.args([
+        "--cmd",
+        &format!("set packpath^={0} runtimepath^={0}", root),
+    ]);
+    let doc = path.join("doc");
+    if doc.is_dir() {
+        cmd.args(["-c", &format!("silent! helptags {}", doc.display())]);
+    }
+    let status = cmd.args(&args.files).status()?;
+    if !status.success() {
+        return Err(Error::command(format!("{} exited with {}", editor, status)));
+    }
+    Ok(())
+}
@@ -1,4 +1,8 @@
+use crate::context::Context;
+use crate::dry_run;
 use crate::package::{self, Package};
+use crate::prompt;
+use crate::space;
 use crate::{Error, Result};
 
 use clap::ArgMatches;
@@ -17,17 +21,17 @@ impl UninstallArgs {
     }
 }
 
-pub fn exec(matches: &ArgMatches) {
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
     let args = UninstallArgs::from_matches(matches);
 
-    if let Err(e) = uninstall_plugins(&args.plugins) {
+    if let Err(e) = uninstall_plugins(ctx, &args.plugins) {
         die!("{}", e);
     }
 }
 
 /// Uninstall multiple plugins based on plugin names.
-fn uninstall_plugins(plugins: &[String]) -> Result<()> {
-    let mut packs = package::fetch()?;
+fn uninstall_plugins(ctx: &Context, plugins: &[String]) -> Result<()> {
+    let mut packs = package::fetch(ctx)?;
 
     let to_uninstall = plugins
         .iter()
@@ -38,28 +42,139 @@ fn uninstall_plugins(plugins: &[String]) -> Result<()> {
             },
         )
         .collect::<Result<Vec<&Package>>>()?;
+    if dry_run::enabled() {
+        plan(ctx, &packs, &to_uninstall);
+        return Ok(());
+    }
+    if !prompt::confirm(&format!("Uninstall {}?", plugins.join(", ")), false)? {
+        return Ok(());
+    }
 
-    for pack in to_uninstall {
-        uninstall_plugin(pack)?;
+    for pack in &to_uninstall {
+        uninstall_plugin(ctx, pack)?;
     }
+    let store_paths = to_uninstall
+        .iter()
+        .map(|p| p.store_path(ctx))
+        .collect::<Vec<_>>();
 
     packs.retain(|x| !plugins.contains(&x.idname)); // keep only installed plugins
-    packs.sort_by(|a, b| a.idname.cmp(&b.idname));
-    package::update_pac_plugin(&packs)?;
-    package::save(packs)?;
+
+    // drop clones from the store no remaining package links to
+    for path in store_paths {
+        if path.is_dir() && !packs.iter().any(|p| p.store_path(ctx) == path) {
+            fs::remove_dir_all(&path)?;
+        }
+    }
+    package::sort(&mut packs);
+    package::update_pac_plugin(ctx, &packs)?;
+    package::save(ctx, packs)?;
+    package::prune_categories(ctx)?;
 
     println!();
     println!("Uninstalled {}", plugins.join(", "));
     Ok(())
 }
 
-/// Uninstall a specific plugin.
-fn uninstall_plugin(plugin: &Package) -> Result<()> {
-    let plugin_path = plugin.path();
+/// Print what uninstalling `to_uninstall` would remove, `--dry-run`: the
+/// packages left requiring them, what the loader stops doing and the space
+/// freed
+fn plan(ctx: &Context, packs: &[Package], to_uninstall: &[&Package]) {
+    let removed = |p: &Package| to_uninstall.iter().any(|u| u.idname == p.idname);
+    for pack in packs.iter().filter(|p| !removed(p)) {
+        let lost: Vec<&str> = to_uninstall
+            .iter()
+            .filter(|u| pack.requires.contains(&u.idname))
+            .map(|u| u.idname.as_str())
+            .collect();
+        if !lost.is_empty() {
+            dry_run::would(format!(
+                "leave {} without {}, which it requires",
+                pack.idname,
+                lost.join(", ")
+            ));
+        }
+    }
+    for pack in to_uninstall {
+        dry_run::would(format!(
+            "drop {} from the loader, {}",
+            pack.idname,
+            loading(pack)
+        ));
+    }
 
-    if plugin_path.is_dir() {
-        fs::remove_dir_all(&plugin_path)?;
+    let mut store_paths = vec![];
+    let mut freed = 0;
+    for pack in to_uninstall {
+        let path = pack.path(ctx);
+        if pack.is_linked(ctx) {
+            dry_run::would(format!("unlink {}", path.display()));
+        } else if path.is_dir() {
+            dry_run::would(format!("delete {}", path.display()));
+            freed += space::dir_size(&path);
+        }
+        let store_path = pack.store_path(ctx);
+        if !store_paths.contains(&store_path) {
+            store_paths.push(store_path);
+        }
     }
+    // clones other packages link to are kept
+    let remaining = packs.iter().filter(|p| !removed(p));
+    let kept: Vec<_> = remaining.map(|p| p.store_path(ctx)).collect();
+    for path in store_paths {
+        if path.is_dir() && !kept.contains(&path) {
+            dry_run::would(format!("delete {}", path.display()));
+            freed += space::dir_size(&path);
+        }
+    }
+    dry_run::would("save paconfig and update the loader");
+    dry_run::would(format!("free {}", space::human(freed)));
+}
 
-    Ok(())
+/// What the loader does for `pack` now
+fn loading(pack: &Package) -> String {
+    if pack.disabled || !pack.enabled {
+        return "which loads nothing of it".to_string();
+    }
+    let mut rules = vec![];
+    if !pack.load_commands.is_empty() {
+        let commands: Vec<String> = pack
+            .load_commands
+            .iter()
+            .map(|c| format!(":{}", c))
+            .collect();
+        rules.push(format!("on {}", commands.join(", ")));
+    }
+    if !pack.for_types.is_empty() {
+        rules.push(format!("for {}", pack.for_types.join(", ")));
+    }
+    if let Some(ref c) = pack.condition {
+        rules.push(format!("if {}", c));
+    }
+    match (rules.is_empty(), pack.opt) {
+        (false, _) => format!("which loads it {}", rules.join(" and ")),
+        (true, true) => "which lets `:packadd` load it".to_string(),
+        (true, false) => "which loads it at startup".to_string(),
+    }
+}
+
+/// Uninstall a specific plugin.
+fn uninstall_plugin(ctx: &Context, plugin: &Package) -> Result<()> {
+    plugin.unlink(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uninstall_loading() {
+        let mut go = Package::new("vim-go", "https://github.com/fatih/vim-go", "lang", true);
+        assert_eq!(loading(&go), "which lets `:packadd` load it");
+        go.load_commands = vec!["GoBuild".to_string()];
+        go.for_types = vec!["go".to_string()];
+        assert_eq!(loading(&go), "which loads it on :GoBuild and for go");
+        go.enabled = false;
+        assert_eq!(loading(&go), "which loads nothing of it");
+    }
 }
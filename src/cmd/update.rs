@@ -1,6 +1,14 @@
-use crate::git::GitRepo;
+use crate::context::Context;
+use crate::dry_run;
+use crate::git::{self, GitRepo};
+use crate::notify;
 use crate::package::{self, Package};
+use crate::prompt;
+use crate::source;
+use crate::space;
+use crate::state;
 use crate::task::{TaskManager, TaskType};
+use crate::warnings::{self, Kind};
 use crate::{Error, Result};
 use clap::{value_t, ArgMatches};
 
@@ -9,6 +17,9 @@ struct UpdateArgs {
     plugins: Vec<String>,
     skip: Vec<String>,
     threads: Option<usize>,
+    build_threads: Option<usize>,
+    to: Option<String>,
+    from_upstream: bool,
     paconfig: bool,
 }
 
@@ -18,73 +29,249 @@ impl UpdateArgs {
             plugins: m.values_of_lossy("package").unwrap_or_default(),
             skip: m.values_of_lossy("skip").unwrap_or_default(),
             threads: value_t!(m, "threads", usize).ok(),
+            build_threads: value_t!(m, "build-threads", usize).ok(),
+            to: value_t!(m, "to", String).ok(),
+            from_upstream: m.value_of("from") == Some("upstream"),
             // TODO: remove this opt (already removed from cli)
             paconfig: m.is_present("paconfig"),
         }
     }
 }
 
-pub fn exec(matches: &ArgMatches) {
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
     let args = UpdateArgs::from_matches(matches);
 
     if args.paconfig {
-        if let Err(e) = update_paconfig() {
+        if let Err(e) = update_paconfig(ctx) {
             die!("Err: {}", e);
         }
         return;
     }
 
     let threads = args.threads.unwrap_or_else(num_cpus::get);
-    if threads < 1 {
+    let build_threads = args.build_threads.unwrap_or_else(num_cpus::get);
+    if threads < 1 || build_threads < 1 {
         die!("Threads should be greater than 0");
     }
 
-    if let Err(e) = update_plugins(&args.plugins, threads, &args.skip) {
+    if let Some(ref rev) = args.to {
+        if args.plugins.len() != 1 {
+            die!("Exactly one plugin has to be given with --to");
+        }
+        if let Err(e) = update_to(ctx, &args.plugins[0], rev) {
+            die!("Err: {}", e);
+        }
+        return;
+    }
+
+    if args.from_upstream {
+        if let Err(e) = merge_upstreams(ctx, &args.plugins, threads, build_threads) {
+            die!("Err: {}", e);
+        }
+        return;
+    }
+
+    if let Err(e) = update_plugins(ctx, &args.plugins, threads, build_threads, &args.skip) {
         die!("Err: {}", e);
     }
 }
 
-fn update_paconfig() -> Result<()> {
+fn update_paconfig(ctx: &Context) -> Result<()> {
     println!("Update _pack file for all plugins.");
-    let mut packs = package::fetch()?;
+    let mut packs = package::fetch(ctx)?;
 
-    packs.sort_by(|a, b| a.idname.cmp(&b.idname));
-    package::update_pac_plugin(&packs)?;
+    package::sort(&mut packs);
+    package::update_pac_plugin(ctx, &packs)?;
 
     Ok(())
 }
 
-fn update_plugins(plugins: &[String], threads: usize, skip: &[String]) -> Result<()> {
-    let mut packs = package::fetch()?;
+fn update_plugins(
+    ctx: &Context,
+    plugins: &[String],
+    threads: usize,
+    build_threads: usize,
+    skip: &[String],
+) -> Result<()> {
+    let mut packs = package::fetch(ctx)?;
+    space::check_update(ctx)?;
 
-    let mut manager = TaskManager::new(TaskType::Update, threads);
+    let mut manager = TaskManager::new(ctx, TaskType::Update, threads);
+    manager.build_threads(build_threads);
+    let mut queued = 0;
     if plugins.is_empty() {
-        for pack in &packs {
+        for pack in packs.iter().filter(|p| p.enabled) {
             if skip.iter().any(|x| pack.idname.contains(x)) {
                 println!("Skip {}", pack.idname);
                 continue;
             }
-            manager.add(pack.clone());
+            if discard_changes(ctx, pack)? {
+                manager.add(pack.clone());
+                queued += 1;
+            }
         }
     } else {
         for pack in packs.iter().filter(|x| plugins.contains(&x.idname)) {
-            manager.add(pack.clone());
+            if !pack.enabled {
+                warnings::push(Kind::Skipped, Some(&pack.idname), "disabled in paconfig");
+                continue;
+            }
+            if discard_changes(ctx, pack)? {
+                manager.add(pack.clone());
+                queued += 1;
+            }
         }
     }
 
-    for fail in manager.run(update_plugin) {
+    let fails = manager.run(update_plugin);
+    if dry_run::enabled() {
+        return Ok(());
+    }
+    let updated = queued - fails.len();
+    notify::report(
+        Some(format!("Updated {}", notify::packages(updated))),
+        &fails,
+    );
+    for fail in fails {
         packs.retain(|e| e.idname != fail);
     }
+    let all = plugins.is_empty() && skip.is_empty();
+    if let Err(e) = state::record_sync(ctx, updated, all) {
+        log::info!("fail to save sync state: {}", e);
+    }
 
-    packs.sort_by(|a, b| a.idname.cmp(&b.idname));
+    package::sort(&mut packs);
 
-    package::update_pac_plugin(&packs)?;
+    package::update_pac_plugin(ctx, &packs)?;
 
     Ok(())
 }
 
-fn update_plugin(pack: &Package) -> (Result<()>, bool) {
-    let res = do_update(pack);
+/// Merge what the repos the forks among `plugins`, or all forks, were made
+/// from into their checkouts. Other packages are left alone.
+fn merge_upstreams(
+    ctx: &Context,
+    plugins: &[String],
+    threads: usize,
+    build_threads: usize,
+) -> Result<()> {
+    let mut packs = package::fetch(ctx)?;
+    let mut manager = TaskManager::new(ctx, TaskType::Update, threads);
+    manager.build_threads(build_threads);
+    let mut queued = 0;
+    for pack in packs
+        .iter()
+        .filter(|p| p.enabled && (plugins.is_empty() || plugins.contains(&p.idname)))
+    {
+        if pack.fork.is_none() {
+            if !plugins.is_empty() {
+                warnings::push(
+                    Kind::Skipped,
+                    Some(&pack.idname),
+                    "not a fork, nothing to merge",
+                );
+            }
+            continue;
+        }
+        if dry_run::enabled() {
+            dry_run::would(format!("merge upstream into {}", pack.idname));
+        }
+        manager.add(pack.clone());
+        queued += 1;
+    }
+    if queued == 0 {
+        println!("No forks to merge upstream into");
+        return Ok(());
+    }
+    if dry_run::enabled() {
+        return Ok(());
+    }
+
+    let fails = manager.run(|ctx: &Context, pack: &Package| {
+        let res = pack.git_merge_upstream(ctx);
+        let status = res.is_ok();
+        (res, status)
+    });
+    notify::report(
+        Some(format!(
+            "Merged upstream into {}",
+            notify::packages(queued - fails.len())
+        )),
+        &fails,
+    );
+    if fails.len() < queued {
+        println!("Push the merges to keep them, a plain update checks out what the forks have");
+    }
+    package::sort(&mut packs);
+    package::update_pac_plugin(ctx, &packs)
+}
+
+/// Whether updating `pack` may throw away changes to its files, updates check
+/// out over them. Asks with `--confirm`, packages without changes go ahead.
+fn discard_changes(ctx: &Context, pack: &Package) -> Result<bool> {
+    let dirty = git::checkout(&pack.path(ctx)).is_some_and(|(_, dirty)| dirty);
+    if !dirty {
+        return Ok(true);
+    }
+    if dry_run::enabled() {
+        dry_run::would(format!(
+            "ask to discard the local changes of {}",
+            pack.idname
+        ));
+        return Ok(true);
+    }
+    let question = format!("{} has local changes, discard them?", pack.idname);
+    let discard = prompt::confirm(&question, false)?;
+    if !discard {
+        warnings::push(
+            Kind::Skipped,
+            Some(&pack.idname),
+            "has local changes, left as it was",
+        );
+    }
+    Ok(discard)
+}
+
+/// Check out `rev` for a single package and remember it in paconfig
+fn update_to(ctx: &Context, plugin: &str, rev: &str) -> Result<()> {
+    let mut packs = package::fetch(ctx)?;
+    let pack = match packs.iter_mut().find(|p| p.idname == plugin) {
+        Some(p) => p,
+        None => return Err(Error::plugin_not_installed(plugin)),
+    };
+    if !discard_changes(ctx, pack)? {
+        return Ok(());
+    }
+    if dry_run::enabled() {
+        dry_run::would(format!(
+            "check out {} for {} and save it as its rev",
+            rev, pack.idname
+        ));
+        return Ok(());
+    }
+    pack.revision = Some(rev.to_string());
+    pack.channel = None;
+
+    let mut manager = TaskManager::new(ctx, TaskType::Update, 1);
+    manager.add(pack.clone());
+    if !manager.run(update_plugin_to).is_empty() {
+        return Err(Error::Git(format!("{} is left as it was", plugin)));
+    }
+
+    package::sort(&mut packs);
+    package::update_pac_plugin(ctx, &packs)?;
+    package::save(ctx, packs)
+}
+
+/// Unlike a plain update a missing ref fails, it would be saved otherwise
+fn update_plugin_to(ctx: &Context, pack: &Package) -> (Result<()>, bool) {
+    let res = do_update(ctx, pack);
+    let status = res.is_ok();
+    (res, status)
+}
+
+fn update_plugin(ctx: &Context, pack: &Package) -> (Result<()>, bool) {
+    let res = do_update(ctx, pack);
     let status = match res {
         Err(Error::SkipLocal) | Err(Error::Git(_)) => true,
         Err(_) => false,
@@ -93,11 +280,11 @@ fn update_plugin(pack: &Package) -> (Result<()>, bool) {
     (res, status)
 }
 
-fn do_update(pack: &Package) -> Result<()> {
-    let path = pack.path();
+fn do_update(ctx: &Context, pack: &Package) -> Result<()> {
+    let path = pack.path(ctx);
     if !path.is_dir() {
         Err(Error::plugin_not_installed(&pack.idname))
     } else {
-        pack.git_pull()
+        source::of(pack).update(ctx, pack)
     }
 }
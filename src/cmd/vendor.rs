@@ -0,0 +1,131 @@
+use crate::context::Context;
+use crate::git::GitRepo;
+use crate::package::{self, Package};
+use crate::utils;
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+/// Written next to the vendored categories, lists what came from where
+const VENDOR_MANIFEST: &str = "pac-vendor.yaml";
+
+const VENDOR_HEADER: &[u8] = b"# vim: ft=yaml
+#
+# Generated by pac vendor, the packages in this directory and their origin.
+
+";
+
+#[derive(Debug)]
+struct VendorArgs {
+    into: PathBuf,
+}
+
+impl VendorArgs {
+    fn from_matches(m: &ArgMatches) -> VendorArgs {
+        VendorArgs {
+            into: value_t!(m, "into", PathBuf).unwrap(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = VendorArgs::from_matches(matches);
+
+    // vendoring replaces the directories it copies to
+    let same = |a: &Path, b: &Path| match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    };
+    if same(&args.into, &ctx.pack_dir) {
+        die!("Err: Can not vendor into the pack directory pac manages");
+    }
+
+    if let Err(e) = vendor(ctx, &args.into) {
+        die!("Err: {}", e);
+    }
+}
+
+/// Path of the package below a pack directory, `<category>/<start|opt>/<name>`
+fn relpath(pack: &Package) -> PathBuf {
+    let kind = if pack.opt { "opt" } else { "start" };
+    Path::new(&pack.category).join(kind).join(&pack.name)
+}
+
+/// Copy every installed package without its git history into `into`, laid
+/// out like `pack/`, so it can be committed to a dotfiles repo
+fn vendor(ctx: &Context, into: &Path) -> Result<()> {
+    let packs: Vec<Package> = package::fetch(ctx)?
+        .into_iter()
+        .filter(|p| p.is_installed(ctx))
+        .collect();
+
+    // packages vendored before but since removed
+    let manifest = into.join(VENDOR_MANIFEST);
+    for old in previous_paths(&manifest) {
+        if !packs.iter().any(|p| relpath(p) == old) {
+            let dir = into.join(&old);
+            if dir.is_dir() {
+                fs::remove_dir_all(&dir)?;
+                println!("Removed {}", old.display());
+            }
+        }
+    }
+
+    let mut entries = vec![];
+    for pack in &packs {
+        let rel = relpath(pack);
+        let dst = into.join(&rel);
+        if dst.exists() {
+            fs::remove_dir_all(&dst)?;
+        }
+        fs::create_dir_all(&dst)?;
+        utils::copy_worktree(pack.repo_path(ctx), dst)?;
+
+        let mut doc = Hash::new();
+        doc.insert(Yaml::from_str("idname"), Yaml::String(pack.idname.clone()));
+        doc.insert(Yaml::from_str("remote"), Yaml::String(pack.remote.clone()));
+        if let Ok(oid) = pack.git_head(ctx) {
+            doc.insert(Yaml::from_str("commit"), Yaml::String(oid.to_string()));
+        }
+        let path = rel.display().to_string();
+        doc.insert(Yaml::from_str("path"), Yaml::String(path));
+        entries.push(Yaml::Hash(doc));
+        println!("Vendored {} => {}", pack.idname, rel.display());
+    }
+
+    let mut doc = Hash::new();
+    doc.insert(Yaml::from_str("packages"), Yaml::Array(entries));
+    let mut out = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.dump(&Yaml::Hash(doc))?;
+    }
+    let mut f = File::create(&manifest)?;
+    f.write_all(VENDOR_HEADER)?;
+    f.write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// Package paths listed by an earlier run, an unreadable manifest lists none
+fn previous_paths(manifest: &Path) -> Vec<PathBuf> {
+    let data = fs::read_to_string(manifest).unwrap_or_default();
+    let docs = YamlLoader::load_from_str(&data).unwrap_or_default();
+    docs.first()
+        .and_then(|d| d["packages"].as_vec())
+        .map(|packs| {
+            packs
+                .iter()
+                .filter_map(|p| p["path"].as_str())
+                // never leave the vendor directory
+                .filter(|p| !p.split('/').any(|c| c == ".." || c.is_empty()))
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,118 @@
+use crate::context::Context;
+use crate::package::{self, Package};
+use crate::Result;
+
+use clap::{value_t, ArgMatches};
+
+#[derive(Debug)]
+struct WhyArgs {
+    package: String,
+}
+
+impl WhyArgs {
+    fn from_matches(m: &ArgMatches) -> WhyArgs {
+        WhyArgs {
+            package: value_t!(m, "package", String).unwrap(),
+        }
+    }
+}
+
+pub fn exec(ctx: &Context, matches: &ArgMatches) {
+    let args = WhyArgs::from_matches(matches);
+
+    if let Err(e) = why(ctx, &args.package) {
+        die!("Err: {}", e);
+    }
+}
+
+fn why(ctx: &Context, query: &str) -> Result<()> {
+    let packs = package::fetch(ctx)?;
+    let listed = packs
+        .iter()
+        .find(|p| p.idname == query)
+        .or_else(|| packs.iter().find(|p| p.name == query));
+    let idname = listed.map_or(query, |p| p.idname.as_str());
+    let chains = chains(&packs, idname);
+
+    match listed {
+        Some(p) => println!("{} is listed in paconfig", p.idname),
+        None if !chains.is_empty() => println!("{} is not in paconfig", idname),
+        None => {
+            // a directory under pack/ nothing refers to
+            let dirs: Vec<_> = package::installed(ctx)?
+                .into_iter()
+                .filter(|p| p.name == query)
+                .collect();
+            if dirs.is_empty() {
+                die!("Err: {} is neither installed nor required", query);
+            }
+            for dir in dirs {
+                println!(
+                    "pack/{} is orphaned, installed but not in paconfig",
+                    dir.relpath().display()
+                );
+            }
+            return Ok(());
+        }
+    }
+    if !chains.is_empty() {
+        println!("Required through:");
+        for chain in chains {
+            println!("  {}", chain.join(" -> "));
+        }
+    }
+    Ok(())
+}
+
+/// Every path of `requires` leading to `idname`, starting at a package no
+/// other package requires. Paths running into a cycle start at its entry.
+fn chains<'a>(packs: &'a [Package], idname: &'a str) -> Vec<Vec<&'a str>> {
+    fn walk<'a>(packs: &'a [Package], path: &mut Vec<&'a str>, found: &mut Vec<Vec<&'a str>>) {
+        let current = path[path.len() - 1];
+        let parents: Vec<&str> = packs
+            .iter()
+            .filter(|p| p.requires.iter().any(|r| r == current))
+            .map(|p| p.idname.as_str())
+            .filter(|p| !path.contains(p))
+            .collect();
+        if parents.is_empty() {
+            if path.len() > 1 {
+                found.push(path.iter().rev().copied().collect());
+            }
+            return;
+        }
+        for parent in parents {
+            path.push(parent);
+            walk(packs, path, found);
+            path.pop();
+        }
+    }
+
+    let mut found = vec![];
+    walk(packs, &mut vec![idname], &mut found);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn why_chains() {
+        let pack = |remote: &str, requires: &[&str]| {
+            let mut p = Package::new(&Package::name_from_remote(remote), remote, "default", false);
+            p.requires = requires.iter().map(|r| r.to_string()).collect();
+            p
+        };
+        let packs = vec![
+            pack("https://github.com/a/app", &["b/lib"]),
+            pack("https://github.com/b/lib", &["c/core"]),
+            pack("https://github.com/d/other", &["c/core"]),
+        ];
+        assert_eq!(
+            chains(&packs, "c/core"),
+            vec![vec!["a/app", "b/lib", "c/core"], vec!["d/other", "c/core"]]
+        );
+        assert!(chains(&packs, "a/app").is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Filesystem locations pac works with. Built once in main from the command
+/// line and environment, then passed down to everything touching the disk.
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// Directory with all packages, `pack/` under the vim config directory
+    pub pack_dir: PathBuf,
+    /// Directory the generated `_pac.vim` is written to
+    pub plugin_dir: PathBuf,
+    /// Directory of the generated `pac.vim` with `pac#status()`
+    pub autoload_dir: PathBuf,
+    /// Directory with pac's own files, parent of `config_file`
+    pub config_dir: PathBuf,
+    /// The paconfig.yaml tracking installed packages
+    pub config_file: PathBuf,
+    /// Directory all packages are cloned into, pack/ only has links to these
+    pub store_dir: PathBuf,
+    /// Machine local files which can be refetched, like the registry index.
+    /// `$PAC_CACHE_DIR`, `$XDG_CACHE_HOME/pac` or `~/.cache/pac`
+    pub cache_dir: PathBuf,
+    /// Machine local history, like timings of past runs.
+    /// `$PAC_STATE_DIR`, `$XDG_STATE_HOME/pac` or `~/.local/state/pac`
+    pub state_dir: PathBuf,
+}
+
+impl Context {
+    /// Resolve paths with `root` and `config` taking precedence over the
+    /// environment. `root` is the vim config directory, `$VIM_CONFIG_PATH`
+    /// by default, else `~/.vim` if pac already keeps a paconfig there, else
+    /// the first entry of the editor's `packpath` and at last `~/.vim`.
+    pub fn new(root: Option<PathBuf>, config: Option<PathBuf>) -> Context {
+        let vim_dir = dirs::home_dir().map(|h| h.join(".vim"));
+        let base_dir = root
+            .or_else(|| env::var("VIM_CONFIG_PATH").ok().map(PathBuf::from))
+            .or_else(|| {
+                vim_dir
+                    .clone()
+                    .filter(|d| d.join(".pac").join("paconfig.yaml").is_file())
+            })
+            .or_else(|| {
+                let cache_dir = xdg_dir("PAC_CACHE_DIR", "XDG_CACHE_HOME", ".cache");
+                detect_root(&cache_dir)
+            })
+            .unwrap_or_else(|| vim_dir.expect("No home directory found"));
+        let config_file = config.unwrap_or_else(|| base_dir.join(".pac").join("paconfig.yaml"));
+        Self::with_paths(base_dir, config_file)
+    }
+
+    pub fn with_paths<P: AsRef<Path>>(base_dir: P, config_file: P) -> Context {
+        // links into the store must not depend on the working directory
+        let absolute = |p: &Path| match env::current_dir() {
+            Ok(cwd) if p.is_relative() => cwd.join(p),
+            _ => p.to_path_buf(),
+        };
+        let base_dir = absolute(base_dir.as_ref());
+        let config_file = absolute(config_file.as_ref());
+        let config_dir = config_file
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| base_dir.join(".pac"));
+        Context {
+            pack_dir: base_dir.join("pack"),
+            plugin_dir: base_dir.join("plugin"),
+            autoload_dir: base_dir.join("autoload"),
+            store_dir: config_dir.join("store"),
+            cache_dir: xdg_dir("PAC_CACHE_DIR", "XDG_CACHE_HOME", ".cache"),
+            state_dir: xdg_dir("PAC_STATE_DIR", "XDG_STATE_HOME", ".local/state"),
+            config_dir,
+            config_file,
+        }
+    }
+
+    /// The commits `pac lock` pinned the packages to, next to paconfig so
+    /// that both are committed together
+    pub fn lock_file(&self) -> PathBuf {
+        self.config_dir.join("pac-lock.yaml")
+    }
+
+    /// Cached registry index fetched by `pac registry update`
+    pub fn registry_file(&self) -> PathBuf {
+        self.cache_dir.join("registry.yaml")
+    }
+
+    /// Refs of the remotes listed by recent runs of `pac outdated`
+    pub fn ref_cache_file(&self) -> PathBuf {
+        self.cache_dir.join("remote-refs.yaml")
+    }
+
+    /// Where the registry index was cached before it moved to `cache_dir`
+    pub fn legacy_registry_file(&self) -> PathBuf {
+        self.config_dir.join("registry.yaml")
+    }
+
+    /// Timings of recent runs, read by `pac stats`
+    pub fn stats_file(&self) -> PathBuf {
+        self.state_dir.join("stats.yaml")
+    }
+
+    /// How often packages were installed, updated and failed, read by
+    /// `pac stats`
+    pub fn counts_file(&self) -> PathBuf {
+        self.state_dir.join("counts.yaml")
+    }
+
+    /// Pending updates and time of the last sync, read by `pac#status()`
+    pub fn sync_state_file(&self) -> PathBuf {
+        self.state_dir.join("sync")
+    }
+
+    /// Touched by the loader when it reminds to sync, which it does once a day
+    pub fn reminder_file(&self) -> PathBuf {
+        self.state_dir.join("reminded")
+    }
+
+    /// Left behind when the first run offer to take over existing plugins is
+    /// declined, so it is not made again
+    pub fn first_run_file(&self) -> PathBuf {
+        self.state_dir.join("first-run-declined")
+    }
+}
+
+/// Editor to ask for its packpath: `$PAC_EDITOR`, else vim or neovim
+/// depending on `$VISUAL`/`$EDITOR`, else whichever is installed.
+pub fn editors() -> Vec<String> {
+    if let Ok(e) = env::var("PAC_EDITOR") {
+        return vec![e];
+    }
+    let preferred = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_default();
+    if preferred.ends_with("nvim") {
+        vec!["nvim".to_string(), "vim".to_string()]
+    } else {
+        vec!["vim".to_string(), "nvim".to_string()]
+    }
+}
+
+/// First `packpath` entry of the editor inside the home directory, the
+/// user's own one. The editor starts with `--clean`, without the user's
+/// config and plugins, and system directories like `/usr/share/vim` are
+/// skipped.
+fn query_packpath(editor: &str) -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    let mut cmd = Command::new(editor);
+    cmd.arg("--clean");
+    if editor.ends_with("nvim") {
+        cmd.args(["--headless", "-i", "NONE"])
+            .args(["-c", "lua io.stdout:write(vim.o.packpath)"]);
+    } else {
+        cmd.args(["-es", "-i", "NONE"]).args([
+            "-c",
+            "redir! > /dev/stdout | silent echon &packpath | redir END",
+        ]);
+    }
+    let out = cmd
+        .args(["-c", "qa!"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let packpath = String::from_utf8(out.stdout).ok()?;
+    packpath
+        .trim()
+        .split(',')
+        .map(PathBuf::from)
+        .find(|p| p.starts_with(&home))
+}
+
+/// The detected directory is cached as `<editor>\n<dir>` so the editor only
+/// runs once, which is also when the directory is shown. Remove
+/// `$PAC_CACHE_DIR/packpath` to detect again.
+fn detect_root(cache_dir: &Path) -> Option<PathBuf> {
+    let cache = cache_dir.join("packpath");
+    let editors = editors();
+    if let Ok(data) = fs::read_to_string(&cache) {
+        let mut lines = data.lines();
+        if let (Some(editor), Some(dir)) = (lines.next(), lines.next()) {
+            if editors.first().map(String::as_str) == Some(editor) {
+                return Some(PathBuf::from(dir));
+            }
+        }
+    }
+    for editor in editors.iter() {
+        if let Some(dir) = query_packpath(editor) {
+            let _ = fs::create_dir_all(cache_dir)
+                .and_then(|_| fs::write(&cache, format!("{}\n{}\n", editor, dir.display())));
+            eprintln!(
+                "Managing packages in {}, the first home directory of {}'s packpath. \
+                 Set $VIM_CONFIG_PATH or pass --root to use another one.",
+                dir.display(),
+                editor
+            );
+            return Some(dir);
+        }
+    }
+    None
+}
+
+/// `$<own>` as is, otherwise `pac` under `$<xdg>` or `~/<fallback>`. Relative
+/// XDG paths are ignored as the spec asks.
+fn xdg_dir(own: &str, xdg: &str, fallback: &str) -> PathBuf {
+    if let Some(dir) = env::var_os(own).filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    env::var_os(xdg)
+        .map(PathBuf::from)
+        .filter(|d| d.is_absolute())
+        .or_else(|| dirs::home_dir().map(|h| h.join(fallback)))
+        .unwrap_or_else(env::temp_dir)
+        .join("pac")
+}
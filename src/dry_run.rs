@@ -0,0 +1,21 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Commands taking `--dry-run`
+pub const COMMANDS: &[&str] = &["install", "update", "uninstall", "import"];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Only print what the run would change, `--dry-run`
+pub fn set(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Print a change the run leaves out
+pub fn would<T: Display>(change: T) {
+    println!("Would {}", change);
+}
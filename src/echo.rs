@@ -1,71 +1,603 @@
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{cursor, execute, queue};
 use lazy_static::lazy_static;
 use std::io::{self, Write};
-use std::sync::Mutex;
-use termion::{clear, color, cursor};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::Duration;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-lazy_static! {
-    static ref MUTEX: Mutex<u16> = Mutex::new(0);
+use crate::settings::{Colors, Theme};
+use crate::utils;
+
+/// State of a task, decides the sign in front of its line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Running,
+    Success,
+    Failure,
 }
 
-pub fn line() -> u16 {
-    let mut v = MUTEX.lock().unwrap();
-    let current = *v;
-    *v += 1;
-    println!();
-    current
+struct Task {
+    name: String,
+    /// Spinner ticks so far, picks the spinner frame
+    ticks: usize,
+    status: Status,
+    elapsed: Duration,
+    message: String,
+    /// Printed above the live area, it is never redrawn again
+    flushed: bool,
 }
 
-pub fn async_print(line: u16, right: u16, msg: &str) {
-    let current = MUTEX.lock().unwrap();
-    let offset = *current - line;
-    print!("{}", cursor::Hide);
-    print!(
-        "{}{}{}{}",
-        cursor::Up(offset),
-        msg,
-        cursor::Left(right),
-        cursor::Down(offset)
-    );
-    print!("{}", cursor::Show);
-    let stdout = io::stdout();
-    let mut handle = stdout.lock();
-    handle.flush().unwrap();
-}
-
-pub fn character<C: color::Color>(line: u16, offset: u16, c: char, char_color: C) {
-    async_print(
-        line,
-        offset + 1,
-        &format!(
-            "{}{}{}{}",
-            cursor::Right(offset),
-            color::Fg(char_color),
-            c,
-            color::Fg(color::Reset)
-        ),
-    );
+impl Task {
+    fn draw<W: Write>(
+        &self,
+        out: &mut W,
+        theme: &Theme,
+        colors: &Colors,
+        name_width: usize,
+        cols: usize,
+    ) -> io::Result<()> {
+        // 5 columns go to the sign in front, the name gets at most half of
+        // the rest so there is always room for the message
+        let cols = cols.saturating_sub(6);
+        let name_width = name_width.min(cols / 2);
+        let name = pad(&format!("[{}]", self.name), name_width);
+        let line = format!(
+            "{} {} {}",
+            name,
+            utils::format_duration(self.elapsed),
+            self.message,
+        );
+        let (sign_color, sign) = match self.status {
+            Status::Running => {
+                let frame = self.ticks.checked_rem(theme.spinner.len()).unwrap_or(0);
+                (
+                    Color::Reset,
+                    theme.spinner.get(frame).copied().unwrap_or(' '),
+                )
+            }
+            Status::Success => (colors.success, theme.success),
+            Status::Failure => (colors.failure, theme.failure),
+        };
+        queue!(
+            out,
+            Clear(ClearType::CurrentLine),
+            Print("   "),
+            SetForegroundColor(sign_color),
+            Print(sign),
+            ResetColor,
+            Print(" "),
+            // a wrapped line would throw off the cursor movement on redraw
+            Print(truncate(&line, cols)),
+            Print("\r\n"),
+        )
+    }
+}
+
+/// Columns `s` takes up on the terminal, wide characters count twice
+pub fn width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// Cut `s` down to at most `max` columns, marking the cut with `…`
+pub fn truncate(s: &str, max: usize) -> String {
+    if width(s) <= max {
+        return s.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = UnicodeWidthChar::width(c).unwrap_or(0);
+        if used + w + 1 > max {
+            break;
+        }
+        used += w;
+        out.push(c);
+    }
+    if max > 0 {
+        out.push('…');
+    }
+    out
+}
+
+/// Truncate or fill `s` with spaces to exactly `cols` columns
+pub fn pad(s: &str, cols: usize) -> String {
+    let mut out = truncate(s, cols);
+    let w = width(&out);
+    out.push_str(&" ".repeat(cols.saturating_sub(w)));
+    out
+}
+
+/// Everything the task workers report. Workers only send events, a single
+/// render thread turns them into output so lines from parallel tasks can't
+/// interleave.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Sent before the first task with the names of the queued tasks.
+    /// `name_width` is the width of the package name column, including the
+    /// brackets, and `syncing` what the first step of a task does, like
+    /// `cloning`.
+    Begin {
+        names: Vec<String>,
+        name_width: usize,
+        syncing: &'static str,
+    },
+    Start {
+        id: usize,
+        name: String,
+    },
+    /// Spinner tick of a running task
+    Spin {
+        id: usize,
+        elapsed: Duration,
+    },
+    Message {
+        id: usize,
+        msg: String,
+    },
+    Finish {
+        id: usize,
+        status: Status,
+        elapsed: Duration,
+        msg: String,
+    },
+}
+
+/// A frontend for the event stream
+pub trait Renderer: Send {
+    fn handle(&mut self, event: Event);
+
+    /// Called once after the last event
+    fn finish(&mut self) {}
+}
+
+/// Tasks are drawn in two parts: finished tasks are printed once and left
+/// to the terminal's scrollback, running tasks form a live area at the
+/// bottom which is redrawn on every change and never taller than the
+/// terminal.
+#[derive(Default)]
+pub struct Tty {
+    theme: Theme,
+    colors: Colors,
+    tasks: Vec<(usize, Task)>,
+    name_width: usize,
+    /// Tasks to run and finished so far, for the progress bar
+    total: usize,
+    finished: usize,
+    /// Lines of the live area currently on the terminal
+    drawn: usize,
+}
+
+impl Tty {
+    pub fn new(theme: Theme, colors: Colors) -> Tty {
+        Tty {
+            theme,
+            colors,
+            ..Tty::default()
+        }
+    }
+
+    fn modify<F: FnOnce(&mut Task)>(&mut self, id: usize, f: F) {
+        if let Some((_, task)) = self.tasks.iter_mut().find(|(i, _)| *i == id) {
+            if !task.flushed {
+                f(task);
+            }
+        }
+    }
+
+    /// `   [███░░░░░░░] 3/10`, at most 40 cells wide
+    fn progress_bar(&self, cols: usize) -> String {
+        let count = format!("{}/{}", self.finished, self.total);
+        let cells = cols.saturating_sub(count.len() + 7).min(40);
+        let filled = cells * self.finished / self.total;
+        let (full, empty) = self.theme.bar;
+        let bar: String = std::iter::repeat_n(full, filled)
+            .chain(std::iter::repeat_n(empty, cells - filled))
+            .collect();
+        format!("   [{}] {}", bar, count)
+    }
+
+    fn render(&mut self) {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        let _ = self.draw(&mut out).and_then(|_| out.flush());
+    }
+
+    fn draw<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let (cols, rows) = terminal::size()
+            .map(|(c, r)| (c as usize, r as usize))
+            .unwrap_or((80, 24));
+        queue!(out, cursor::Hide)?;
+        if self.drawn > 0 {
+            queue!(out, cursor::MoveUp(self.drawn as u16))?;
+        }
+        queue!(out, Print("\r"))?;
+
+        let width = self.name_width;
+        for (_, task) in self.tasks.iter_mut() {
+            if task.status != Status::Running && !task.flushed {
+                task.flushed = true;
+                self.finished += 1;
+                task.draw(out, &self.theme, &self.colors, width, cols)?;
+            }
+        }
+        // flushed tasks are never looked at again
+        self.tasks.retain(|(_, t)| !t.flushed);
+
+        // keep a line free so the live area never scrolls off the top,
+        // and one more for the progress bar
+        let bar = self.total > 0 && self.finished < self.total;
+        let room = rows.saturating_sub(1 + bar as usize).max(1);
+        let running = self.tasks.len();
+        let shown = if running > room { room - 1 } else { running };
+        for (_, task) in self.tasks.iter().take(shown) {
+            task.draw(out, &self.theme, &self.colors, width, cols)?;
+        }
+        let mut drawn = shown;
+        if running > shown {
+            let more = format!("   ... and {} more\r\n", running - shown);
+            queue!(out, Clear(ClearType::CurrentLine), Print(more))?;
+            drawn += 1;
+        }
+        if bar {
+            let line = self.progress_bar(cols);
+            queue!(
+                out,
+                Clear(ClearType::CurrentLine),
+                Print(line),
+                Print("\r\n")
+            )?;
+            drawn += 1;
+        }
+        self.drawn = drawn;
+        // the area shrinks as tasks finish or the terminal gets smaller
+        queue!(out, Clear(ClearType::FromCursorDown), cursor::Show)
+    }
+}
+
+impl Renderer for Tty {
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Begin {
+                names, name_width, ..
+            } => {
+                self.total = names.len();
+                self.name_width = name_width;
+            }
+            Event::Start { id, name } => self.tasks.push((
+                id,
+                Task {
+                    name,
+                    ticks: 0,
+                    status: Status::Running,
+                    elapsed: Duration::default(),
+                    message: String::new(),
+                    flushed: false,
+                },
+            )),
+            Event::Spin { id, elapsed } => self.modify(id, |t| {
+                t.ticks += 1;
+                t.elapsed = elapsed;
+            }),
+            Event::Message { id, msg } => self.modify(id, |t| t.message = msg),
+            Event::Finish {
+                id,
+                status,
+                elapsed,
+                msg,
+            } => self.modify(id, |t| {
+                t.status = status;
+                t.elapsed = elapsed;
+                t.message = msg;
+            }),
+        }
+        self.render();
+    }
+}
+
+/// Prints a line whenever a task starts, changes its message or finishes,
+/// with no cursor movement or animation. For screen readers and dumb
+/// terminals.
+#[derive(Default)]
+pub struct Simple {
+    names: Vec<(usize, String)>,
+}
+
+impl Simple {
+    fn name(&self, id: usize) -> &str {
+        self.names
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map(|(_, n)| n.as_str())
+            .unwrap_or("")
+    }
+}
+
+impl Renderer for Simple {
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Start { id, name } => self.names.push((id, name)),
+            Event::Message { id, msg } => println!("[{}] {}", self.name(id), msg),
+            Event::Finish {
+                id,
+                status,
+                elapsed,
+                msg,
+            } => {
+                let time = utils::format_duration(elapsed);
+                let msg = match status {
+                    Status::Failure => format!("failed: {}", msg),
+                    _ => msg,
+                };
+                println!("[{}] {} ({})", self.name(id), msg, time.trim());
+                self.names.retain(|(i, _)| *i != id);
+            }
+            Event::Begin { .. } | Event::Spin { .. } => (),
+        }
+    }
+}
+
+/// Prints a JSON object on its own line whenever a task changes state, for
+/// frontends drawing their own progress. Every object has an `event`, one of
+/// `queued`, the `syncing` step of Begin, `waiting` (to build), `building`,
+/// `message`, `done` or `failed`, and the `package`. `message` carries the
+/// text of messages and failures, `done` and `failed` the `elapsed_ms`.
+#[derive(Default)]
+pub struct Jsonl {
+    names: Vec<(usize, String)>,
+    syncing: &'static str,
+}
+
+impl Jsonl {
+    fn line(&self, event: &str, id: usize, rest: &[(&str, String)]) {
+        let name = self
+            .names
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map(|(_, n)| n.as_str())
+            .unwrap_or("");
+        println!("{}", json_line(event, name, rest));
+    }
 }
 
-pub fn inline_message(line: u16, offset: u16, msg: &str) {
-    async_print(
-        line,
-        offset + msg.len() as u16,
-        &format!("{}{}{}", cursor::Right(offset), clear::UntilNewline, msg),
+fn json_line(event: &str, package: &str, rest: &[(&str, String)]) -> String {
+    let mut fields = vec![
+        format!("\"event\": {}", utils::json_string(event)),
+        format!("\"package\": {}", utils::json_string(package)),
+    ];
+    fields.extend(
+        rest.iter()
+            .map(|(k, v)| format!("{}: {}", utils::json_string(k), v)),
     );
+    format!("{{{}}}", fields.join(", "))
 }
 
-pub fn message(line: u16, offset: u16, msg: &str) {
-    async_print(line, offset + msg.len() as u16, msg);
+impl Renderer for Jsonl {
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Begin { names, syncing, .. } => {
+                self.syncing = syncing;
+                for name in names {
+                    println!("{}", json_line("queued", &name, &[]));
+                }
+            }
+            Event::Start { id, name } => self.names.push((id, name)),
+            Event::Message { id, msg } => match msg.as_str() {
+                "syncing" => self.line(self.syncing, id, &[]),
+                "waiting to build" => self.line("waiting", id, &[]),
+                "building" => self.line("building", id, &[]),
+                _ => self.line("message", id, &[("message", utils::json_string(&msg))]),
+            },
+            Event::Finish {
+                id,
+                status,
+                elapsed,
+                msg,
+            } => {
+                let elapsed = ("elapsed_ms", elapsed.as_millis().to_string());
+                match status {
+                    Status::Failure => {
+                        let msg = ("message", utils::json_string(&msg));
+                        self.line("failed", id, &[msg, elapsed])
+                    }
+                    _ => self.line("done", id, &[elapsed]),
+                }
+                self.names.retain(|(i, _)| *i != id);
+            }
+            Event::Spin { .. } => (),
+        }
+    }
+}
+
+/// Which renderer tasks report to
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Ui {
+    #[default]
+    Tty,
+    Simple,
+    Jsonl,
+}
+
+#[derive(Default)]
+struct Output {
+    tx: Option<Sender<Event>>,
+    handle: Option<thread::JoinHandle<()>>,
+    next_id: usize,
+    ui: Ui,
+    theme: Theme,
+    colors: Colors,
+}
+
+lazy_static! {
+    static ref OUTPUT: Mutex<Output> = Mutex::new(Output::default());
+}
+
+pub fn set_ui(ui: Ui) {
+    OUTPUT.lock().unwrap().ui = ui;
+}
+
+pub fn set_theme(theme: Theme, colors: Colors) {
+    let mut output = OUTPUT.lock().unwrap();
+    output.theme = theme;
+    output.colors = colors;
+}
+
+pub fn ui() -> Ui {
+    OUTPUT.lock().unwrap().ui
+}
+
+/// A renderer for the selected ui
+pub fn renderer() -> Box<dyn Renderer> {
+    let output = OUTPUT.lock().unwrap();
+    match output.ui {
+        Ui::Tty => Box::new(Tty::new(output.theme.clone(), output.colors.clone())),
+        Ui::Simple => Box::new(Simple::default()),
+        Ui::Jsonl => Box::new(Jsonl::default()),
+    }
+}
+
+/// Bring the cursor back if pac panics while it is hidden
+fn restore_on_panic() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let default = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = execute!(io::stdout(), ResetColor, cursor::Show);
+            default(info);
+        }));
+    });
+}
+
+/// Spawn the render thread, events sent before are dropped
+pub fn start(mut renderer: Box<dyn Renderer>) {
+    restore_on_panic();
+    let (tx, rx) = channel();
+    let handle = thread::spawn(move || {
+        for event in rx {
+            renderer.handle(event);
+        }
+        renderer.finish();
+    });
+    let mut output = OUTPUT.lock().unwrap();
+    output.tx = Some(tx);
+    output.handle = Some(handle);
+}
+
+/// Wait for the render thread to handle every event sent so far
+pub fn stop() {
+    let handle = {
+        let mut output = OUTPUT.lock().unwrap();
+        output.tx = None;
+        output.handle.take()
+    };
+    if let Some(handle) = handle {
+        let _ = handle.join();
+    }
+}
+
+fn send(output: &Output, event: Event) {
+    if let Some(tx) = &output.tx {
+        let _ = tx.send(event);
+    }
+}
+
+/// Announce the tasks which will run, see `Event::Begin`
+pub fn begin(names: Vec<String>, name_width: usize, syncing: &'static str) {
+    let event = Event::Begin {
+        names,
+        name_width,
+        syncing,
+    };
+    send(&OUTPUT.lock().unwrap(), event);
+}
+
+/// Start a new task line, returns the id used to update it
+pub fn task(name: &str) -> usize {
+    let mut output = OUTPUT.lock().unwrap();
+    let id = output.next_id;
+    output.next_id += 1;
+    let name = name.to_string();
+    send(&output, Event::Start { id, name });
+    id
+}
+
+/// Advance the spinner of a running task
+pub fn spin(id: usize, elapsed: Duration) {
+    send(&OUTPUT.lock().unwrap(), Event::Spin { id, elapsed });
+}
+
+pub fn message(id: usize, msg: &str) {
+    let msg = msg.to_string();
+    send(&OUTPUT.lock().unwrap(), Event::Message { id, msg });
+}
+
+/// Mark a task as finished, moving it out of the live area
+pub fn finish(id: usize, status: Status, elapsed: Duration, msg: &str) {
+    let msg = msg.to_string();
+    let event = Event::Finish {
+        id,
+        status,
+        elapsed,
+        msg,
+    };
+    send(&OUTPUT.lock().unwrap(), event);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_task() {
+        let first = task("a");
+        task("b");
+        assert_eq!(task("c"), first + 2);
+    }
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width("vim-surround"), 12);
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(truncate("日本語", 5), "日本…");
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(pad("日本", 6), "日本  ");
+        assert_eq!(width(&pad("日本語です", 7)), 7);
+    }
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Renderer for Recorder {
+        fn handle(&mut self, event: Event) {
+            if let Event::Message { msg, .. } = event {
+                self.0.lock().unwrap().push(msg);
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_line() {
+        assert_eq!(
+            json_line(
+                "failed",
+                "me/x",
+                &[("message", utils::json_string("no \"x\""))]
+            ),
+            r#"{"event": "failed", "package": "me/x", "message": "no \"x\""}"#
+        );
+    }
 
     #[test]
-    fn test_line() {
-        line();
-        line();
-        assert_eq!(line(), 2);
+    fn test_render_thread() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        start(Box::new(Recorder(messages.clone())));
+        let id = task("a");
+        message(id, "syncing");
+        message(id, "done");
+        stop();
+        assert_eq!(*messages.lock().unwrap(), vec!["syncing", "done"]);
     }
 }
@@ -16,12 +16,26 @@ pub enum Error {
     Git(String),
     Editor,
     Build(String),
+    Command(String),
     PluginNotInstalled(String),
     NoPlugin,
     SkipLocal,
     PluginInstalled(String),
     PaconfigFile(String),
     CopyDir(String),
+    Manifest(String),
+    Network(String),
+    Registry(String),
+    Settings(String),
+    Auth(String),
+    Policy(String),
+    Category(String),
+    Name(String),
+    Set(String),
+    Space(String),
+    Archive(String),
+    Spec(String),
+    Store(String),
     SaveYaml,
     LoadYaml,
 }
@@ -35,10 +49,66 @@ impl Error {
         Error::Build(format!("Fail to build plugin: {}", s.as_ref()))
     }
 
+    pub fn command<T: AsRef<str>>(s: T) -> Error {
+        Error::Command(format!("Command failed: {}", s.as_ref()))
+    }
+
     pub fn plugin_installed<T: AsRef<Path>>(s: T) -> Error {
         Error::PluginInstalled(format!("Plugin already installed under {:?}", s.as_ref()))
     }
 
+    pub fn manifest(s: &str) -> Error {
+        Error::Manifest(format!("Invalid pac.yaml manifest: {}", s))
+    }
+
+    pub fn network<T: AsRef<str>>(url: &str, s: T) -> Error {
+        Error::Network(format!("Fail to download {}: {}", url, s.as_ref()))
+    }
+
+    pub fn registry<T: AsRef<str>>(s: T) -> Error {
+        Error::Registry(format!("Registry error: {}", s.as_ref()))
+    }
+
+    pub fn settings<T: AsRef<str>>(s: T) -> Error {
+        Error::Settings(format!("Invalid settings: {}", s.as_ref()))
+    }
+
+    pub fn auth<T: AsRef<str>>(s: T) -> Error {
+        Error::Auth(format!("Keyring error: {}", s.as_ref()))
+    }
+
+    pub fn policy<T: AsRef<str>>(s: T) -> Error {
+        Error::Policy(format!("Blocked by policy: {}", s.as_ref()))
+    }
+
+    pub fn category<T: AsRef<str>>(s: T) -> Error {
+        Error::Category(format!("Category error: {}", s.as_ref()))
+    }
+
+    pub fn name<T: AsRef<str>>(s: T) -> Error {
+        Error::Name(format!("Invalid package name: {}", s.as_ref()))
+    }
+
+    pub fn set<T: AsRef<str>>(s: T) -> Error {
+        Error::Set(format!("Package set error: {}", s.as_ref()))
+    }
+
+    pub fn space<T: AsRef<str>>(s: T) -> Error {
+        Error::Space(format!("Not enough disk space: {}", s.as_ref()))
+    }
+
+    pub fn archive<T: AsRef<str>>(s: T) -> Error {
+        Error::Archive(format!("Fail to unpack archive: {}", s.as_ref()))
+    }
+
+    pub fn spec<T: AsRef<str>>(s: T) -> Error {
+        Error::Spec(format!("Invalid plugin spec: {}", s.as_ref()))
+    }
+
+    pub fn store<T: AsRef<str>>(s: T) -> Error {
+        Error::Store(format!("Broken store: {}", s.as_ref()))
+    }
+
     pub fn plugin_not_installed(s: &str) -> Error {
         Error::PluginNotInstalled(format!("{} not installed", s))
     }
@@ -92,10 +162,24 @@ impl fmt::Display for Error {
             Error::Editor => write!(f, "Can not open editor"),
             Error::NoPlugin => write!(f, "Can not find such plugin"),
             Error::SkipLocal => write!(f, "Local plugin. Skipping"),
-            Error::Io(ref e) => write!(f, "{}", e.to_string()),
+            Error::Io(ref e) => write!(f, "{}", e),
             Error::Build(ref s)
+            | Error::Command(ref s)
             | Error::Git(ref s)
             | Error::CopyDir(ref s)
+            | Error::Manifest(ref s)
+            | Error::Network(ref s)
+            | Error::Registry(ref s)
+            | Error::Settings(ref s)
+            | Error::Auth(ref s)
+            | Error::Policy(ref s)
+            | Error::Category(ref s)
+            | Error::Name(ref s)
+            | Error::Set(ref s)
+            | Error::Space(ref s)
+            | Error::Archive(ref s)
+            | Error::Spec(ref s)
+            | Error::Store(ref s)
             | Error::PluginInstalled(ref s)
             | Error::PluginNotInstalled(ref s)
             | Error::PaconfigFile(ref s) => write!(f, "{}", s),
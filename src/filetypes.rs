@@ -0,0 +1,351 @@
+use crate::context::Context;
+use crate::package::Package;
+
+use std::fs;
+use std::path::Path;
+
+/// Filetypes vim and neovim detect out of the box, sorted
+const KNOWN: &[&str] = &[
+    "ada",
+    "ant",
+    "apache",
+    "arduino",
+    "asciidoc",
+    "asm",
+    "astro",
+    "autohotkey",
+    "awk",
+    "bash",
+    "bib",
+    "bzl",
+    "c",
+    "cabal",
+    "clojure",
+    "cmake",
+    "cobol",
+    "coffee",
+    "conf",
+    "config",
+    "cpp",
+    "crontab",
+    "cs",
+    "csh",
+    "css",
+    "csv",
+    "cucumber",
+    "cuda",
+    "cython",
+    "d",
+    "dart",
+    "debchangelog",
+    "debcontrol",
+    "desktop",
+    "diff",
+    "django",
+    "dockerfile",
+    "dosbatch",
+    "dosini",
+    "dot",
+    "dtd",
+    "dune",
+    "ebnf",
+    "editorconfig",
+    "eelixir",
+    "eiffel",
+    "elixir",
+    "elm",
+    "erb",
+    "erlang",
+    "eruby",
+    "fennel",
+    "fish",
+    "forth",
+    "fortran",
+    "fsharp",
+    "gdscript",
+    "gitcommit",
+    "gitconfig",
+    "gitignore",
+    "gitrebase",
+    "gitsendemail",
+    "gleam",
+    "glsl",
+    "gnuplot",
+    "go",
+    "gomod",
+    "gosum",
+    "gpg",
+    "graphql",
+    "groovy",
+    "haml",
+    "handlebars",
+    "haskell",
+    "hcl",
+    "heex",
+    "help",
+    "hjson",
+    "hlsl",
+    "html",
+    "htmldjango",
+    "http",
+    "i3config",
+    "idris",
+    "ini",
+    "j",
+    "java",
+    "javascript",
+    "javascriptreact",
+    "jinja",
+    "jq",
+    "json",
+    "json5",
+    "jsonc",
+    "jsonnet",
+    "julia",
+    "kotlin",
+    "latex",
+    "ledger",
+    "less",
+    "lilypond",
+    "liquid",
+    "lisp",
+    "lua",
+    "m4",
+    "mail",
+    "make",
+    "man",
+    "markdown",
+    "mason",
+    "matlab",
+    "meson",
+    "mf",
+    "mp",
+    "muttrc",
+    "mysql",
+    "nasm",
+    "netrw",
+    "nginx",
+    "nim",
+    "nix",
+    "nroff",
+    "nsis",
+    "objc",
+    "objcpp",
+    "ocaml",
+    "octave",
+    "org",
+    "pascal",
+    "perl",
+    "php",
+    "plantuml",
+    "po",
+    "pod",
+    "postscr",
+    "povray",
+    "prolog",
+    "proto",
+    "ps1",
+    "pug",
+    "puppet",
+    "purescript",
+    "python",
+    "qf",
+    "qml",
+    "r",
+    "racket",
+    "readline",
+    "rego",
+    "rescript",
+    "rmd",
+    "rnoweb",
+    "rst",
+    "rtf",
+    "ruby",
+    "rust",
+    "sass",
+    "scala",
+    "scheme",
+    "scss",
+    "sed",
+    "sh",
+    "sieve",
+    "slim",
+    "sml",
+    "solidity",
+    "sql",
+    "sshconfig",
+    "sshdconfig",
+    "starlark",
+    "strace",
+    "sudoers",
+    "svelte",
+    "svg",
+    "swift",
+    "systemd",
+    "tads",
+    "tcl",
+    "terraform",
+    "tex",
+    "texinfo",
+    "text",
+    "tmux",
+    "toml",
+    "tsv",
+    "tsx",
+    "twig",
+    "typescript",
+    "typescriptreact",
+    "typst",
+    "udevrules",
+    "vala",
+    "vb",
+    "verilog",
+    "vhdl",
+    "vim",
+    "vimwiki",
+    "vue",
+    "wast",
+    "wgsl",
+    "xdefaults",
+    "xf86conf",
+    "xhtml",
+    "xml",
+    "xquery",
+    "xs",
+    "xslt",
+    "yacc",
+    "yaml",
+    "zig",
+    "zsh",
+];
+
+/// Filetypes a plugin brings along: the names of its `ftdetect`, `ftplugin`,
+/// `syntax` and `indent` scripts and what its ftdetect scripts set
+pub fn provided(dir: &Path) -> Vec<String> {
+    let mut found = vec![];
+    for sub in &["ftdetect", "ftplugin", "syntax", "indent"] {
+        let entries = match dir.join(sub).read_dir() {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                // ftplugin/rust_extra.vim is for rust too
+                found.push(stem.split('_').next().unwrap_or(stem).to_string());
+            }
+            if *sub == "ftdetect" {
+                found.extend(set_in(&fs::read_to_string(&path).unwrap_or_default()));
+            }
+        }
+    }
+    found
+}
+
+/// Filetypes an ftdetect script sets with `setfiletype x`, `setf x`,
+/// `set filetype=x` or `ft=x` and lua's `filetype = 'x'`
+fn set_in(script: &str) -> Vec<String> {
+    let word = |s: &str| -> Option<String> {
+        let s = s.trim_start_matches([' ', '=', '\'', '"']);
+        let w: String = s
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+            .collect();
+        Some(w).filter(|w| !w.is_empty())
+    };
+    let mut found = vec![];
+    for line in script.lines() {
+        for (i, m) in line.match_indices("setf") {
+            let rest = &line[i + m.len()..];
+            let rest = rest.strip_prefix("iletype").unwrap_or(rest);
+            if rest.starts_with(' ') {
+                found.extend(word(rest));
+            }
+        }
+        for key in &["filetype", "ft"] {
+            for (i, m) in line.match_indices(key) {
+                let before = line[..i].chars().last();
+                let rest = line[i + m.len()..].trim_start();
+                if before.is_none_or(|c| !c.is_alphanumeric()) && rest.starts_with('=') {
+                    found.extend(word(rest));
+                }
+            }
+        }
+    }
+    found
+}
+
+/// The `for:` values of `pack` which no known or bundled filetype is called,
+/// each with the closest known name if there is one. Patterns like `*.md`
+/// are left alone.
+pub fn unknown(ctx: &Context, pack: &Package) -> Vec<(String, Option<&'static str>)> {
+    let provided = provided(&pack.repo_path(ctx));
+    pack.for_types
+        .iter()
+        .filter(|t| !t.contains(['*', '?', '[']))
+        .filter(|t| KNOWN.binary_search(&t.as_str()).is_err() && !provided.contains(t))
+        .map(|t| (t.clone(), closest(t)))
+        .collect()
+}
+
+fn closest(name: &str) -> Option<&'static str> {
+    KNOWN
+        .iter()
+        .map(|k| (distance(name, k), *k))
+        .filter(|(d, _)| *d <= 2)
+        .min()
+        .map(|(_, k)| k)
+}
+
+/// Levenshtein distance
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(cur)
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn filetypes_unknown() {
+        assert!(KNOWN.windows(2).all(|w| w[0] < w[1]));
+
+        let root = env::temp_dir().join(format!("pac-filetypes-{}", process::id()));
+        let ctx = Context::with_paths(root.join("vim"), root.join("paconfig.yaml"));
+        let mut pack = Package::new("vim-just", "https://github.com/me/vim-just", "lang", true);
+        let dir = pack.repo_path(&ctx);
+        fs::create_dir_all(dir.join("ftdetect")).unwrap();
+        fs::write(
+            dir.join("ftdetect/just.vim"),
+            "au BufNewFile,BufRead *.just setfiletype just\n\
+             au BufRead Justfile set ft=justfile\n",
+        )
+        .unwrap();
+        pack.set_types(vec![
+            "just".into(),
+            "justfile".into(),
+            "pyton".into(),
+            "*.j".into(),
+        ]);
+
+        let unknown = unknown(&ctx, &pack);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(unknown, vec![("pyton".to_string(), Some("python"))]);
+    }
+}
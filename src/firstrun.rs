@@ -0,0 +1,200 @@
+//! Taking over the plugins found on the first run, before there is a
+//! paconfig: git checkouts under `pack/` and the directories of vim-plug and
+//! Vundle
+
+use crate::context::Context;
+use crate::git;
+use crate::notify;
+use crate::package::{self, Package};
+use crate::prompt;
+use crate::warnings::{self, Kind};
+use crate::{Error, Result};
+
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+/// A checkout pac can manage, not moved yet
+#[derive(Debug)]
+struct Found {
+    pack: Package,
+    dir: PathBuf,
+}
+
+/// Directories other plugin managers clone into. `plugged/` is vim-plug's
+/// default for vim and `stdpath('data')/plugged` for neovim, `bundle/` is
+/// Vundle's.
+fn manager_dirs(ctx: &Context) -> Vec<PathBuf> {
+    let root = ctx.pack_dir.parent().unwrap_or(&ctx.pack_dir);
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|d| d.is_absolute())
+        .or_else(|| dirs::home_dir().map(|h| h.join(".local/share")));
+    let mut found = vec![root.join("plugged")];
+    found.extend(data_home.map(|d| d.join("nvim/plugged")));
+    found.push(root.join("bundle"));
+    found
+}
+
+/// The checkouts under `pack/` and the plugin manager directories, and the
+/// directories under `pack/` which are no git clones and stay where they are
+fn scan(ctx: &Context) -> Result<(Vec<Found>, Vec<PathBuf>)> {
+    let mut found: Vec<Found> = vec![];
+    let mut unmanaged = vec![];
+    let mut add = |pack: Package, dir: PathBuf| {
+        // the same plugin cloned twice is taken from the first place
+        if !found.iter().any(|f| f.pack.idname == pack.idname) {
+            found.push(Found { pack, dir });
+        }
+    };
+    for p in package::installed(ctx)? {
+        if p.path.is_symlink() {
+            continue;
+        }
+        match git::origin_url(&p.path) {
+            Some(remote) => add(Package::new(&p.name, &remote, &p.category, p.opt), p.path),
+            None => unmanaged.push(p.path),
+        }
+    }
+    for dir in manager_dirs(ctx) {
+        let entries = match dir.read_dir() {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        let mut dirs: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+        dirs.sort();
+        for dir in dirs.into_iter().filter(|d| d.is_dir()) {
+            let name = dir.file_name().unwrap_or_default().to_string_lossy();
+            if let Some(remote) = git::origin_url(&dir) {
+                add(Package::new(&name, &remote, "default", false), dir.clone());
+            }
+        }
+    }
+    Ok((found, unmanaged))
+}
+
+/// Move a checkout into the store and link it from `pack/`, putting it back
+/// if it can't be linked
+fn relocate(ctx: &Context, found: &Found) -> Result<()> {
+    let store = found.pack.store_path(ctx);
+    if store.exists() {
+        return Err(Error::plugin_installed(&store));
+    }
+    if let Some(parent) = store.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&found.dir, &store)?;
+    if let Err(e) = found.pack.link(ctx) {
+        fs::rename(&store, &found.dir)?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Offer to build a paconfig from the plugins already on disk when pac runs
+/// for the first time on a terminal. A declined offer is not made again.
+pub fn offer(ctx: &Context) -> Result<()> {
+    let declined = ctx.first_run_file();
+    if ctx.config_file.exists() || declined.exists() || !io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let (found, unmanaged) = scan(ctx)?;
+    if found.is_empty() {
+        return Ok(());
+    }
+    println!(
+        "There is no paconfig yet, pac can manage these {}:",
+        notify::packages(found.len())
+    );
+    for f in &found {
+        println!("  {} ({})", f.pack.idname, f.dir.display());
+    }
+    let question = "Move them into pac's store and build a paconfig from them?";
+    if !prompt::confirm(question, true)? {
+        fs::create_dir_all(&ctx.state_dir)?;
+        fs::write(&declined, "")?;
+        println!(
+            "Not asking again, remove {} to be asked",
+            declined.display()
+        );
+        return Ok(());
+    }
+
+    let mut packs = vec![];
+    let mut from_managers = false;
+    for f in found {
+        match relocate(ctx, &f) {
+            Ok(()) => {
+                from_managers |= !f.dir.starts_with(&ctx.pack_dir);
+                packs.push(f.pack);
+            }
+            Err(e) => {
+                let message = format!("{} is not moved: {}", f.dir.display(), e);
+                warnings::push(Kind::Skipped, Some(&f.pack.idname), message);
+            }
+        }
+    }
+    for dir in unmanaged {
+        let message = format!("{} is not a git clone, left in place", dir.display());
+        warnings::push(Kind::Skipped, None, message);
+    }
+    package::sort(&mut packs);
+    package::save(ctx, packs.clone())?;
+    package::update_pac_plugin(ctx, &packs)?;
+    println!(
+        "Wrote {} with {}",
+        ctx.config_file.display(),
+        notify::packages(packs.len())
+    );
+    if from_managers {
+        println!("Remove the vim-plug or Vundle setup from your vimrc, pac loads these now");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Repository;
+    use std::path::Path;
+    use std::process;
+
+    #[test]
+    fn firstrun_scan() {
+        let root = env::temp_dir().join(format!("pac-firstrun-{}", process::id()));
+        let ctx = Context::with_paths(root.join("vim"), root.join("paconfig.yaml"));
+        let clone = |dir: &Path, remote: &str| {
+            let repo = Repository::init(dir).unwrap();
+            repo.remote("origin", remote).unwrap();
+        };
+        clone(
+            &ctx.pack_dir.join("lang/opt/vim-go"),
+            "https://github.com/fatih/vim-go",
+        );
+        clone(
+            &root.join("vim/plugged/goyo.vim"),
+            "https://github.com/junegunn/goyo.vim.git",
+        );
+        clone(
+            &root.join("vim/bundle/vim-go"),
+            "https://github.com/fatih/vim-go",
+        );
+        fs::create_dir_all(ctx.pack_dir.join("default/start/mine")).unwrap();
+
+        let (found, unmanaged) = scan(&ctx).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        let found: Vec<_> = found
+            .iter()
+            .map(|f| (f.pack.idname.as_str(), f.pack.category.as_str(), f.pack.opt))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("fatih/vim-go", "lang", true),
+                ("junegunn/goyo.vim", "default", false)
+            ]
+        );
+        assert_eq!(unmanaged, vec![ctx.pack_dir.join("default/start/mine")]);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::package::Package;
+
+/// Score how well `query` matches `text`, `None` if the characters of
+/// `query` don't all appear in `text` in order. Consecutive characters and
+/// characters at the start of a word score higher, case is ignored.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut total = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+    for q in query.to_lowercase().chars() {
+        let found = text[pos..].iter().position(|&c| c == q)? + pos;
+        total += 1;
+        if last.is_some_and(|l| l + 1 == found) {
+            total += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            total += 3;
+        }
+        // matches further from the previous one are worth less
+        total -= (found - last.map_or(0, |l| l + 1)).min(3) as i64;
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(total)
+}
+
+/// Best score of `query` against the idname, name and description
+pub fn score_package(query: &str, pack: &Package) -> Option<i64> {
+    let desc = pack.description.as_deref().unwrap_or("");
+    [pack.idname.as_str(), pack.name.as_str(), desc]
+        .iter()
+        .filter_map(|text| score(query, text))
+        .max()
+}
+
+/// Packages matching `query`, best match first
+pub fn search<'a>(query: &str, packs: &'a [Package]) -> Vec<&'a Package> {
+    let mut found: Vec<(i64, &Package)> = packs
+        .iter()
+        .filter_map(|p| score_package(query, p).map(|s| (s, p)))
+        .collect();
+    // stable, so equal scores keep the paconfig order
+    found.sort_by_key(|f| std::cmp::Reverse(f.0));
+    found.into_iter().map(|(_, p)| p).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_score() {
+        assert!(score("srnd", "tpope/vim-surround").is_some());
+        assert!(score("xyz", "tpope/vim-surround").is_none());
+        assert!(score("fug", "vim-fugitive") > score("fug", "fzf-lua-git"));
+
+        let packs = vec![
+            Package::new(
+                "fzf-lua",
+                "https://github.com/ibhagwan/fzf-lua",
+                "default",
+                false,
+            ),
+            Package::new(
+                "fzf.vim",
+                "https://github.com/junegunn/fzf.vim",
+                "default",
+                false,
+            ),
+        ];
+        let found = search("FZFvim", &packs);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].idname, "junegunn/fzf.vim");
+    }
+}
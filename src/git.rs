@@ -1,37 +1,257 @@
+use crate::auth;
+use crate::context::Context;
+use crate::policy;
+use crate::refcache;
+use crate::utils;
 use crate::{Error, Result};
 
-use git2::{self, Repository};
+use git2::{self, Oid, Repository};
+use lazy_static::lazy_static;
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// Answer https authentication requests with the token of the remote's host
+/// (see `pac auth`), once, so a rejected token fails instead of looping
+fn callbacks<'a>() -> git2::RemoteCallbacks<'a> {
+    let mut callbacks = git2::RemoteCallbacks::new();
+    let mut tried = false;
+    callbacks.credentials(move |url, username, allowed| {
+        // tokens are never sent over plain http
+        let found = Some(url)
+            .filter(|u| u.starts_with("https://"))
+            .and_then(auth::host)
+            .and_then(|h| auth::token(h).map(|t| (h, t.0)));
+        match found {
+            Some((host, token))
+                if !tried && allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) =>
+            {
+                tried = true;
+                let user = username.unwrap_or_else(|| auth::token_user(host));
+                git2::Cred::userpass_plaintext(user, &token)
+            }
+            _ => Err(git2::Error::from_str(
+                "authentication required, add a token with `pac auth login`",
+            )),
+        }
+    });
+    callbacks
+}
 
 /// Fetch from a remote repo (branches and tags). Does not change working tree.
 /// Returns the default remote branch.
 fn fetch(repo: &Repository, remote: &str) -> Result<String> {
     let mut opts = git2::FetchOptions::new();
     opts.download_tags(git2::AutotagOption::All)
-        .update_fetchhead(true);
+        .update_fetchhead(true)
+        .remote_callbacks(callbacks());
 
     // fetch branches and tags
     let refspec = ["refs/heads/*:refs/heads/*", "refs/tags/*:refs/tags/*"];
-    let mut remote = repo.remote_anonymous(&remote)?;
+    let mut remote = repo.remote_anonymous(remote)?;
     remote.fetch(&refspec, Some(&mut opts), None)?;
 
     remote
         .default_branch()?
         .as_str()
-        .ok_or_else(|| Error::Git(
-            "Default branch name is invalid utf-8".to_string(),
-        ))
+        .ok_or_else(|| Error::Git("Default branch name is invalid utf-8".to_string()))
         // s is of the form "refs/heads/master" so split and use "master" only
         .map(|s| s.to_string())
 }
 
+lazy_static! {
+    static ref BINARY: RwLock<PathBuf> = RwLock::new(PathBuf::from("git"));
+}
+
+/// Searched after `$PATH`, editors started from a desktop often get a
+/// minimal one
+const FALLBACK_DIRS: &[&str] = &["/opt/homebrew/bin", "/usr/local/bin", "/usr/bin"];
+
+/// Where the git binary is: the `git_binary` setting, a name looked up like
+/// `git` or a path, else `git` on `$PATH` or in the usual places
+pub fn find_binary(configured: Option<&str>) -> Option<PathBuf> {
+    let name = match configured {
+        Some(c) => match (c.strip_prefix("~/"), env::var_os("HOME")) {
+            (Some(rest), Some(home)) => Path::new(&home).join(rest).to_string_lossy().into(),
+            _ => c.to_string(),
+        },
+        None => "git".to_string(),
+    };
+    utils::find_executable(&name).or_else(|| {
+        FALLBACK_DIRS
+            .iter()
+            .map(|d| Path::new(d).join(&name))
+            .find(|p| !name.contains('/') && p.is_file())
+    })
+}
+
+/// Run the git binary found for `configured` for the rest of the run, plain
+/// `git` if there is none so running it fails as usual
+pub fn set_binary(configured: Option<&str>) {
+    let found = find_binary(configured);
+    *BINARY.write().unwrap() = found.unwrap_or_else(|| PathBuf::from(configured.unwrap_or("git")));
+}
+
+pub fn binary() -> PathBuf {
+    BINARY.read().unwrap().clone()
+}
+
+/// `git version 2.43.0` of the git binary
+pub fn version() -> Option<String> {
+    let output = Command::new(binary()).arg("--version").output().ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|v| output.status.success() && !v.is_empty())
+}
+
+/// Run the git binary in `dir`, returning its output. It is used where
+/// libgit2 falls short: pushing through the user's credential helpers and
+/// ssh setup, shallow fetches and adopting existing directories. Remotes
+/// and other values from paconfig go after `--end-of-options`, so that
+/// none is read as an option.
+pub fn run(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new(binary())
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Git(format!(
+            "git {} failed: {}",
+            args[0],
+            stderr.trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string())
+}
+
+/// Url of the `origin` remote of the repo at `path`, `None` when it is no
+/// git repo or has no origin
+pub fn origin_url(path: &Path) -> Option<String> {
+    let repo = Repository::open(path).ok()?;
+    let remote = repo.find_remote("origin").ok()?;
+    remote.url().map(String::from)
+}
+
+/// Make the files at `dir` a clone of `remote` at its default branch. The
+/// files are left alone, what differs from the remote shows up as local
+/// changes.
+pub fn adopt(dir: &Path, remote: &str) -> Result<()> {
+    if Repository::open(dir).is_err() {
+        run(dir, &["init", "--quiet"])?;
+    }
+    if origin_url(dir).is_some() {
+        run(
+            dir,
+            &["remote", "set-url", "--end-of-options", "origin", remote],
+        )?;
+    } else {
+        run(
+            dir,
+            &["remote", "add", "--end-of-options", "origin", remote],
+        )?;
+    }
+    run(dir, &["fetch", "--quiet", "--tags", "origin"])?;
+    run(dir, &["remote", "set-head", "origin", "--auto"])?;
+    run(dir, &["reset", "--quiet", "origin/HEAD"])?;
+    Ok(())
+}
+
+/// Like `fetch` but only for the commits made after `since`, a date like
+/// `1 year` or `2020-01-01`. libgit2 cannot fetch shallow, the git binary
+/// does.
+fn fetch_since(repo: &Repository, remote: &str, since: &str) -> Result<String> {
+    let dir = repo
+        .workdir()
+        .ok_or_else(|| Error::Git("Shallow fetches need a working tree".to_string()))?;
+    let since = format!("--shallow-since={}", since);
+    run(
+        dir,
+        &[
+            "fetch",
+            "--quiet",
+            "--update-head-ok",
+            "--tags",
+            &since,
+            "--end-of-options",
+            remote,
+            "+refs/heads/*:refs/heads/*",
+        ],
+    )?;
+    let head = run(
+        dir,
+        &["ls-remote", "--symref", "--end-of-options", remote, "HEAD"],
+    )?;
+    symref_head(&head).ok_or_else(|| Error::Git("Remote has no default branch".to_string()))
+}
+
+/// `refs/heads/main` from the `ref: refs/heads/main\tHEAD` line of
+/// `git ls-remote --symref`
+fn symref_head(output: &str) -> Option<String> {
+    output.lines().find_map(|l| {
+        let (target, name) = l.strip_prefix("ref: ")?.split_once('\t')?;
+        Some(target.to_string()).filter(|_| name == "HEAD")
+    })
+}
+
+/// Time spent in the steps of syncing a repo
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SyncTimes {
+    pub fetch: Duration,
+    /// Checking out the ref, including submodules
+    pub checkout: Duration,
+}
+
+/// Numeric parts of a release tag like `v1.2.0`, `None` for other tags and
+/// pre-releases like `1.0-rc1`
+fn version_key(tag: &str) -> Option<Vec<u64>> {
+    let version = tag.trim_start_matches(['v', 'V']);
+    version.split('.').map(|p| p.parse().ok()).collect()
+}
+
+/// Newest of the release tags in `tags`
+fn latest_tag<'a, I: Iterator<Item = &'a str>>(tags: I) -> Option<&'a str> {
+    tags.filter_map(|t| version_key(t).map(|k| (k, t)))
+        .max()
+        .map(|(_, t)| t)
+}
+
 /// Fetch changes from remote for a local repo, discard changes in working tree,
-/// checkout the given reference (or master if its None) and update submodules.
-fn sync_repo(repo: &Repository, remote: &str, refname: Option<String>) -> Result<()> {
-    let default_branch = fetch(&repo, remote)?;
+/// checkout the given reference (or master if its None) and update submodules
+/// recursively if `submodules` is set. Without a reference the newest release
+/// tag is checked out if `tags` is set, falling back to the default branch for
+/// repos without releases. History older than `since` is left out.
+fn sync_repo(
+    repo: &Repository,
+    remote: &str,
+    refname: Option<String>,
+    tags: bool,
+    since: Option<&str>,
+    submodules: bool,
+) -> Result<SyncTimes> {
+    policy::check_remote(remote)?;
+    let start = Instant::now();
+    let default_branch = match since {
+        Some(since) => fetch_since(repo, remote, since)?,
+        None => fetch(repo, remote)?,
+    };
+    let fetched = Instant::now();
 
-    let refname = refname.unwrap_or(default_branch);
+    let refname = match refname {
+        Some(r) => r,
+        None if tags => {
+            let names = repo.tag_names(None)?;
+            latest_tag(names.iter().flatten())
+                .map(|t| format!("refs/tags/{}", t))
+                .unwrap_or(default_branch)
+        }
+        None => default_branch,
+    };
     // `object` will always point to a commit disregarding intermediate
     // refs. `gitref` will be this intermediate ref, if applicable.
     let (object, gitref) = repo.revparse_ext(&refname)?;
@@ -48,8 +268,13 @@ fn sync_repo(repo: &Repository, remote: &str, refname: Option<String>) -> Result
         None => repo.set_head_detached(object.id()),
     }?;
 
-    update_submodules(&repo)?;
-    Ok(())
+    if submodules {
+        update_submodules(repo)?;
+    }
+    Ok(SyncTimes {
+        fetch: fetched - start,
+        checkout: fetched.elapsed(),
+    })
 }
 
 fn update_submodules(repo: &Repository) -> Result<()> {
@@ -72,25 +297,404 @@ fn update_submodules(repo: &Repository) -> Result<()> {
     Ok(())
 }
 
+/// Private ref the remote's default branch is fetched to, so that neither
+/// the branches nor the working tree change
+const UPSTREAM_REF: &str = "refs/pac/upstream";
+
+/// Private ref the tracked branch of a pinned package is fetched to
+const TRACKED_REF: &str = "refs/pac/tracked";
+
+/// Fetch the default branch of the remote to `UPSTREAM_REF`, returns its tip
+fn fetch_upstream(repo: &Repository, remote: &str) -> Result<Oid> {
+    policy::check_remote(remote)?;
+    let mut remote = repo.remote_anonymous(remote)?;
+    remote.connect_auth(git2::Direction::Fetch, Some(callbacks()), None)?;
+    let branch = remote
+        .default_branch()?
+        .as_str()
+        .ok_or_else(|| Error::Git("Default branch name is invalid utf-8".to_string()))?
+        .to_string();
+    remote.disconnect()?;
+    fetch_into(repo, &mut remote, &branch, UPSTREAM_REF)
+}
+
+/// Fetch the ref `from` of `remote` to the private ref `into`, returns its tip
+fn fetch_into(repo: &Repository, remote: &mut git2::Remote, from: &str, into: &str) -> Result<Oid> {
+    let mut opts = git2::FetchOptions::new();
+    opts.download_tags(git2::AutotagOption::None)
+        .remote_callbacks(callbacks());
+    let refspec = format!("+{}:{}", from, into);
+    remote.fetch(&[refspec.as_str()], Some(&mut opts), None)?;
+    Ok(repo.refname_to_id(into)?)
+}
+
+/// The checked out commit against the default branch of the remote
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Upstream {
+    /// Commit time of the default branch's tip, seconds since the epoch
+    pub last_commit: i64,
+    /// Commit time of the checked out commit
+    pub checked_out: i64,
+    /// Commits on the default branch which are not checked out
+    pub behind: usize,
+    /// Checked out commits which are not on the default branch, like the
+    /// changes of a fork
+    pub ahead: usize,
+}
+
+/// How a local checkout compares to its remote
+#[derive(Debug, PartialEq)]
+pub enum RemoteStatus {
+    UpToDate,
+    /// Remote has moved from the `local` commit to `remote`
+    Outdated {
+        local: Oid,
+        remote: Oid,
+    },
+    /// Checked out at a commit, there is nothing to compare against
+    Pinned,
+    /// Pinned, the tracked `branch` has moved `behind` commits past the
+    /// `local` commit to `remote`
+    Tracking {
+        branch: String,
+        local: Oid,
+        remote: Oid,
+        behind: usize,
+    },
+}
+
+/// Left in `.git` while a clone runs, to tell a clone cut short from a
+/// checkout
+const CLONING_MARK: &str = "pac-cloning";
+
+/// Whether the directory at `path` is a clone an interrupted run left
+/// behind: no repo, no commit checked out or still marked as cloning
+pub fn is_partial_clone(path: &Path) -> bool {
+    match Repository::open(path) {
+        Ok(repo) => {
+            repo.path().join(CLONING_MARK).exists()
+                || repo.head().and_then(|h| h.peel_to_commit()).is_err()
+        }
+        Err(_) => true,
+    }
+}
+
+/// Commit checked out in the repository at `path` and whether tracked files
+/// have uncommitted changes, `None` if it is not a git repository
+pub fn checkout(path: &Path) -> Option<(Oid, bool)> {
+    let repo = Repository::open(path).ok()?;
+    let head = repo.head().ok()?.peel_to_commit().ok()?.id();
+    // untracked files like the generated doc/tags don't count
+    let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(false).include_ignored(false);
+    let dirty = repo.statuses(Some(&mut opts)).is_ok_and(|s| !s.is_empty());
+    Some((head, dirty))
+}
+
+/// The checkout at `path` as `git describe --tags` names it, like `v1.2` or
+/// `v1.2-3-gabc1234`, or its short commit id if no tag is reachable
+pub fn describe(path: &Path) -> Option<String> {
+    let repo = Repository::open(path).ok()?;
+    let mut opts = git2::DescribeOptions::new();
+    opts.describe_tags().show_commit_oid_as_fallback(true);
+    let description = repo.describe(&opts).ok()?;
+    let mut format = git2::DescribeFormatOptions::new();
+    format.abbreviated_size(7);
+    description.format(Some(&format)).ok()
+}
+
+/// The refs of `remote`, listed like `git ls-remote` does or taken from the
+/// listings of this run and of recent ones (see `refcache`)
+fn remote_refs(ctx: &Context, repo: &Repository, url: &str) -> Result<Vec<(String, Oid)>> {
+    policy::check_remote(url)?;
+    if let Some(refs) = refcache::get(ctx, url) {
+        return Ok(refs);
+    }
+    let mut remote = repo.remote_anonymous(url)?;
+    remote.connect_auth(git2::Direction::Fetch, Some(callbacks()), None)?;
+    let refs: Vec<(String, Oid)> = remote
+        .list()?
+        .iter()
+        .filter(|h| refcache::kept(h.name()))
+        .map(|h| (h.name().to_string(), h.oid()))
+        .collect();
+    refcache::put(url, refs.clone());
+    Ok(refs)
+}
+
+/// Find the commit `refname` points to on the remote. Only the refs are
+/// listed, nothing is fetched or written to the repo.
+/// Returns `None` if the remote has no such ref, ie. it is a commit id.
+fn remote_oid(
+    ctx: &Context,
+    repo: &Repository,
+    remote: &str,
+    refname: Option<&str>,
+    tags: bool,
+) -> Result<Option<Oid>> {
+    let heads = remote_refs(ctx, repo, remote)?;
+
+    let latest = if refname.is_none() && tags {
+        let names = heads.iter().filter_map(|(name, _)| {
+            let tag = name.strip_prefix("refs/tags/")?;
+            Some(tag.trim_end_matches("^{}"))
+        });
+        latest_tag(names).map(|t| t.to_string())
+    } else {
+        None
+    };
+    let candidates = match refname.or(latest.as_deref()) {
+        None => vec!["HEAD".to_string()],
+        Some(r) => vec![
+            format!("refs/heads/{}", r),
+            // peeled annotated tag points to the commit, prefer it
+            format!("refs/tags/{}^{{}}", r),
+            format!("refs/tags/{}", r),
+            r.to_string(),
+        ],
+    };
+    let oid = candidates.iter().find_map(|c| {
+        heads
+            .iter()
+            .find(|(name, _)| name == c)
+            .map(|(_, oid)| *oid)
+    });
+    Ok(oid)
+}
+
+/// Name the remotes of a fork's checkout, `origin` for the fork and
+/// `upstream` for the repo it was forked from, and fetch the branches of
+/// `upstream` to `refs/remotes/upstream/*` to merge from
+fn track_upstream(repo: &Repository, origin: &str, upstream: &str) -> Result<()> {
+    policy::check_remote(upstream)?;
+    for (name, url) in [("origin", origin), ("upstream", upstream)] {
+        match repo.find_remote(name) {
+            Ok(r) if r.url() == Some(url) => (),
+            Ok(_) => repo.remote_set_url(name, url)?,
+            Err(_) => {
+                repo.remote(name, url)?;
+            }
+        }
+    }
+    let mut opts = git2::FetchOptions::new();
+    opts.download_tags(git2::AutotagOption::None)
+        .remote_callbacks(callbacks());
+    repo.find_remote("upstream")?.fetch(
+        &["+refs/heads/*:refs/remotes/upstream/*"],
+        Some(&mut opts),
+        None,
+    )?;
+    Ok(())
+}
+
+/// Merge the default branch of `upstream` into the checked out branch,
+/// fast-forwarding when the branch has no commits of its own. Conflicts or
+/// local changes in the way leave everything as it was.
+fn merge_upstream(repo: &Repository, upstream: &str) -> Result<()> {
+    let oid = fetch_upstream(repo, upstream)?;
+    let head = repo.head()?;
+    let branch = match head.name() {
+        Some(name) if head.is_branch() => name.to_string(),
+        _ => {
+            return Err(Error::Git(
+                "not on a branch, there is nothing to merge upstream into".to_string(),
+            ))
+        }
+    };
+    let (analysis, _) = repo.merge_analysis(&[&repo.find_annotated_commit(oid)?])?;
+    if analysis.is_up_to_date() {
+        return Ok(());
+    }
+    let target = if analysis.is_fast_forward() {
+        oid
+    } else {
+        let ours = head.peel_to_commit()?;
+        let theirs = repo.find_commit(oid)?;
+        let mut index = repo.merge_commits(&ours, &theirs, None)?;
+        if index.has_conflicts() {
+            return Err(Error::Git(
+                "upstream conflicts with the checkout, merge it there with git".to_string(),
+            ));
+        }
+        let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+        let sig = repo.signature().map_err(|_| {
+            Error::Git("merging needs user.name and user.email in the git config".to_string())
+        })?;
+        repo.commit(None, &sig, &sig, "Merge upstream", &tree, &[&ours, &theirs])?
+    };
+    let mut opts = git2::build::CheckoutBuilder::new();
+    opts.safe();
+    repo.checkout_tree(&repo.find_object(target, None)?, Some(&mut opts))?;
+    repo.reference(&branch, target, true, "pac: merge upstream")?;
+    Ok(())
+}
+
 /// Trait representing high level git operations on a repo
 pub trait GitRepo {
     /// Get (remote_url, local_path, reference) for cloning and updating repo
-    fn clone_info(&self) -> (&str, PathBuf, Option<String>);
+    fn clone_info(&self, ctx: &Context) -> (String, PathBuf, Option<String>);
+
+    /// The repo a fork was made from, fetched along with the fork
+    fn upstream(&self) -> Option<&str> {
+        None
+    }
+
+    /// Only fetch history after this date, see `fetch_since`
+    fn history_since(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether to follow the newest release tag when no reference is given
+    fn follows_tags(&self) -> bool {
+        false
+    }
+
+    /// Branch to compare a pinned checkout with, see `git_remote_status`
+    fn tracked_branch(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether to init and update the submodules, and theirs, after syncing
+    fn submodules(&self) -> bool {
+        true
+    }
 
     /// Clone a remote repository and update submodules.
-    fn git_clone(&self) -> Result<()> {
-        let (remote, path, rev) = self.clone_info();
+    fn git_clone(&self, ctx: &Context) -> Result<SyncTimes> {
+        let (remote, path, rev) = self.clone_info(ctx);
         let repo = git2::Repository::init(&path)?;
-        let result = sync_repo(&repo, remote, rev);
-        if result.is_err() {
-            fs::remove_dir_all(&path)?;
+        fs::write(repo.path().join(CLONING_MARK), "")?;
+        let result = sync_repo(
+            &repo,
+            &remote,
+            rev,
+            self.follows_tags(),
+            self.history_since(),
+            self.submodules(),
+        )
+        .and_then(|times| match self.upstream() {
+            Some(upstream) => track_upstream(&repo, &remote, upstream).map(|_| times),
+            None => Ok(times),
+        });
+        match result {
+            Ok(_) => fs::remove_file(repo.path().join(CLONING_MARK))?,
+            Err(_) => fs::remove_dir_all(&path)?,
         }
         result
     }
 
-    fn git_pull(&self) -> Result<()> {
-        let (remote, path, rev) = self.clone_info();
+    fn git_pull(&self, ctx: &Context) -> Result<SyncTimes> {
+        let (remote, path, rev) = self.clone_info(ctx);
+        let repo = Repository::open(&path)?;
+        let times = sync_repo(
+            &repo,
+            &remote,
+            rev,
+            self.follows_tags(),
+            self.history_since(),
+            self.submodules(),
+        )?;
+        if let Some(upstream) = self.upstream() {
+            track_upstream(&repo, &remote, upstream)?;
+        }
+        Ok(times)
+    }
+
+    /// The checked out commit
+    fn git_head(&self, ctx: &Context) -> Result<Oid> {
+        let (_, path, _) = self.clone_info(ctx);
+        let repo = Repository::open(&path)?;
+        let commit = repo.head()?.peel_to_commit()?;
+        Ok(commit.id())
+    }
+
+    /// Fetch the remote's default branch and compare the checked out commit
+    /// with it, whatever ref the package follows. A fork is compared with
+    /// the repo it was forked from.
+    fn git_upstream(&self, ctx: &Context) -> Result<Upstream> {
+        let (remote, path, _) = self.clone_info(ctx);
+        let remote = self.upstream().unwrap_or(&remote);
+        let repo = Repository::open(&path)?;
+        let local = repo.head()?.peel_to_commit()?;
+        let tip = repo.find_commit(fetch_upstream(&repo, remote)?)?;
+        let (ahead, behind) = repo.graph_ahead_behind(local.id(), tip.id())?;
+        Ok(Upstream {
+            last_commit: tip.time().seconds(),
+            checked_out: local.time().seconds(),
+            behind,
+            ahead,
+        })
+    }
+
+    /// Merge the default branch of the repo a fork was made from into the
+    /// checked out branch
+    fn git_merge_upstream(&self, ctx: &Context) -> Result<()> {
+        let upstream = self
+            .upstream()
+            .ok_or_else(|| Error::Git("not a fork, there is no upstream".to_string()))?;
+        let (_, path, _) = self.clone_info(ctx);
+        let repo = Repository::open(&path)?;
+        merge_upstream(&repo, upstream)
+    }
+
+    /// Compare the checked out commit with the tracked ref on the remote. A
+    /// pinned checkout with a tracked branch is compared with the branch,
+    /// which is fetched for counting the commits it has moved.
+    fn git_remote_status(&self, ctx: &Context) -> Result<RemoteStatus> {
+        let (remote, path, rev) = self.clone_info(ctx);
         let repo = Repository::open(&path)?;
-        sync_repo(&repo, remote, rev)
+        let local = repo.head()?.peel_to_commit()?.id();
+        if let Some(branch) = self.tracked_branch() {
+            policy::check_remote(&remote)?;
+            let mut remote = repo.remote_anonymous(&remote)?;
+            let from = format!("refs/heads/{}", branch);
+            let tip = fetch_into(&repo, &mut remote, &from, TRACKED_REF)?;
+            let (_, behind) = repo.graph_ahead_behind(local, tip)?;
+            return Ok(match behind {
+                0 => RemoteStatus::UpToDate,
+                _ => RemoteStatus::Tracking {
+                    branch: branch.to_string(),
+                    local,
+                    remote: tip,
+                    behind,
+                },
+            });
+        }
+        match remote_oid(ctx, &repo, &remote, rev.as_deref(), self.follows_tags())? {
+            None => Ok(RemoteStatus::Pinned),
+            Some(oid) if oid == local => Ok(RemoteStatus::UpToDate),
+            Some(oid) => Ok(RemoteStatus::Outdated { local, remote: oid }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_latest_tag() {
+        let tags = ["v1.9.0", "v1.10.0", "2.0-rc1", "nightly", "v1.10"];
+        assert_eq!(latest_tag(tags.iter().copied()), Some("v1.10.0"));
+        assert_eq!(latest_tag(["nightly"].iter().copied()), None);
+    }
+
+    #[test]
+    fn git_partial_clone() {
+        let dir = env::temp_dir().join(format!("pac-partial-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let no_repo = is_partial_clone(&dir);
+        // initialized, but nothing fetched and checked out
+        Repository::init(&dir).unwrap();
+        let unborn = is_partial_clone(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(no_repo && unborn);
+    }
+
+    #[test]
+    fn git_symref_head() {
+        let out = "ref: refs/heads/main\tHEAD\n0123abcd\tHEAD";
+        assert_eq!(symref_head(out).as_deref(), Some("refs/heads/main"));
+        assert_eq!(symref_head("0123abcd\tHEAD"), None);
     }
 }
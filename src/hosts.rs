@@ -0,0 +1,145 @@
+use crate::context::Context;
+use crate::manifest::Manifest;
+use crate::package::Package;
+use crate::utils::on_path;
+
+use std::process::{Command, Stdio};
+
+/// A language neovim runs remote plugins in, through an interpreter and a
+/// host package for it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Host {
+    Python3,
+    Node,
+    Ruby,
+}
+
+impl Host {
+    /// As plugins name it in `rplugin/` and in the `hosts` of pac.yaml
+    pub fn parse(name: &str) -> Option<Host> {
+        match name {
+            "python3" | "python" => Some(Host::Python3),
+            "node" => Some(Host::Node),
+            "ruby" => Some(Host::Ruby),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Host::Python3 => "python3",
+            Host::Node => "node",
+            Host::Ruby => "ruby",
+        }
+    }
+
+    /// What is missing and how to install it, `None` if the host works
+    pub fn check(self) -> Option<String> {
+        let interpreter = self.as_str();
+        if !on_path(interpreter) {
+            return Some(format!("{} is not installed", interpreter));
+        }
+        let (found, package, hint) = match self {
+            Host::Python3 => (
+                // pynvim is imported as `neovim` by old versions
+                succeeds("python3", &["-c", "import pynvim"])
+                    || succeeds("python3", &["-c", "import neovim"]),
+                "pynvim",
+                "python3 -m pip install --user pynvim",
+            ),
+            Host::Node => (
+                on_path("neovim-node-host"),
+                "neovim",
+                "npm install -g neovim",
+            ),
+            Host::Ruby => (on_path("neovim-ruby-host"), "neovim", "gem install neovim"),
+        };
+        if found {
+            None
+        } else {
+            Some(format!(
+                "the {} {} package is missing, install it with `{}`",
+                interpreter, package, hint
+            ))
+        }
+    }
+}
+
+fn succeeds(program: &str, args: &[&str]) -> bool {
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Hosts `pack` needs, from the directories under its `rplugin/` and the
+/// `hosts` its manifest declares
+pub fn needed(ctx: &Context, pack: &Package) -> Vec<Host> {
+    let mut names: Vec<String> = Manifest::load(pack.repo_path(ctx))
+        .ok()
+        .flatten()
+        .map(|m| m.hosts)
+        .unwrap_or_default();
+    if let Ok(entries) = pack.repo_path(ctx).join("rplugin").read_dir() {
+        names.extend(
+            entries
+                .flatten()
+                .filter(|e| e.path().is_dir())
+                .filter_map(|e| e.file_name().into_string().ok()),
+        );
+    }
+    let mut hosts = vec![];
+    for host in names.iter().filter_map(|n| Host::parse(n)) {
+        if !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+    hosts
+}
+
+/// Lines naming the hosts `packs` need which don't work, each host checked
+/// once
+pub fn report(ctx: &Context, packs: &[&Package]) -> Vec<String> {
+    let mut users: Vec<(Host, Vec<&str>)> = vec![];
+    for pack in packs {
+        for host in needed(ctx, pack) {
+            match users.iter_mut().find(|(h, _)| *h == host) {
+                Some((_, ids)) => ids.push(&pack.idname),
+                None => users.push((host, vec![&pack.idname])),
+            }
+        }
+    }
+    users
+        .into_iter()
+        .filter_map(|(host, ids)| {
+            host.check()
+                .map(|problem| format!("{} needs {}: {}", ids.join(", "), host.as_str(), problem))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn hosts_needed() {
+        let root = env::temp_dir().join(format!("pac-hosts-{}", process::id()));
+        let ctx = Context::with_paths(root.join("vim"), root.join("paconfig.yaml"));
+        let pack = Package::new("remote", "https://github.com/me/remote", "default", false);
+        let dir = pack.repo_path(&ctx);
+        fs::create_dir_all(dir.join("rplugin/python3")).unwrap();
+        fs::create_dir_all(dir.join("rplugin/lua")).unwrap();
+        fs::write(dir.join("pac.yaml"), "hosts: [node, python3]").unwrap();
+
+        let hosts = needed(&ctx, &pack);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(hosts, vec![Host::Node, Host::Python3]);
+    }
+}
@@ -0,0 +1,165 @@
+//! Reading back what a generated `_pac.vim` sets up, from the
+//! `g:pac_plugins` lines it starts with
+
+/// How the loader sets up one package
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Loaded {
+    pub opt: bool,
+    pub commands: Vec<String>,
+    pub types: Vec<String>,
+}
+
+/// The packages of a loader by the name they are packadd'ed with, in the
+/// order of the loader
+pub fn read(loader: &str) -> Vec<(String, Loaded)> {
+    loader.lines().filter_map(parse_line).collect()
+}
+
+/// `let g:pac_plugins['name'] = {'idname': ..., 'opt': 1, 'on': [...], ...}`
+fn parse_line(line: &str) -> Option<(String, Loaded)> {
+    let rest = line.strip_prefix("let g:pac_plugins[")?;
+    let (name, rest) = vim_string(rest)?;
+    let mut rest = rest.strip_prefix("] = {")?;
+    let mut loaded = Loaded::default();
+    while let Some((key, r)) = vim_string(rest) {
+        let r = r.strip_prefix(": ")?;
+        let r = match key.as_str() {
+            "opt" => {
+                loaded.opt = r.starts_with('1');
+                r.get(1..)?
+            }
+            "on" | "for" => {
+                let (items, r) = vim_list(r)?;
+                if key == "on" {
+                    loaded.commands = items;
+                } else {
+                    loaded.types = items;
+                }
+                r
+            }
+            _ => vim_string(r)?.1,
+        };
+        rest = r.strip_prefix(", ").unwrap_or(r);
+    }
+    Some((name, loaded))
+}
+
+/// A single quoted string at the start of `s`, with the rest after it
+fn vim_string(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('\'')?.char_indices().peekable();
+    let mut out = String::new();
+    while let Some((i, c)) = chars.next() {
+        if c != '\'' {
+            out.push(c);
+        } else if chars.peek().map(|(_, c)| *c) == Some('\'') {
+            chars.next();
+            out.push('\'');
+        } else {
+            return Some((out, &s[i + 2..]));
+        }
+    }
+    None
+}
+
+fn vim_list(s: &str) -> Option<(Vec<String>, &str)> {
+    let mut rest = s.strip_prefix('[')?;
+    let mut items = vec![];
+    while !rest.starts_with(']') {
+        let (item, r) = vim_string(rest)?;
+        items.push(item);
+        rest = r.strip_prefix(", ").unwrap_or(r);
+    }
+    Some((items, &rest[1..]))
+}
+
+/// One line per package whose setup differs between the `old` and `new`
+/// loader: `+` for added ones, `-` for removed ones and `~` for changed ones
+pub fn delta(old: &str, new: &str) -> Vec<String> {
+    let (old, new) = (read(old), read(new));
+    let place = |opt: bool| if opt { "opt" } else { "start" };
+    let mut lines = vec![];
+    for (name, now) in &new {
+        let was = match old.iter().find(|(n, _)| n == name) {
+            Some((_, was)) => was,
+            None => {
+                let mut line = format!("+ {} ({}", name, place(now.opt));
+                if !now.commands.is_empty() {
+                    line += &format!(", on {}", now.commands.join(" "));
+                }
+                if !now.types.is_empty() {
+                    line += &format!(", for {}", now.types.join(" "));
+                }
+                lines.push(line + ")");
+                continue;
+            }
+        };
+        let mut changes = vec![];
+        if was.opt != now.opt {
+            changes.push(format!("{} -> {}", place(was.opt), place(now.opt)));
+        }
+        for (what, was, now) in [
+            ("commands", &was.commands, &now.commands),
+            ("filetypes", &was.types, &now.types),
+        ] {
+            let mut items: Vec<String> = now
+                .iter()
+                .filter(|i| !was.contains(i))
+                .map(|i| format!("+{}", i))
+                .collect();
+            items.extend(
+                was.iter()
+                    .filter(|i| !now.contains(i))
+                    .map(|i| format!("-{}", i)),
+            );
+            if !items.is_empty() {
+                changes.push(format!("{} {}", what, items.join(" ")));
+            }
+        }
+        if !changes.is_empty() {
+            lines.push(format!("~ {}: {}", name, changes.join(", ")));
+        }
+    }
+    for (name, _) in old.iter().filter(|(n, _)| !new.iter().any(|(m, _)| m == n)) {
+        lines.push(format!("- {}", name));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loader_delta() {
+        let line = |name: &str, opt: u8, on: &str, ft: &str| {
+            format!(
+                "let g:pac_plugins[{}] = {{'idname': 'me/x', 'category': 'default', \
+                 'opt': {}, 'on': [{}], 'for': [{}], 'path': '/p/it''s'}}\n",
+                name, opt, on, ft
+            )
+        };
+        let old = format!(
+            "{}{}{}",
+            line("'fugitive'", 1, "'Git', 'G'", ""),
+            line("'it''s'", 0, "", ""),
+            line("'gone'", 0, "", "")
+        );
+        let new = format!(
+            "{}{}{}",
+            line("'fugitive'", 1, "'Git', 'Gdiff'", "'git'"),
+            line("'it''s'", 1, "", ""),
+            line("'goyo'", 1, "'Goyo'", "")
+        );
+        assert_eq!(read(&old)[1], ("it's".to_string(), Loaded::default()));
+        assert_eq!(
+            delta(&old, &new),
+            vec![
+                "~ fugitive: commands +Gdiff -G, filetypes +git",
+                "~ it's: start -> opt",
+                "+ goyo (opt, on Goyo)",
+                "- gone",
+            ]
+        );
+        assert!(delta(&new, &new).is_empty());
+    }
+}
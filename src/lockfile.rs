@@ -0,0 +1,123 @@
+use crate::context::Context;
+use crate::git::GitRepo;
+use crate::package::Package;
+use crate::source;
+use crate::utils;
+use crate::{Error, Result};
+
+use std::fs;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+const LOCK_HEADER: &str = "# vim: ft=yaml
+#
+# Generated by pac lock, the commits `pac sync --locked` checks out.
+
+";
+
+/// A package pinned by the lockfile
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locked {
+    pub idname: String,
+    pub remote: String,
+    pub commit: String,
+}
+
+/// The packages of the lockfile, `None` if there is none
+pub fn load(ctx: &Context) -> Result<Option<Vec<Locked>>> {
+    let data = match fs::read_to_string(ctx.lock_file()) {
+        Ok(d) => d,
+        Err(_) => return Ok(None),
+    };
+    let docs = YamlLoader::load_from_str(&data)?;
+    let packs = match docs.first().map(|d| &d["packages"]) {
+        Some(Yaml::Array(packs)) => packs,
+        _ => return Err(Error::Format),
+    };
+    packs
+        .iter()
+        .map(|p| {
+            let field = |key: &str| p[key].as_str().map(String::from).ok_or(Error::Format);
+            Ok(Locked {
+                idname: field("idname")?,
+                remote: field("remote")?,
+                commit: field("commit")?,
+            })
+        })
+        .collect::<Result<Vec<Locked>>>()
+        .map(Some)
+}
+
+/// Write the checked out commits of the installed git packages among
+/// `packs`, in their order. Returns how many were locked.
+pub fn save(ctx: &Context, packs: &[Package]) -> Result<usize> {
+    let locked: Vec<Locked> = packs
+        .iter()
+        .filter(|p| p.is_installed(ctx) && source::of(p).versioned())
+        .filter_map(|p| {
+            let commit = p.git_head(ctx).ok()?;
+            Some(Locked {
+                idname: p.idname.clone(),
+                remote: p.remote.clone(),
+                commit: commit.to_string(),
+            })
+        })
+        .collect();
+    let entries = locked
+        .iter()
+        .map(|l| {
+            let mut doc = Hash::new();
+            doc.insert(Yaml::from_str("idname"), Yaml::String(l.idname.clone()));
+            doc.insert(Yaml::from_str("remote"), Yaml::String(l.remote.clone()));
+            doc.insert(Yaml::from_str("commit"), Yaml::String(l.commit.clone()));
+            Yaml::Hash(doc)
+        })
+        .collect();
+    let mut doc = Hash::new();
+    doc.insert(Yaml::from_str("packages"), Yaml::Array(entries));
+    let mut out = String::from(LOCK_HEADER);
+    {
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.dump(&Yaml::Hash(doc))?;
+    }
+    out.push('\n');
+    fs::create_dir_all(&ctx.config_dir)?;
+    utils::write_atomic(ctx.lock_file(), out.as_bytes())?;
+    Ok(locked.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn lockfile_load() {
+        let root = env::temp_dir().join(format!("pac-lockfile-{}", process::id()));
+        let ctx = Context::with_paths(root.join("vim"), root.join("paconfig.yaml"));
+        assert_eq!(load(&ctx).unwrap(), None);
+        assert_eq!(save(&ctx, &[]).unwrap(), 0);
+        assert_eq!(load(&ctx).unwrap(), Some(vec![]));
+
+        fs::write(
+            ctx.lock_file(),
+            "packages:\n  - idname: me/a\n    remote: https://github.com/me/a\n    \
+             commit: 0123abc\n",
+        )
+        .unwrap();
+        let locked = load(&ctx).unwrap();
+        fs::write(ctx.lock_file(), "packages:\n  - idname: me/a\n").unwrap();
+        let broken = load(&ctx);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            locked,
+            Some(vec![Locked {
+                idname: "me/a".to_string(),
+                remote: "https://github.com/me/a".to_string(),
+                commit: "0123abc".to_string(),
+            }])
+        );
+        assert!(broken.is_err());
+    }
+}
@@ -0,0 +1,293 @@
+//! Just enough of Lua to read plugin specs of packer.nvim and lazy.nvim:
+//! literals and table constructors. Anything else, like function bodies
+//! or calls used as values, is skipped over and read as `Value::Other`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    Nil,
+    Table(Table),
+    Other,
+}
+
+/// Positional items and `key = value` fields of a table constructor
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub items: Vec<Value>,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Table {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// A string or a table of strings
+    pub fn as_list(&self) -> Vec<String> {
+        match self {
+            Value::Str(s) => vec![s.clone()],
+            Value::Table(t) => t
+                .items
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Str(String),
+    Num(f64),
+    Name(String),
+    /// Any other punctuation, one character each
+    Sym(char),
+}
+
+/// Split `src` into tokens, dropping comments. Long strings and comments
+/// (`[[...]]`, `--[==[...]==]`) are supported, escapes only partly.
+pub fn tokenize(src: &str) -> Vec<Token> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    // `[[`, `[=[`, ... at `i`, returns the level and length of the opener
+    let long_open = |i: usize| -> Option<(usize, usize)> {
+        if chars.get(i) != Some(&'[') {
+            return None;
+        }
+        let level = chars[i + 1..].iter().take_while(|&&c| c == '=').count();
+        match chars.get(i + 1 + level) {
+            Some('[') => Some((level, level + 2)),
+            _ => None,
+        }
+    };
+    let long_close = |from: usize, level: usize| -> (String, usize) {
+        let close: String = format!("]{}]", "=".repeat(level));
+        let rest: String = chars[from..].iter().collect();
+        match rest.find(&close) {
+            Some(end) => {
+                let body = rest[..end].to_string();
+                let len = body.chars().count() + close.len();
+                (body, from + len)
+            }
+            None => (rest, chars.len()),
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && chars.get(i + 1) == Some(&'-') {
+            i += 2;
+            if let Some((level, len)) = long_open(i) {
+                i = long_close(i + len, level).1;
+            } else {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+        } else if c == '"' || c == '\'' {
+            let mut s = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    i += 1;
+                    s.push(match chars[i] {
+                        'n' => '\n',
+                        't' => '\t',
+                        other => other,
+                    });
+                } else {
+                    s.push(chars[i]);
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Str(s));
+        } else if let Some((level, len)) = long_open(i) {
+            let (body, next) = long_close(i + len, level);
+            // a newline right after the opener is not part of the string
+            tokens.push(Token::Str(
+                body.strip_prefix('\n').unwrap_or(&body).to_string(),
+            ));
+            i = next;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            let num: String = chars[start..i].iter().collect();
+            tokens.push(Token::Num(num.parse().unwrap_or(0.0)));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else {
+            tokens.push(Token::Sym(c));
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// Reads values from a token stream
+pub struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: &'a [Token]) -> Parser<'a> {
+        Parser { tokens, pos: 0 }
+    }
+
+    pub fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    pub fn next(&mut self) -> Option<&'a Token> {
+        let t = self.tokens.get(self.pos);
+        self.pos += 1;
+        t
+    }
+
+    fn is_sym(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Sym(c))
+    }
+
+    /// Table constructor, the parser is at its `{`
+    pub fn table(&mut self) -> Table {
+        let mut table = Table::default();
+        self.next();
+        loop {
+            match self.peek() {
+                None => break,
+                Some(Token::Sym('}')) => {
+                    self.next();
+                    break;
+                }
+                Some(Token::Sym(',')) | Some(Token::Sym(';')) => {
+                    self.next();
+                }
+                Some(Token::Name(key))
+                    if self.tokens.get(self.pos + 1) == Some(&Token::Sym('='))
+                        && self.tokens.get(self.pos + 2) != Some(&Token::Sym('=')) =>
+                {
+                    self.pos += 2;
+                    let value = self.value();
+                    table.fields.push((key.clone(), value));
+                }
+                // `["key"] = value`
+                Some(Token::Sym('[')) => {
+                    self.next();
+                    let key = self.value();
+                    if self.is_sym(']') {
+                        self.next();
+                    }
+                    if self.is_sym('=') {
+                        self.next();
+                    }
+                    let value = self.value();
+                    if let Value::Str(k) = key {
+                        table.fields.push((k, value));
+                    }
+                }
+                Some(_) => {
+                    let value = self.value();
+                    table.items.push(value);
+                }
+            }
+        }
+        table
+    }
+
+    /// A single expression, up to the next `,`, `;` or closing bracket
+    pub fn value(&mut self) -> Value {
+        let value = match self.peek() {
+            Some(Token::Str(s)) => {
+                self.next();
+                Value::Str(s.clone())
+            }
+            Some(Token::Num(n)) => {
+                self.next();
+                Value::Num(*n)
+            }
+            Some(Token::Name(n)) if n == "true" || n == "false" => {
+                self.next();
+                Value::Bool(n == "true")
+            }
+            Some(Token::Name(n)) if n == "nil" => {
+                self.next();
+                Value::Nil
+            }
+            Some(Token::Sym('{')) => Value::Table(self.table()),
+            _ => Value::Other,
+        };
+        // anything following, like `.. "x"` or the rest of a call, turns
+        // the value into something that can't be evaluated here
+        if self.skip_expression() {
+            Value::Other
+        } else {
+            value
+        }
+    }
+
+    /// Skip to the end of the current expression, true if anything was skipped
+    fn skip_expression(&mut self) -> bool {
+        let mut skipped = false;
+        let mut depth = 0;
+        while let Some(t) = self.peek() {
+            match t {
+                Token::Sym(',') | Token::Sym(';') if depth == 0 => break,
+                Token::Sym('}') | Token::Sym(')') | Token::Sym(']') if depth == 0 => break,
+                Token::Sym('{') | Token::Sym('(') | Token::Sym('[') => depth += 1,
+                Token::Sym('}') | Token::Sym(')') | Token::Sym(']') => depth -= 1,
+                Token::Name(n) if n == "function" || n == "if" || n == "do" => depth += 1,
+                Token::Name(n) if n == "end" => depth -= 1,
+                _ => (),
+            }
+            skipped = true;
+            self.next();
+        }
+        skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lua_table() {
+        let src = r#"{ 'a/b', opt = true, cmd = { "X", "Y" }, -- comment
+            config = function() if x then require('b').setup({}) end end,
+            run = [[make]], ["as"] = 'c' .. 'd', 3 }"#;
+        let tokens = tokenize(src);
+        let table = Parser::new(&tokens).table();
+        assert_eq!(
+            table.items,
+            vec![Value::Str("a/b".to_string()), Value::Num(3.0)]
+        );
+        assert_eq!(table.get("opt"), Some(&Value::Bool(true)));
+        assert_eq!(table.get("cmd").unwrap().as_list(), vec!["X", "Y"]);
+        assert_eq!(table.get("config"), Some(&Value::Other));
+        assert_eq!(table.get("run"), Some(&Value::Str("make".to_string())));
+        assert_eq!(table.get("as"), Some(&Value::Other));
+    }
+}
@@ -1,17 +1,42 @@
 use clap::ArgMatches;
 use std::env;
-use std::io;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
 
 #[macro_use]
 mod utils;
 
+mod auth;
 mod cli;
 mod cmd;
+mod context;
+mod dry_run;
 mod echo;
 mod error;
+mod filetypes;
+mod firstrun;
+mod fuzzy;
 mod git;
+mod hosts;
+mod loader;
+mod lockfile;
+mod lua;
+mod manifest;
+mod migrate;
+mod notify;
 mod package;
+mod policy;
+mod prompt;
+mod refcache;
+mod registry;
+mod sets;
+mod settings;
+mod source;
+mod space;
+mod state;
+mod stats;
 mod task;
+mod warnings;
 
 pub use error::{Error, Result};
 
@@ -22,17 +47,107 @@ fn main() {
 
     let app_m = cli::build_cli().get_matches();
 
+    // global args may be given before or after the subcommand
+    let global = |name: &str| {
+        app_m
+            .value_of(name)
+            .or_else(|| app_m.subcommand().1.and_then(|m| m.value_of(name)))
+    };
+    let global_path = |name: &str| global(name).map(PathBuf::from);
+    let ctx = context::Context::new(global_path("root"), global_path("config"));
+
+    let settings = settings::Settings::load(&ctx).unwrap_or_else(|e| die!("Err: {}", e));
+    let flag = |name: &str| {
+        app_m.is_present(name) || app_m.subcommand().1.is_some_and(|m| m.is_present(name))
+    };
+    notify::set_enabled(flag("notify") || (settings.notify && !io::stdout().is_terminal()));
+    if flag("yes") {
+        prompt::set_mode(prompt::Mode::Yes);
+    } else if flag("confirm") {
+        prompt::set_mode(prompt::Mode::Confirm);
+    }
+    if flag("dry-run") {
+        let command = app_m.subcommand_name().unwrap_or_default();
+        if !dry_run::COMMANDS.contains(&command) {
+            die!(
+                "Err: --dry-run is only supported by {}",
+                dry_run::COMMANDS.join(", ")
+            );
+        }
+        dry_run::set(true);
+    }
+    git::set_binary(settings.git_binary.as_deref());
+    refcache::set_ttl(settings.ref_cache);
+    match global("retries").map(str::parse::<u32>) {
+        Some(Ok(n)) => task::set_retries(n),
+        Some(Err(_)) => die!("Err: --retries must be a number"),
+        None => task::set_retries(settings.retries),
+    }
+    echo::set_theme(settings.theme, settings.colors);
+    policy::set(policy::Policy::load(&ctx).unwrap_or_else(|e| die!("Err: {}", e)));
+
+    warnings::set_json(global("warnings") == Some("json"));
+
+    let dumb = env::var("TERM").map(|t| t == "dumb").unwrap_or(false);
+    match global("ui") {
+        Some("simple") => echo::set_ui(echo::Ui::Simple),
+        None if dumb => echo::set_ui(echo::Ui::Simple),
+        _ => (),
+    }
+    // only the commands running tasks have a jsonl format
+    if global("format") == Some("jsonl") {
+        echo::set_ui(echo::Ui::Jsonl);
+    }
+
+    // commands bringing their own paconfig or none at all don't take over
+    // the plugins on disk, nor does a dry run
+    if !dry_run::enabled()
+        && !matches!(
+            app_m.subcommand_name(),
+            Some("completions" | "env" | "import" | "sync-config")
+        )
+    {
+        firstrun::offer(&ctx).unwrap_or_else(|e| die!("Err: {}", e));
+    }
+
     match app_m.subcommand() {
-        ("list", Some(m)) => cmd::list::exec(m),
-        ("install", Some(m)) => cmd::install::exec(m),
-        ("uninstall", Some(m)) => cmd::uninstall::exec(m),
-        ("move", Some(m)) => cmd::move_cmd::exec(m),
-        ("update", Some(m)) => cmd::update::exec(m),
-        ("generate", Some(m)) => cmd::generate::exec(m),
+        ("list", Some(m)) => cmd::list::exec(&ctx, m),
+        ("info", Some(m)) => cmd::info::exec(&ctx, m),
+        ("install", Some(m)) => cmd::install::exec(&ctx, m),
+        ("sync", Some(m)) => cmd::sync::exec(&ctx, m),
+        ("lock", Some(_)) => cmd::lock::exec(&ctx),
+        ("try", Some(m)) => cmd::try_cmd::exec(&ctx, m),
+        ("uninstall", Some(m)) => cmd::uninstall::exec(&ctx, m),
+        ("reinstall", Some(m)) => cmd::reinstall::exec(&ctx, m),
+        ("test", Some(m)) => cmd::test::exec(&ctx, m),
+        ("move", Some(m)) => cmd::move_cmd::exec(&ctx, m),
+        ("category", Some(m)) => cmd::category::exec(&ctx, m),
+        ("sets", Some(_)) => cmd::sets::exec(&ctx),
+        ("enable-set", Some(m)) => cmd::sets::toggle(&ctx, m, true),
+        ("disable-set", Some(m)) => cmd::sets::toggle(&ctx, m, false),
+        ("outdated", Some(m)) => cmd::outdated::exec(&ctx, m),
+        ("stale", Some(m)) => cmd::stale::exec(&ctx, m),
+        ("schedule", Some(m)) => cmd::schedule::exec(&ctx, m),
+        ("stats", Some(m)) => cmd::stats::exec(&ctx, m),
+        ("update", Some(m)) => cmd::update::exec(&ctx, m),
+        ("auth", Some(m)) => cmd::auth::exec(&ctx, m),
+        ("sync-config", Some(m)) => cmd::sync_config::exec(&ctx, m),
+        ("registry", Some(m)) => cmd::registry::exec(&ctx, m),
+        ("exec", Some(m)) => cmd::exec::exec(&ctx, m),
+        ("why", Some(m)) => cmd::why::exec(&ctx, m),
+        ("env", Some(m)) => cmd::env::exec(&ctx, m),
+        ("vendor", Some(m)) => cmd::vendor::exec(&ctx, m),
+        ("licenses", Some(m)) => cmd::licenses::exec(&ctx, m),
+        ("report", Some(m)) => cmd::report::exec(&ctx, m),
+        ("export", Some(m)) => cmd::export::exec(&ctx, m),
+        ("import", Some(m)) => cmd::import::exec(&ctx, m),
+        ("graph", Some(m)) => cmd::graph::exec(&ctx, m),
+        ("generate", Some(m)) => cmd::generate::exec(&ctx, m),
         ("completions", Some(m)) => {
             let shell = m.value_of("SHELL").unwrap();
             cli::build_cli().gen_completions_to("pac", shell.parse().unwrap(), &mut io::stdout());
         }
-        _ => cmd::list::exec(&ArgMatches::default()),
+        _ => cmd::list::exec(&ctx, &ArgMatches::default()),
     }
+    warnings::flush();
 }
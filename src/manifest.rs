@@ -0,0 +1,92 @@
+use crate::utils;
+use crate::{Error, Result};
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Name of the manifest file plugin authors can ship in the root of their repo
+pub const MANIFEST_FILENAME: &str = "pac.yaml";
+
+/// Defaults declared by a plugin in its `pac.yaml`. Every field is optional
+/// and only fills in what the user has not configured in paconfig.
+#[derive(Debug, Default, Clone)]
+pub struct Manifest {
+    /// Packages (idnames) this plugin depends on
+    pub requires: Vec<String>,
+    /// Build commands to run in order after cloning or updating
+    pub build_commands: Vec<String>,
+    /// Commands which should lazy load the plugin
+    pub load_commands: Vec<String>,
+    /// Filetypes which should lazy load the plugin
+    pub for_types: Vec<String>,
+    /// SPDX identifier of the plugin's license, for `pac licenses`
+    pub license: Option<String>,
+    /// Remote plugin hosts the plugin needs, like `python3` or `node`
+    pub hosts: Vec<String>,
+}
+
+impl Manifest {
+    /// Read the manifest from a plugin directory. Returns `None` if the
+    /// plugin does not ship one.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Option<Manifest>> {
+        let path = dir.as_ref().join(MANIFEST_FILENAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let mut data = String::new();
+        File::open(&path)?.read_to_string(&mut data)?;
+        Self::parse(&data).map(Some)
+    }
+
+    pub fn parse(data: &str) -> Result<Manifest> {
+        let docs = YamlLoader::load_from_str(data).map_err(|e| Error::manifest(&e.to_string()))?;
+        match docs.first() {
+            Some(doc) => Self::from_yaml(doc),
+            None => Ok(Manifest::default()),
+        }
+    }
+
+    fn from_yaml(doc: &Yaml) -> Result<Manifest> {
+        let get_list = |key: &str| -> Result<Vec<String>> {
+            utils::yaml_list(&doc[key])
+                .ok_or_else(|| Error::manifest(&format!("invalid `{}` value", key)))
+        };
+
+        Ok(Manifest {
+            requires: get_list("requires")?,
+            // shell commands may contain commas so only a list can give several
+            build_commands: match doc["build"] {
+                Yaml::String(ref s) => vec![s.trim().to_string()],
+                _ => get_list("build")?,
+            },
+            load_commands: get_list("on")?,
+            for_types: get_list("for")?,
+            license: doc["license"].as_str().map(|s| s.to_string()),
+            hosts: get_list("hosts")?,
+        })
+    }
+
+    /// Whether the manifest asks for the plugin to be lazy loaded
+    pub fn is_lazy(&self) -> bool {
+        !self.load_commands.is_empty() || !self.for_types.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_parse() {
+        let m = Manifest::parse("requires: [nvim-lua/plenary.nvim]\nbuild: make\nfor: rust, toml,");
+        let m = m.unwrap();
+        assert_eq!(m.requires, vec!["nvim-lua/plenary.nvim"]);
+        assert_eq!(m.build_commands, vec!["make"]);
+        assert_eq!(m.for_types, vec!["rust", "toml"]);
+        assert!(m.load_commands.is_empty());
+        assert!(m.is_lazy());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{Error, Result};
+
+use yaml_rust::yaml::Hash;
+use yaml_rust::Yaml;
+
+/// Version of the paconfig layout written by this version of pac
+pub const CONFIG_VERSION: i64 = 2;
+
+/// Migrations upgrading a paconfig document by one version. The migration at
+/// index `i` upgrades version `i + 1` to `i + 2`.
+const MIGRATIONS: &[fn(Yaml) -> Result<Yaml>] = &[v1_to_v2];
+
+/// Version 1 is the original layout: a bare list of packages without any
+/// version field. Version 2 wraps it in a mapping.
+fn v1_to_v2(doc: Yaml) -> Result<Yaml> {
+    let mut hash = Hash::new();
+    hash.insert(Yaml::from_str("version"), Yaml::Integer(2));
+    hash.insert(Yaml::from_str("packages"), doc);
+    Ok(Yaml::Hash(hash))
+}
+
+/// Version of a paconfig document as it was loaded from disk
+pub fn version(doc: &Yaml) -> Result<i64> {
+    match *doc {
+        Yaml::Array(_) | Yaml::Null => Ok(1),
+        Yaml::Hash(_) => doc["version"].as_i64().ok_or(Error::Format),
+        _ => Err(Error::Format),
+    }
+}
+
+/// Upgrade a paconfig document to `CONFIG_VERSION`. Nothing is written to
+/// disk here, the new layout is only persisted on the next save.
+pub fn migrate(mut doc: Yaml) -> Result<Yaml> {
+    let from = version(&doc)?;
+    if from > CONFIG_VERSION {
+        return Err(Error::PaconfigFile(format!(
+            "paconfig version {} is newer than supported version {}, upgrade pac",
+            from, CONFIG_VERSION
+        )));
+    }
+    if from < 1 {
+        return Err(Error::Format);
+    }
+    if let Yaml::Null = doc {
+        doc = Yaml::Array(vec![]);
+    }
+    for m in &MIGRATIONS[from as usize - 1..] {
+        doc = m(doc)?;
+    }
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn migrate_bare_list() {
+        let docs =
+            YamlLoader::load_from_str("- remote: https://github.com/tpope/vim-surround").unwrap();
+        let doc = migrate(docs[0].clone()).unwrap();
+        assert_eq!(version(&doc).unwrap(), CONFIG_VERSION);
+        assert_eq!(
+            doc["packages"][0]["remote"].as_str(),
+            Some("https://github.com/tpope/vim-surround")
+        );
+    }
+
+    #[test]
+    fn migrate_newer_version() {
+        let docs = YamlLoader::load_from_str("version: 100\npackages: []").unwrap();
+        assert!(migrate(docs[0].clone()).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Send a notification at the end of install, update and outdated runs
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// "1 package" or "n packages"
+pub fn packages(n: usize) -> String {
+    match n {
+        1 => "1 package".to_string(),
+        n => format!("{} packages", n),
+    }
+}
+
+/// Summary of a run, `done` being what succeeded, like "Updated 3 packages".
+/// Nothing is sent when nothing happened.
+pub fn report(done: Option<String>, failed: &[String]) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut lines: Vec<String> = done.into_iter().collect();
+    match failed.len() {
+        0 => (),
+        1 => lines.push(format!("{} failed", failed[0])),
+        n => lines.push(format!("{} packages failed: {}", n, failed.join(", "))),
+    }
+    if lines.is_empty() {
+        return;
+    }
+    if let Err(e) = send("pac", &lines.join("\n")) {
+        log::info!("fail to send notification: {}", e);
+    }
+}
+
+/// Errors are only logged, a missing notification daemon must not fail
+/// the run
+fn send(title: &str, body: &str) -> std::io::Result<()> {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let script = format!(
+            "display notification {} with title {}",
+            quote(body),
+            quote(title)
+        );
+        let mut cmd = Command::new("osascript");
+        cmd.args(["-e", &script]);
+        cmd
+    } else {
+        let mut cmd = Command::new("notify-send");
+        cmd.args(["--app-name", "pac", title, body]);
+        cmd
+    };
+    cmd.stdout(Stdio::null()).stderr(Stdio::null()).status()?;
+    Ok(())
+}
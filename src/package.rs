@@ -1,14 +1,24 @@
-use crate::git::GitRepo;
+use crate::context::Context;
+use crate::git::{self, GitRepo};
+use crate::loader;
+use crate::manifest::Manifest;
+use crate::migrate::{self, CONFIG_VERSION};
+use crate::policy;
+use crate::sets::Sets;
+use crate::settings::{Colors, Settings};
+use crate::state;
+use crate::utils;
+use crate::warnings::{self, Kind};
 use crate::{Error, Result};
 
-use std::env;
 use std::fmt;
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::process;
 
-use lazy_static::lazy_static;
+use crossterm::style::{style, Color, Stylize};
+use git2::Oid;
 use yaml_rust::yaml::Hash;
 use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
 
@@ -17,9 +27,76 @@ const PAC_PLUGIN_HEADER: &str = "\" Generated by pac. DO NOT EDIT!
 
 scriptencoding utf-8
 
+if v:version < 703 && !has('nvim')
+    echoerr 'pac: the generated loader needs Vim 7.3 or newer'
+    finish
+endif
+
+\" Vim before 7.4.1384 has no packages, the loader adds the directories to
+\" 'runtimepath' and sources their plugins itself
+let s:packages = has('packages')
+let s:added = {}
+
+\" the opt packages a package requires are added before it
+function! s:packadd(pack)
+    if has_key(s:added, a:pack)
+        return
+    endif
+    let s:added[a:pack] = 1
+    for dep in g:pac_plugins[a:pack].requires
+        if g:pac_plugins[dep].opt && s:enabled(dep, g:pac_plugins[dep].idname)
+            call s:packadd(dep)
+        endif
+    endfor
+    if s:packages
+        execute 'packadd' a:pack
+        return
+    endif
+    let path = g:pac_plugins[a:pack].path
+    execute 'set runtimepath^=' . fnameescape(path)
+    execute 'set runtimepath+=' . fnameescape(path . '/after')
+    for dir in [path, path . '/after']
+        for file in split(glob(dir . '/plugin/**/*.vim'), \"\\n\")
+            execute 'source' fnameescape(file)
+        endfor
+    endfor
+endfunction
+
 function! s:do_cmd(cmd, bang, start, end, args)
     exec printf('%s%s%s %s', (a:start == a:end ? '' : (a:start.','.a:end)), a:cmd, a:bang, a:args)
 endfunction
+
+\" plugins listed by name or idname in g:pac_disabled are not set up to load
+function! s:enabled(name, idname)
+    let disabled = get(g:, 'pac_disabled', [])
+    return index(disabled, a:name) < 0 && index(disabled, a:idname) < 0
+endfunction
+
+function! s:lazy_cmd(cmds, pack, cmd, bang, start, end, args)
+    for c in a:cmds
+        execute 'silent! delcommand' c
+    endfor
+    call s:packadd(a:pack)
+    call s:do_cmd(a:cmd, a:bang, a:start, a:end, a:args)
+endfunction
+";
+
+const PAC_PLUGIN_FOOTER: &str =
+    "\" start packages are only loaded by Vim itself if it knows packages, the
+\" opt packages they require are added here either way
+for s:name in filter(copy(s:order), '!g:pac_plugins[v:val].opt')
+    if s:enabled(s:name, g:pac_plugins[s:name].idname)
+        if s:packages
+            for s:dep in g:pac_plugins[s:name].requires
+                if g:pac_plugins[s:dep].opt && s:enabled(s:dep, g:pac_plugins[s:dep].idname)
+                    call s:packadd(s:dep)
+                endif
+            endfor
+        else
+            call s:packadd(s:name)
+        endif
+    endif
+endfor
 ";
 
 const PAC_CONFIG_HEADER: &[u8] = b"# vim: ft=yaml
@@ -28,17 +105,31 @@ const PAC_CONFIG_HEADER: &[u8] = b"# vim: ft=yaml
 
 ";
 
-lazy_static! {
-    static ref VIM_BASE_DIR: PathBuf = env::var("VIM_CONFIG_PATH")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| {
-            let home = dirs::home_dir().expect("No home directory found");
-            home.join(".vim")
-        });
-    static ref VIM_PACKAGE_DIR: PathBuf = (*VIM_BASE_DIR).join("pack");
-    static ref VIM_PLUGIN_DIR: PathBuf = (*VIM_BASE_DIR).join("plugin");
-    static ref PAC_CONFIG_DIR: PathBuf = (*VIM_BASE_DIR).join(".pac");
-    static ref PAC_CONFIG_FILE: PathBuf = (*PAC_CONFIG_DIR).join("paconfig.yaml");
+/// What a package without a `rev` follows
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Channel {
+    /// The newest release tag, tags with a pre-release suffix are skipped
+    Stable,
+    /// The default branch
+    #[default]
+    Nightly,
+}
+
+impl Channel {
+    pub fn parse(s: &str) -> Option<Channel> {
+        match s {
+            "stable" => Some(Channel::Stable),
+            "nightly" => Some(Channel::Nightly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Channel::Stable => "stable",
+            Channel::Nightly => "nightly",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -54,16 +145,56 @@ pub struct Package {
     pub remote: String,
     /// The branch, tag, or commit to checkout described as a rev
     pub revision: Option<String>,
+    /// Followed when no `revision` is given
+    pub channel: Option<Channel>,
+    /// The branch to fetch and track. It is checked out when no `revision`
+    /// is given, otherwise `pac outdated` reports how far it has moved past
+    /// the pinned revision.
+    pub branch: Option<String>,
+    /// Owner of a fork to clone instead of `remote`, which is still fetched
+    /// as the `upstream` remote
+    pub fork: Option<String>,
+    /// Only fetch the history after this date, like `1 year`, for old
+    /// plugins with long histories
+    pub history_since: Option<String>,
+    /// Whether the submodules are checked out along with the repo, and
+    /// theirs in turn. True by default.
+    pub submodules: bool,
     /// Install package under pack/<category>/. Default value is "default"
     pub category: String,
     /// Whether to put this package under pack/*/opt. Default value is false.
     pub opt: bool,
-    /// Load this package on this command
-    pub load_command: Option<String>,
+    /// Load this package on any of these commands
+    pub load_commands: Vec<String>,
     /// Load this package for these types
     pub for_types: Vec<String>,
-    /// Build command for this package
-    pub build_command: Option<String>,
+    /// Vim expression, like `has('nvim')`, without which the loader leaves
+    /// the package alone
+    pub condition: Option<String>,
+    /// Build commands for this package, run in order
+    pub build_commands: Vec<String>,
+    /// Commands run once, after the build commands of the first install
+    pub post_install: Vec<String>,
+    /// Idnames of packages this package depends on
+    pub requires: Vec<String>,
+    /// One line summary shown by `pac list`
+    pub description: Option<String>,
+    /// Packages with higher priorities are synced and built first, 0 by
+    /// default
+    pub priority: i64,
+    /// Where the package goes in paconfig, `pac list` and the loader, lower
+    /// first. Packages with the same order, 0 by default, stay in the order
+    /// paconfig lists them.
+    pub order: i64,
+    /// Listed by a disabled set, the package then stays under opt/ and
+    /// nothing loads it. Not saved, it follows `disabled_sets`.
+    pub disabled: bool,
+    /// `enabled: false` in paconfig keeps the entry but nothing installs,
+    /// updates or loads the package
+    pub enabled: bool,
+    /// What `merge_manifest` filled in from the plugin's `pac.yaml`, which
+    /// is read again on every load and so not saved to paconfig
+    pub from_manifest: Manifest,
 }
 
 impl Package {
@@ -73,22 +204,59 @@ impl Package {
             idname: Self::idname_from_remote(remote),
             remote: remote.to_string(),
             revision: None,
+            channel: None,
+            branch: None,
+            fork: None,
+            history_since: None,
+            submodules: true,
             category: category.to_string(),
             opt,
-            load_command: None,
+            load_commands: Vec::new(),
             for_types: Vec::new(),
-            build_command: None,
+            condition: None,
+            build_commands: Vec::new(),
+            post_install: Vec::new(),
+            requires: Vec::new(),
+            description: None,
+            priority: 0,
+            order: 0,
+            disabled: false,
+            enabled: true,
+            from_manifest: Manifest::default(),
         }
     }
 
-    /// Get username/repo from a git remote
+    /// Get username/repo from a git remote on any host, like
+    /// `https://gitlab.com/group/repo.git`, `git@codeberg.org:user/repo` or
+    /// `https://git.sr.ht/~user/repo`
+    /// An archive url is named after the repo its release or archive path is
+    /// under, `https://github.com/user/repo/releases/download/v1/repo.zip`
+    /// is `user/repo` too.
     pub fn idname_from_remote(remote: &str) -> String {
-        let parts = remote.split('/').collect::<Vec<_>>();
-        parts[parts.len() - 2..].join("/")
+        let remote = remote.trim_end_matches('/');
+        let stem = archive_stem(remote);
+        let path = stem.unwrap_or_else(|| remote.strip_suffix(".git").unwrap_or(remote));
+        // scp like remotes put a colon between the host and the path
+        let mut parts = path
+            .split(['/', ':'])
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>();
+        if stem.is_some() {
+            if let Some(i) = parts.iter().position(|p| ARCHIVE_MARKERS.contains(p)) {
+                parts.truncate(i);
+            }
+        }
+        parts[parts.len().saturating_sub(2)..].join("/")
+    }
+
+    /// The name a package from `remote` gets by default, its repo name
+    pub fn name_from_remote(remote: &str) -> String {
+        let idname = Self::idname_from_remote(remote);
+        idname.rsplit('/').next().unwrap_or(&idname).to_string()
     }
 
-    pub fn is_installed(&self) -> bool {
-        self.path().is_dir()
+    pub fn is_installed(&self, ctx: &Context) -> bool {
+        self.path(ctx).is_dir()
     }
 
     pub fn set_category<T: Into<String>>(&mut self, cat: T) {
@@ -105,39 +273,86 @@ impl Package {
         self.for_types = types
     }
 
-    /// Parse a Package from a single list item in `PAC_CONFIG_FILE`
+    /// Parse a Package from a single list item in the paconfig file
     pub fn from_yaml(doc: &Yaml) -> Result<Package> {
         let remote = doc["remote"]
             .as_str()
             .map(|s| s.to_string())
             .ok_or(Error::Format)?;
+        validate_remote(&remote)?;
 
         let name = doc["name"]
             .as_str()
-            .or_else(|| remote.rsplit('/').next())
             .map(|s| s.to_string())
-            .ok_or(Error::Format)?;
+            .unwrap_or_else(|| Self::name_from_remote(&remote));
+        validate_name(&name)?;
 
         let category = doc["category"]
             .as_str()
             .map_or("default".to_string(), |s| s.to_string());
+        validate_category(&category)?;
 
         let opt = doc["opt"].as_bool().unwrap_or(false);
+        let enabled = match doc["enabled"] {
+            Yaml::Boolean(e) => e,
+            Yaml::BadValue => true,
+            _ => return Err(Error::Format),
+        };
+        let submodules = match doc["submodules"] {
+            Yaml::Boolean(s) => s,
+            Yaml::BadValue => true,
+            _ => return Err(Error::Format),
+        };
 
         let get_val = |key: &str| doc[key].as_str().map(|s| s.to_string());
         let revision = get_val("rev");
-        let cmd = get_val("on");
-        let build = get_val("build");
-
-        let types = match doc["for"].as_vec() {
-            Some(f) => {
-                let mut types = Vec::with_capacity(f.len());
-                for e in f {
-                    types.push(e.as_str().map(|s| s.to_string()).ok_or(Error::Format)?);
-                }
-                types
+        let branch = get_val("branch");
+        for key in ["tag", "commit"] {
+            if !doc[key].is_badvalue() {
+                let message = format!("`{}` is no longer read, use `rev` instead", key);
+                warnings::push(
+                    Kind::Deprecated,
+                    Some(&Self::idname_from_remote(&remote)),
+                    message,
+                );
             }
-            None => vec![],
+        }
+        let description = get_val("desc");
+        let fork = get_val("fork");
+        let history_since = get_val("history_since");
+        let channel = match get_val("channel") {
+            Some(c) => Some(Channel::parse(&c).ok_or(Error::Format)?),
+            None => None,
+        };
+
+        let get_list = |key: &str| utils::yaml_list(&doc[key]).ok_or(Error::Format);
+        // shell commands may contain commas so only a list can give several
+        let get_commands = |key: &str| match doc[key] {
+            Yaml::String(ref s) if !s.trim().is_empty() => Ok(vec![s.trim().to_string()]),
+            _ => get_list(key),
+        };
+        let cmds = get_list("on")?;
+        let types = get_list("for")?;
+        let condition = match doc["if"] {
+            Yaml::String(ref c) if !c.trim().is_empty() => Some(c.trim().to_string()),
+            Yaml::BadValue => None,
+            _ => return Err(Error::Format),
+        };
+        // commands, filetypes and conditions to load on need an opt package
+        // to packadd
+        let opt = opt || !cmds.is_empty() || !types.is_empty() || condition.is_some();
+        let build = get_commands("build")?;
+        let post_install = get_commands("post_install")?;
+        let requires = get_list("requires")?;
+        let priority = match doc["priority"] {
+            Yaml::Integer(p) => p,
+            Yaml::BadValue => 0,
+            _ => return Err(Error::Format),
+        };
+        let order = match doc["order"] {
+            Yaml::Integer(o) => o,
+            Yaml::BadValue => 0,
+            _ => return Err(Error::Format),
         };
 
         Ok(Package {
@@ -145,15 +360,29 @@ impl Package {
             idname: Self::idname_from_remote(&remote),
             remote,
             revision,
+            channel,
+            branch,
+            fork,
+            history_since,
+            submodules,
             category,
             opt,
-            load_command: cmd,
+            load_commands: cmds,
             for_types: types,
-            build_command: build,
+            condition,
+            build_commands: build,
+            post_install,
+            requires,
+            description,
+            priority,
+            order,
+            disabled: false,
+            enabled,
+            from_manifest: Manifest::default(),
         })
     }
 
-    /// Convert Package to a list item to be added to `PAC_CONFIG_FILE`
+    /// Convert Package to a list item to be added to the paconfig file
     pub fn into_yaml(self) -> Yaml {
         let mut doc = Hash::new();
 
@@ -165,13 +394,19 @@ impl Package {
 
         yaml_insert!(remote);
 
-        if !self.remote.ends_with(&self.name) {
+        if self.name != Self::name_from_remote(&self.remote) {
             yaml_insert!(name);
         }
         if self.category != "default" {
             yaml_insert!(category);
         }
-        if self.opt {
+        // a lazy or conditional package is opt anyway, and goes back to
+        // start/ once it has nothing left to be loaded on. Only what paconfig
+        // itself loads it on counts, a lazy manifest leaves it under opt/.
+        let own = |list: &Vec<String>, merged: &Vec<String>| !list.is_empty() && list != merged;
+        let lazy = own(&self.load_commands, &self.from_manifest.load_commands)
+            || own(&self.for_types, &self.from_manifest.for_types);
+        if self.opt && !lazy && self.condition.is_none() {
             doc.insert(Yaml::from_str("opt"), Yaml::Boolean(self.opt));
         }
 
@@ -184,44 +419,291 @@ impl Package {
         }
 
         yaml_opt_insert!(rev, revision);
-        yaml_opt_insert!(on, load_command);
-        yaml_opt_insert!(build, build_command);
+        if let Some(c) = self.channel {
+            doc.insert(Yaml::from_str("channel"), Yaml::from_str(c.as_str()));
+        }
+        if let Some(ref b) = self.branch {
+            doc.insert(Yaml::from_str("branch"), Yaml::String(b.clone()));
+        }
+        if let Some(ref f) = self.fork {
+            doc.insert(Yaml::from_str("fork"), Yaml::String(f.clone()));
+        }
+        if let Some(ref h) = self.history_since {
+            doc.insert(Yaml::from_str("history_since"), Yaml::String(h.clone()));
+        }
+        if !self.submodules {
+            doc.insert(Yaml::from_str("submodules"), Yaml::Boolean(false));
+        }
+        // any text is fine, Yaml::from_str would turn `1.0` into a number
+        if let Some(ref d) = self.description {
+            doc.insert(Yaml::from_str("desc"), Yaml::String(d.clone()));
+        }
 
-        if !self.for_types.is_empty() {
-            let types = self
-                .for_types
-                .iter()
-                .map(|e| Yaml::from_str(e))
-                .collect::<Vec<Yaml>>();
-            doc.insert(Yaml::from_str("for"), Yaml::Array(types));
+        // the defaults merged from the manifest are left out
+        macro_rules! yaml_list_insert {
+            ($key:expr,$member:ident) => {
+                yaml_list_insert!($key, $member, []);
+            };
+            ($key:expr,$member:ident,$merged:expr) => {
+                let list = self
+                    .$member
+                    .iter()
+                    .filter(|e| !$merged.contains(e))
+                    .map(|e| Yaml::from_str(e))
+                    .collect::<Vec<Yaml>>();
+                if !list.is_empty() {
+                    doc.insert(Yaml::from_str($key), Yaml::Array(list));
+                }
+            };
+        }
+
+        let merged = &self.from_manifest;
+        yaml_list_insert!("on", load_commands, merged.load_commands);
+        yaml_list_insert!("for", for_types, merged.for_types);
+        if let Some(ref c) = self.condition {
+            doc.insert(Yaml::from_str("if"), Yaml::String(c.clone()));
+        }
+        yaml_list_insert!("build", build_commands, merged.build_commands);
+        yaml_list_insert!("post_install", post_install);
+        yaml_list_insert!("requires", requires, merged.requires);
+        if self.priority != 0 {
+            doc.insert(Yaml::from_str("priority"), Yaml::Integer(self.priority));
+        }
+        if self.order != 0 {
+            doc.insert(Yaml::from_str("order"), Yaml::Integer(self.order));
+        }
+        if !self.enabled {
+            doc.insert(Yaml::from_str("enabled"), Yaml::Boolean(false));
         }
         Yaml::Hash(doc)
     }
 
+    /// Whether the package is loaded on demand by a command or filetype
+    pub fn is_lazy(&self) -> bool {
+        !self.load_commands.is_empty() || !self.for_types.is_empty()
+    }
+
+    /// Fill in settings the user has not configured with the defaults declared
+    /// by the plugin's `pac.yaml`, noting them in `from_manifest`. Does not
+    /// touch `opt` since that decides where the plugin lives on disk.
+    pub fn merge_manifest(&mut self, manifest: &Manifest) {
+        let merged = &mut self.from_manifest;
+        if self.build_commands.is_empty() && !manifest.build_commands.is_empty() {
+            self.build_commands = manifest.build_commands.clone();
+            merged.build_commands = manifest.build_commands.clone();
+        }
+        if self.load_commands.is_empty() && !manifest.load_commands.is_empty() {
+            self.load_commands = manifest.load_commands.clone();
+            merged.load_commands = manifest.load_commands.clone();
+        }
+        if self.for_types.is_empty() && !manifest.for_types.is_empty() {
+            self.for_types = manifest.for_types.clone();
+            merged.for_types = manifest.for_types.clone();
+        }
+        for req in &manifest.requires {
+            if !self.requires.contains(req) {
+                self.requires.push(req.clone());
+                merged.requires.push(req.clone());
+            }
+        }
+    }
+
+    /// Read the plugin's `pac.yaml` (if any) from the installed directory
+    pub fn manifest(&self, ctx: &Context) -> Result<Option<Manifest>> {
+        Manifest::load(self.path(ctx))
+    }
+
     /// Returns absolute path to directory where plugin can be installed
-    pub fn path(&self) -> PathBuf {
-        if self.opt {
-            VIM_PACKAGE_DIR
+    pub fn path(&self, ctx: &Context) -> PathBuf {
+        if self.opt || self.disabled {
+            ctx.pack_dir
                 .join(&self.category)
                 .join("opt")
                 .join(&self.name)
         } else {
-            VIM_PACKAGE_DIR
+            ctx.pack_dir
                 .join(&self.category)
                 .join("start")
                 .join(&self.name)
         }
     }
 
-    /// Run the build command using `sh -c ...`
+    /// Directory in the shared store the package is cloned to,
+    /// `<store>/<host>/<owner>/<repo>`
+    pub fn store_path(&self, ctx: &Context) -> PathBuf {
+        ctx.store_dir.join(store_relpath(&self.clone_url()))
+    }
+
+    /// Where the package is cloned from, the fork if there is one
+    pub fn clone_url(&self) -> String {
+        match self.fork {
+            Some(ref user) => fork_remote(&self.remote, user),
+            None => self.remote.clone(),
+        }
+    }
+
+    /// The directory on this machine the package comes from, for a remote
+    /// which is an absolute path or a `file://` url
+    pub fn local_dir(&self) -> Option<PathBuf> {
+        let path = self.remote.strip_prefix("file://").unwrap_or(&self.remote);
+        Some(PathBuf::from(path)).filter(|p| p.is_absolute())
+    }
+
+    /// Whether the package is copied from a local directory, which it is
+    /// when that directory is no git repo to clone
+    pub fn is_copied(&self) -> bool {
+        self.local_dir()
+            .is_some_and(|d| d.is_dir() && !d.join(".git").exists())
+    }
+
+    /// Copy the files of a local directory into the store, replacing an
+    /// earlier copy only once the new one is complete
+    pub fn copy_local(&self, ctx: &Context) -> Result<()> {
+        let src = self
+            .local_dir()
+            .ok_or_else(|| Error::copy_dir(&format!("{} is no local path", self.remote)))?;
+        let dst = self.store_path(ctx);
+        let mut partial = dst.clone().into_os_string();
+        partial.push(".pac-copy");
+        let partial = PathBuf::from(partial);
+        if partial.exists() {
+            fs::remove_dir_all(&partial)?;
+        }
+        utils::copy_directory(&src, &partial)?;
+        if dst.is_dir() {
+            fs::remove_dir_all(&dst)?;
+        }
+        fs::rename(&partial, &dst)?;
+        Ok(())
+    }
+
+    /// Whether the package is downloaded as a `.tar.gz` or `.zip` archive
+    /// rather than cloned
+    pub fn is_archive(&self) -> bool {
+        is_archive(&self.remote)
+    }
+
+    /// Download the archive and unpack it into the store, replacing an
+    /// earlier download only when the archive changed. A single top level
+    /// directory, like `repo-1.0/` in release tarballs, is unpacked as the
+    /// package itself unless it is a runtime directory like `plugin/`.
+    /// Returns whether the store changed.
+    pub fn fetch_archive(&self, ctx: &Context) -> Result<bool> {
+        let dst = self.store_path(ctx);
+        let data = utils::download(&self.remote)?;
+        // the remote and a digest of the archive, to tell if it changed
+        let stamp = format!("{}\n{:016x}\n", self.remote, utils::fnv1a(&data));
+        let stamp_file = dst.join(ARCHIVE_STAMP);
+        if fs::read_to_string(&stamp_file).is_ok_and(|s| s == stamp) {
+            return Ok(false);
+        }
+
+        let with_suffix = |suffix: &str| {
+            let mut path = dst.clone().into_os_string();
+            path.push(suffix);
+            PathBuf::from(path)
+        };
+        let (archive, partial) = (with_suffix(".pac-archive"), with_suffix(".pac-copy"));
+        if partial.exists() {
+            fs::remove_dir_all(&partial)?;
+        }
+        fs::create_dir_all(&partial)?;
+        fs::write(&archive, &data)?;
+        let zip = archive_stem(&self.remote)
+            .is_some_and(|stem| self.remote[stem.len()..].starts_with(".zip"));
+        let unpacked = unpack(&archive, &partial, zip);
+        fs::remove_file(&archive)?;
+        if let Err(e) = unpacked {
+            fs::remove_dir_all(&partial)?;
+            return Err(e);
+        }
+
+        let entries = fs::read_dir(&partial)?.flatten().collect::<Vec<_>>();
+        let root = match entries.as_slice() {
+            [only]
+                if only.path().is_dir() && !RUNTIME_DIRS.iter().any(|d| only.file_name() == *d) =>
+            {
+                only.path()
+            }
+            _ => partial.clone(),
+        };
+        fs::write(root.join(ARCHIVE_STAMP), stamp)?;
+        if dst.is_dir() {
+            fs::remove_dir_all(&dst)?;
+        }
+        fs::rename(&root, &dst)?;
+        if partial.exists() {
+            fs::remove_dir_all(&partial)?;
+        }
+        Ok(true)
+    }
+
+    /// Whether the entry under pack/ is a link into the store
+    pub fn is_linked(&self, ctx: &Context) -> bool {
+        fs::symlink_metadata(self.path(ctx))
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false)
+    }
+
+    /// Directory with the git checkout. Packages installed before the store
+    /// existed are checked out directly under pack/.
+    pub fn repo_path(&self, ctx: &Context) -> PathBuf {
+        let path = self.path(ctx);
+        if path.is_dir() && !self.is_linked(ctx) {
+            path
+        } else {
+            self.store_path(ctx)
+        }
+    }
+
+    /// Make the entry under pack/ a link to the clone in the store, replacing
+    /// a previous (possibly dangling) link.
+    pub fn link(&self, ctx: &Context) -> Result<()> {
+        let path = self.path(ctx);
+        if self.is_linked(ctx) {
+            utils::remove_link(&path)?;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        utils::link_dir(self.store_path(ctx), &path)
+    }
+
+    /// Remove the entry under pack/, leaving the store untouched
+    pub fn unlink(&self, ctx: &Context) -> Result<()> {
+        let path = self.path(ctx);
+        if self.is_linked(ctx) {
+            utils::remove_link(&path)?;
+        } else if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Run the build commands in order using `sh -c ...`
     ///
     /// # Errors
     ///
-    /// If the build process returns a non zero exit status, an `Error::Build`
-    /// variant will be returned along with stderr.
-    pub fn try_build(&self) -> Result<()> {
-        if let Some(ref c) = self.build_command {
-            let path = self.path();
+    /// If a build process returns a non zero exit status, an `Error::Build`
+    /// variant will be returned along with stderr and the remaining commands
+    /// are not run. An `Error::Policy` is returned without running anything
+    /// if the policy does not allow building.
+    pub fn try_build(&self, ctx: &Context) -> Result<()> {
+        self.run_commands(ctx, &self.build_commands)
+    }
+
+    /// Run the `post_install` commands like `try_build` runs the build
+    /// commands. Only called once the package is first installed and built.
+    pub fn try_post_install(&self, ctx: &Context) -> Result<()> {
+        self.run_commands(ctx, &self.post_install)
+    }
+
+    fn run_commands(&self, ctx: &Context, commands: &[String]) -> Result<()> {
+        if !commands.is_empty() {
+            policy::check_build()?;
+        }
+        let path = self.path(ctx);
+        for c in commands {
             let p = process::Command::new("sh")
                 .arg("-c")
                 .arg(c)
@@ -233,25 +715,103 @@ impl Package {
             if !output.status.success() {
                 let err = String::from_utf8(output.stderr)
                     .unwrap_or_else(|_| String::from("No error output!"));
-                return Err(Error::Build(err));
+                return Err(Error::Build(format!("`{}`: {}", c, err)));
             }
         }
         Ok(())
     }
+
+    /// A build command guessed from the files at the top of the repo, for
+    /// plugins which don't list one: an `install.sh`, a Makefile, a
+    /// `Cargo.toml` or a `package.json` with a `build` script, in that order
+    pub fn suggested_build(&self, ctx: &Context) -> Option<String> {
+        let dir = self.repo_path(ctx);
+        let has = |name: &str| dir.join(name).is_file();
+        let npm_build = fs::read_to_string(dir.join("package.json"))
+            .ok()
+            .is_some_and(|json| has_build_script(&json));
+        if has("install.sh") {
+            Some("sh install.sh".to_string())
+        } else if ["Makefile", "makefile", "GNUmakefile"]
+            .iter()
+            .any(|m| has(m))
+        {
+            Some("make".to_string())
+        } else if has("Cargo.toml") {
+            Some("cargo build --release".to_string())
+        } else if npm_build {
+            let install = if has("package-lock.json") {
+                "npm ci"
+            } else {
+                "npm install"
+            };
+            Some(format!("{} && npm run build", install))
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether the `scripts` of a package.json have a `build` entry. Only the
+/// keys of the `scripts` object are looked at, there is no json parser.
+fn has_build_script(json: &str) -> bool {
+    let scripts = match json.find("\"scripts\"") {
+        Some(i) => &json[i..],
+        None => return false,
+    };
+    let body = match (scripts.find('{'), scripts.find('}')) {
+        (Some(start), Some(end)) if start < end => &scripts[start..end],
+        _ => return false,
+    };
+    body.match_indices("\"build\"")
+        .any(|(i, m)| body[i + m.len()..].trim_start().starts_with(':'))
 }
 
 impl GitRepo for Package {
-    fn clone_info(&self) -> (&str, PathBuf, Option<String>) {
-        (&self.remote, self.path(), self.revision.clone())
+    fn clone_info(&self, ctx: &Context) -> (String, PathBuf, Option<String>) {
+        let rev = self.revision.clone().or_else(|| self.branch.clone());
+        (self.clone_url(), self.repo_path(ctx), rev)
+    }
+
+    fn upstream(&self) -> Option<&str> {
+        self.fork.as_ref().map(|_| self.remote.as_str())
+    }
+
+    fn history_since(&self) -> Option<&str> {
+        self.history_since.as_deref()
+    }
+
+    fn follows_tags(&self) -> bool {
+        self.revision.is_none() && self.branch.is_none() && self.channel == Some(Channel::Stable)
+    }
+
+    fn tracked_branch(&self) -> Option<&str> {
+        self.branch.as_deref().filter(|_| self.revision.is_some())
+    }
+
+    fn submodules(&self) -> bool {
+        self.submodules
     }
 }
 
-impl fmt::Display for Package {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let name = if self.opt { "opt" } else { "start" };
-        let on = match self.load_command {
-            Some(ref c) => format!(" [Load on `{}`]", c),
-            None => "".to_string(),
+impl Package {
+    /// The line shown by `pac list`, with the category and opt marker in
+    /// their colors if `colors` is given
+    pub fn describe(&self, colors: Option<&Colors>) -> String {
+        let paint = |text: &str, color: fn(&Colors) -> Color| match colors {
+            Some(c) => format!("{}", style(text).with(color(c))),
+            None => text.to_string(),
+        };
+        let name = if self.opt {
+            paint("opt", |c| c.opt)
+        } else {
+            "start".to_string()
+        };
+        let on = if !self.load_commands.is_empty() {
+            let cmds = self.load_commands.join("`, `");
+            format!(" [Load on `{}`]", cmds)
+        } else {
+            "".to_string()
         };
 
         let types = if !self.for_types.is_empty() {
@@ -260,21 +820,124 @@ impl fmt::Display for Package {
         } else {
             "".to_string()
         };
-        write!(
-            f,
-            "{} => pack/{}/{}{}{}",
-            &self.idname, &self.category, name, on, types
+        let desc = match self.description {
+            Some(ref d) => format!(" - {}", d),
+            None => "".to_string(),
+        };
+        let enabled = if self.enabled { "" } else { " [disabled]" };
+        format!(
+            "{} => pack/{}/{}{}{}{}{}",
+            &self.idname,
+            paint(&self.category, |c| c.category),
+            name,
+            on,
+            types,
+            enabled,
+            desc
         )
     }
 }
 
-pub fn fetch() -> Result<Vec<Package>> {
-    if PAC_CONFIG_FILE.is_file() {
-        fetch_from_paconfig(&*PAC_CONFIG_FILE)
-            .map_err(|e| Error::PaconfigFile(format!("Fail to parse paconfig: {}", e)))
+impl fmt::Display for Package {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.describe(None))
+    }
+}
+
+/// Relative path of a clone in the store, `<host>/<owner>/<repo>` from either a
+/// url (`https://host/owner/repo.git`) or a scp like `git@host:owner/repo`.
+fn store_relpath(remote: &str) -> PathBuf {
+    let remote = remote.splitn(2, "://").last().unwrap_or(remote);
+    let remote = remote.rsplit('@').next().unwrap_or(remote);
+    let remote = remote.trim_end_matches('/').trim_end_matches(".git");
+    remote
+        .split(['/', ':'])
+        .filter(|p| !p.is_empty() && *p != "..")
+        .collect()
+}
+
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.gz", ".tgz", ".tar.xz", ".tar.bz2", ".tar", ".zip"];
+/// Path components release and archive urls of git hosts put after the repo
+const ARCHIVE_MARKERS: &[&str] = &["releases", "archive", "-", "raw"];
+/// Directories vim reads from a package, which an archive may hold alone
+const RUNTIME_DIRS: &[&str] = &[
+    "after", "autoload", "colors", "compiler", "doc", "ftdetect", "ftplugin", "indent", "keymap",
+    "lua", "plugin", "syntax",
+];
+/// File in an unpacked archive with the remote and digest it came from
+pub const ARCHIVE_STAMP: &str = ".pac-archive";
+
+/// `remote` without its archive extension, `None` if it isn't an archive.
+/// A query string or fragment after the extension is allowed.
+fn archive_stem(remote: &str) -> Option<&str> {
+    let path = remote.split(['?', '#']).next().unwrap_or(remote);
+    ARCHIVE_EXTENSIONS
+        .iter()
+        .find_map(|ext| path.strip_suffix(ext))
+}
+
+/// Whether `remote` is the url or path of an archive to unpack
+pub fn is_archive(remote: &str) -> bool {
+    archive_stem(remote).is_some()
+}
+
+/// Unpack `archive` into `dir` with `unzip`, or `tar` which reads any
+/// compression it knows
+fn unpack(archive: &Path, dir: &Path, zip: bool) -> Result<()> {
+    let mut cmd = if zip {
+        let mut cmd = process::Command::new("unzip");
+        cmd.arg("-q").arg(archive).arg("-d").arg(dir);
+        cmd
     } else {
-        Ok(vec![])
+        let mut cmd = process::Command::new("tar");
+        cmd.arg("-xf").arg(archive).arg("-C").arg(dir);
+        cmd
+    };
+    let out = cmd.stdin(process::Stdio::null()).output()?;
+    if !out.status.success() {
+        return Err(Error::archive(String::from_utf8_lossy(&out.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// `remote` with its owner replaced by `user`, `https://host/user/repo` or
+/// `git@host:user/repo`
+fn fork_remote(remote: &str, user: &str) -> String {
+    match remote.rsplit_once('/') {
+        Some((head, repo)) => match head.rfind(['/', ':']) {
+            Some(i) => format!("{}{}/{}", &head[..=i], user, repo),
+            None => format!("{}/{}", user, repo),
+        },
+        None => remote.to_string(),
+    }
+}
+
+/// Put packages in the order they are saved and loaded in, by `order` and
+/// else as given, which is paconfig's order with new packages at the end
+pub fn sort(packs: &mut [Package]) {
+    packs.sort_by_key(|p| p.order);
+}
+
+/// The packages of paconfig, with the defaults of the manifests of the
+/// installed ones merged in
+pub fn fetch(ctx: &Context) -> Result<Vec<Package>> {
+    if !ctx.config_file.is_file() {
+        return Ok(vec![]);
+    }
+    let mut packs = fetch_from_paconfig(&ctx.config_file)
+        .map_err(|e| Error::PaconfigFile(format!("Fail to parse paconfig: {}", e)))?;
+    for pack in packs.iter_mut() {
+        if let Ok(Some(mut manifest)) = pack.manifest(ctx) {
+            // a package under start/ is loaded anyway, install moves the new
+            // ones with lazy manifests under opt/
+            if !pack.opt {
+                manifest.load_commands.clear();
+                manifest.for_types.clear();
+            }
+            pack.merge_manifest(&manifest);
+        }
     }
+    Ok(packs)
 }
 
 /// Returns a list of packages parsed from paconfig
@@ -284,76 +947,349 @@ fn fetch_from_paconfig<P: AsRef<Path>>(paconfig: P) -> Result<Vec<Package>> {
     let docs = YamlLoader::load_from_str(&data)?;
 
     let mut ret = Vec::new();
-    if !docs.is_empty() {
-        if let Some(doc) = docs[0].as_vec() {
-            for d in doc {
-                ret.push(Package::from_yaml(d)?);
+    if let Some(doc) = docs.into_iter().next() {
+        let doc = migrate::migrate(doc)?;
+        let sets = Sets::from_yaml(&doc)?;
+        if let Some(packs) = doc["packages"].as_vec() {
+            for d in packs {
+                let mut pack = Package::from_yaml(d)?;
+                pack.disabled = sets.disables(&pack);
+                ret.push(pack);
             }
         }
     }
     Ok(ret)
 }
 
-/// Write out the yaml paconfig under `PAC_CONFIG_DIR` creating it
+fn existing_paconfig(path: &Path) -> Option<Yaml> {
+    let data = fs::read_to_string(path).ok()?;
+    let doc = YamlLoader::load_from_str(&data).ok()?.into_iter().next()?;
+    migrate::migrate(doc).ok()
+}
+
+/// Write out the yaml paconfig under the config directory creating it
 /// if necessary.
-pub fn save(packs: Vec<Package>) -> Result<()> {
+pub fn save(ctx: &Context, packs: Vec<Package>) -> Result<()> {
     let packs = packs
         .into_iter()
         .map(|e| e.into_yaml())
         .collect::<Vec<Yaml>>();
-    let doc = Yaml::Array(packs);
+    save_section(ctx, "packages", Some(Yaml::Array(packs)))
+}
+
+/// Replace the top level `key` of paconfig, or remove it if `value` is
+/// `None`. The other sections, like settings, are kept as they are.
+pub fn save_section(ctx: &Context, key: &str, value: Option<Yaml>) -> Result<()> {
+    let mut doc = match existing_paconfig(&ctx.config_file) {
+        Some(Yaml::Hash(h)) => h,
+        _ => Hash::new(),
+    };
+    doc.insert(Yaml::from_str("version"), Yaml::Integer(CONFIG_VERSION));
+    match value {
+        Some(v) => doc.insert(Yaml::from_str(key), v),
+        None => doc.remove(&Yaml::from_str(key)),
+    };
+    let doc = Yaml::Hash(doc);
     let mut out = String::new();
     {
         let mut emitter = YamlEmitter::new(&mut out);
         emitter.dump(&doc)?;
     }
-    if !PAC_CONFIG_DIR.is_dir() {
-        fs::create_dir_all(&*PAC_CONFIG_DIR)?;
+    if !ctx.config_dir.is_dir() {
+        fs::create_dir_all(&ctx.config_dir)?;
     }
-    let mut f = File::create(&*PAC_CONFIG_FILE)?;
+    let mut f = File::create(&ctx.config_file)?;
     f.write_all(PAC_CONFIG_HEADER)?;
     f.write_all(out.as_bytes())?;
     Ok(())
 }
 
 /// Update `_pac.vim` file in plugin directory.
-pub fn update_pac_plugin(packs: &[Package]) -> Result<()> {
-    if !VIM_PLUGIN_DIR.is_dir() {
-        fs::create_dir_all(&*VIM_PLUGIN_DIR)?;
+pub fn update_pac_plugin(ctx: &Context, packs: &[Package]) -> Result<()> {
+    let packs: Vec<Package> = packs.iter().filter(|p| p.enabled).cloned().collect();
+    let packs = packs.as_slice();
+    if !ctx.plugin_dir.is_dir() {
+        fs::create_dir_all(&ctx.plugin_dir)?;
     }
+    state::update_autoload(ctx)?;
+
+    let mut body = plugins_dict(ctx, packs) + &loader_setup(packs);
 
-    let mut f = File::create(VIM_PLUGIN_DIR.join(PAC_PLUGIN_FILENAME))?;
-    f.write_all(format!("{}\n\n", PAC_PLUGIN_HEADER).as_bytes())?;
+    if let Some(days) = Settings::load(ctx)?.sync_reminder {
+        body += &sync_reminder(ctx, days);
+    }
+
+    // the hash shows which packages the loader was generated for, and lets
+    // an unchanged loader alone. The template counts too, an upgraded pac
+    // writes its own.
+    let inputs = format!("{}{}{}", PAC_PLUGIN_HEADER, body, PAC_PLUGIN_FOOTER);
+    let hash = format!("\" Inputs: {:016x}", utils::fnv1a(inputs.as_bytes()));
+    let path = ctx.plugin_dir.join(PAC_PLUGIN_FILENAME);
+    let current = fs::read_to_string(&path).unwrap_or_default();
+    if current.lines().nth(1) == Some(hash.as_str()) {
+        return Ok(());
+    }
+    let header = PAC_PLUGIN_HEADER.replacen('\n', &format!("\n{}\n", hash), 1);
+    let loader = format!("{}\n\n{}{}", header, body, PAC_PLUGIN_FOOTER);
+    utils::write_atomic(&path, loader.as_bytes())?;
 
-    let mut plug_setup = String::new();
-    for p in packs.iter() {
-        if let Some(ref c) = p.load_command {
+    // what the edit does at startup, a first loader has nothing to compare to
+    let changes = loader::delta(&current, &loader);
+    if !current.is_empty() && !changes.is_empty() {
+        println!("Loader changes:");
+        for line in changes {
+            println!("  {}", line);
+        }
+    }
+    Ok(())
+}
+
+/// The shims, autocommands and conditions setting up the packages to load
+fn loader_setup(packs: &[Package]) -> String {
+    let indent = |text: &str| {
+        text.trim_end()
+            .lines()
+            .map(|l| {
+                if l.is_empty() {
+                    "".to_string()
+                } else {
+                    format!("    {}", l)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    let mut body = String::new();
+    // the setup of packages with the same condition goes under one guard
+    let mut guarded: Vec<(&str, String)> = vec![];
+    for p in packs.iter().filter(|p| !p.disabled) {
+        let mut plug_setup = String::new();
+        // every shim is removed before packadd so the plugin can define
+        // the real commands
+        let cmds = p
+            .load_commands
+            .iter()
+            .map(|c| vim_string(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        for c in &p.load_commands {
             plug_setup += &format!(
-                "command! -nargs=* -range -bang {cmd} packadd {repo} | \
-                 call s:do_cmd('{cmd}', \"<bang>\", <line1>, <line2>, <q-args>)\n\n",
+                "command! -nargs=* -range -bang {cmd} call s:lazy_cmd([{cmds}], {repo}, \
+                 {name}, \"<bang>\", <line1>, <line2>, <q-args>)\n",
                 cmd = c,
-                repo = p.name,
+                cmds = cmds,
+                repo = vim_string(&p.name),
+                name = vim_string(c),
             );
         }
+        if !p.load_commands.is_empty() {
+            plug_setup += "\n";
+        }
 
         if !p.for_types.is_empty() {
             plug_setup += &format!(
-                "autocmd FileType {} packadd {}\n\n",
+                "autocmd FileType {} call s:packadd({})\n\n",
                 p.for_types.join(","),
-                p.name,
+                vim_string(&p.name),
             );
         }
+        // with nothing to wait for, the condition alone loads it
+        if p.condition.is_some() && !p.is_lazy() {
+            plug_setup += &format!("call s:packadd({})\n", vim_string(&p.name));
+        }
+
+        if plug_setup.is_empty() {
+            continue;
+        }
+        let block = format!(
+            "\" {}\nif s:enabled({}, {})\n{}\nendif\n\n",
+            &p.name,
+            vim_string(&p.name),
+            vim_string(&p.idname),
+            indent(&plug_setup)
+        );
+        match p.condition {
+            Some(ref c) => match guarded.iter_mut().find(|(g, _)| g == c) {
+                Some((_, blocks)) => *blocks += &block,
+                None => guarded.push((c, block)),
+            },
+            None => body += &block,
+        }
+    }
+    for (condition, blocks) in guarded {
+        body += &format!("if {}\n{}\nendif\n\n", condition, indent(&blocks));
+    }
+    body
+}
+
+/// Loader lines echoing a reminder at startup when the last sync of all
+/// packages is `days` old, on the first start of a day only. Nothing is said
+/// before the first sync.
+fn sync_reminder(ctx: &Context, days: u32) -> String {
+    format!(
+        "\" remind to sync when the last sync is {days} days old, once a day
+let s:reminded = {file}
+if localtime() - getftime(s:reminded) >= 86400
+    let s:ago = (localtime() - pac#state().synced) / 86400
+    if pac#state().synced > 0 && s:ago >= {days}
+        augroup pac_sync_reminder
+            autocmd!
+            autocmd VimEnter * echomsg 'pac: plugins were last synced '
+                \\ . (s:ago == 1 ? 'a day' : s:ago . ' days') . ' ago, run pac update'
+        augroup END
+        silent! call writefile([], s:reminded)
+    endif
+endif
 
-        if !plug_setup.is_empty() {
-            plug_setup = format!("\" {}\n", &p.name) + &plug_setup;
-            f.write_all(plug_setup.as_bytes())?;
+",
+        days = days,
+        file = vim_string(&ctx.reminder_file().to_string_lossy()),
+    )
+}
+
+/// Quote `s` as a vim string literal
+fn vim_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// `g:pac_plugins`, the packages keyed by the name they are packadd'ed with,
+/// for statuslines and other plugins to look at
+fn plugins_dict(ctx: &Context, packs: &[Package]) -> String {
+    let list = |items: &[String]| {
+        let items: Vec<String> = items.iter().map(|i| vim_string(i)).collect();
+        format!("[{}]", items.join(", "))
+    };
+    let packs = dependency_order(packs);
+    let mut dict = String::from("let g:pac_plugins = {}\n");
+    for p in &packs {
+        // a disabled package has nothing set up to load it
+        let (cmds, types) = if p.disabled {
+            (&[][..], &[][..])
+        } else {
+            (&p.load_commands[..], &p.for_types[..])
+        };
+        // by name like the keys, leaving out what is missing or disabled
+        let requires: Vec<String> = p
+            .requires
+            .iter()
+            .filter_map(|r| packs.iter().find(|d| d.idname == *r && !d.disabled))
+            .map(|d| d.name.clone())
+            .collect();
+        dict += &format!(
+            "let g:pac_plugins[{}] = {{'idname': {}, 'category': {}, 'opt': {}, \
+             'on': {}, 'for': {}, 'requires': {}, 'path': {}}}\n",
+            vim_string(&p.name),
+            vim_string(&p.idname),
+            vim_string(&p.category),
+            (p.opt || p.disabled) as u8,
+            list(cmds),
+            list(types),
+            list(&requires),
+            vim_string(&p.path(ctx).display().to_string()),
+        );
+    }
+    // a dict has no order, the packages are loaded in this one
+    let names: Vec<String> = packs.iter().map(|p| p.name.clone()).collect();
+    dict + &format!("let s:order = {}\n\n", list(&names))
+}
 
-            plug_setup.clear();
+/// `packs` in their order, except that every package comes after the ones it
+/// requires. Of a cycle, the package reached first goes last.
+fn dependency_order(packs: &[Package]) -> Vec<&Package> {
+    fn visit<'a>(
+        packs: &'a [Package],
+        pack: &'a Package,
+        path: &mut Vec<&'a Package>,
+        out: &mut Vec<&'a Package>,
+    ) {
+        let seen = |list: &[&Package]| list.iter().any(|p| std::ptr::eq(*p, pack));
+        if seen(path) || seen(out) {
+            return;
         }
+        path.push(pack);
+        for req in &pack.requires {
+            if let Some(dep) = packs.iter().find(|p| p.idname == *req) {
+                visit(packs, dep, path, out);
+            }
+        }
+        path.pop();
+        out.push(pack);
+    }
+
+    let mut out = vec![];
+    for pack in packs {
+        visit(packs, pack, &mut vec![], &mut out);
+    }
+    out
+}
+
+/// What is wrong with `name` as a single plain path component, which a name
+/// that could point anywhere isn't
+fn component_problem(name: &str) -> Option<&'static str> {
+    if name.is_empty() {
+        Some("must not be empty")
+    } else if name.contains(['/', '\\']) {
+        Some("must not contain slashes")
+    } else if name.starts_with('.') {
+        Some("must not start with a dot")
+    } else if name.chars().any(char::is_control) {
+        Some("must not contain control characters")
+    } else {
+        None
+    }
+}
+
+/// Categories are directories below pack/
+pub fn validate_category(name: &str) -> Result<()> {
+    match component_problem(name) {
+        Some(problem) => Err(Error::category(format!("`{}` {}", name, problem))),
+        None => Ok(()),
+    }
+}
+
+/// Package names, from `--as` or paconfig, are directories below
+/// `pack/<category>/{start,opt}`
+pub fn validate_name(name: &str) -> Result<()> {
+    match component_problem(name) {
+        Some(problem) => Err(Error::name(format!("`{}` {}", name, problem))),
+        None => Ok(()),
+    }
+}
+
+/// Remotes are given to the git binary, where one starting with a dash would
+/// be read as an option
+pub fn validate_remote(remote: &str) -> Result<()> {
+    if remote.starts_with('-') {
+        return Err(Error::spec(format!(
+            "`{}` must not start with a dash",
+            remote
+        )));
     }
     Ok(())
 }
 
+/// Remove the `pack/<category>` directories left without packages, with
+/// their empty `start` and `opt`, if the `prune_categories` setting is on
+pub fn prune_categories(ctx: &Context) -> Result<()> {
+    let settings = Settings::load(ctx)?;
+    if !settings.prune_categories {
+        return Ok(());
+    }
+    read_dir(&ctx.pack_dir, |path, cate| {
+        if settings.ignores(Path::new(&cate)) {
+            return Ok(());
+        }
+        // removing a directory which is not empty fails and leaves it
+        for kind in ["start", "opt"] {
+            let _ = fs::remove_dir(path.join(kind));
+        }
+        if fs::remove_dir(path).is_ok() {
+            println!("Removed empty category {}", cate);
+        }
+        Ok(())
+    })
+}
+
 fn read_dir<H>(dir: &Path, mut action: H) -> Result<()>
 where
     H: FnMut(&Path, String) -> Result<()>,
@@ -361,55 +1297,306 @@ where
     if !dir.is_dir() {
         return Ok(());
     }
-    for entry in dir.read_dir()? {
-        if let Ok(e) = entry {
-            let sub = e.path();
-            let item = match sub.file_name().iter().flat_map(|s| s.to_str()).next() {
-                None => continue,
-                Some(i) => i.to_string(),
-            };
-            if sub.is_dir() && !item.starts_with('.') {
-                action(&sub, item)?;
-            }
+    for e in dir.read_dir()?.flatten() {
+        let sub = e.path();
+        let item = match sub.file_name().iter().flat_map(|s| s.to_str()).next() {
+            None => continue,
+            Some(i) => i.to_string(),
+        };
+        if sub.is_dir() && !item.starts_with('.') {
+            action(&sub, item)?;
         }
     }
     Ok(())
 }
 
-pub fn walk_packs<F>(category: &Option<String>, start: bool, opt: bool, callback: F) -> Result<()>
-where
-    F: Fn(&str, &str, &str),
-{
-    read_dir(&VIM_PACKAGE_DIR, |path, cate| {
-        let is_match = category.as_ref().map_or(true, |c| *c == cate);
-        if !is_match {
-            Ok(())
-        } else {
-            read_dir(path, |subpath, option| {
-                if (start && option != "start")
-                    || (opt && option != "opt")
-                    || (option != "start" && option != "opt")
-                {
-                    Ok(())
-                } else {
-                    read_dir(subpath, |_, name| {
-                        callback(&cate, &option, &name);
-                        Ok(())
-                    })
-                }
+/// A package directory found under `pack/`, whether paconfig lists it or not
+#[derive(Debug, Clone)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub category: String,
+    pub opt: bool,
+    pub path: PathBuf,
+    /// Checked out commit, `None` if the directory is not a git repository
+    pub head: Option<Oid>,
+    /// Whether tracked files have uncommitted changes
+    pub dirty: bool,
+}
+
+impl InstalledPackage {
+    /// `category/start/name`, relative to `pack/`
+    pub fn relpath(&self) -> PathBuf {
+        let option = if self.opt { "opt" } else { "start" };
+        [&self.category, option, &self.name].iter().collect()
+    }
+}
+
+/// Every package directory in `pack/<category>/{start,opt}` no `ignore`
+/// setting matches, sorted by category, start before opt, then by name
+pub fn installed(ctx: &Context) -> Result<Vec<InstalledPackage>> {
+    let settings = Settings::load(ctx)?;
+    let mut found = vec![];
+    read_dir(&ctx.pack_dir, |path, category| {
+        read_dir(path, |subpath, option| {
+            if option != "start" && option != "opt" {
+                return Ok(());
+            }
+            read_dir(subpath, |dir, name| {
+                let checkout = git::checkout(dir);
+                found.push(InstalledPackage {
+                    name,
+                    category: category.clone(),
+                    opt: option == "opt",
+                    path: dir.to_path_buf(),
+                    head: checkout.map(|(oid, _)| oid),
+                    dirty: checkout.is_some_and(|(_, dirty)| dirty),
+                });
+                Ok(())
             })
-        }
+        })
     })?;
-    Ok(())
+    found.retain(|p| !settings.ignores(&p.relpath()));
+    found.sort_by(|a, b| (&a.category, a.opt, &a.name).cmp(&(&b.category, b.opt, &b.name)));
+    Ok(found)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::settings::{Settings, Theme};
+    use std::env;
+
+    #[test]
+    fn package_save_and_fetch() {
+        let root = env::temp_dir().join(format!("pac-test-{}", process::id()));
+        let ctx = Context::new(Some(root.clone()), None);
+
+        let mut pack = Package::new(
+            "fzf.vim",
+            "https://github.com/junegunn/fzf.vim",
+            "lang",
+            true,
+        );
+        pack.requires = vec!["junegunn/fzf".to_string()];
+        pack.branch = Some("devel".to_string());
+        pack.post_install = vec!["./setup.sh".to_string()];
+        let mut goyo = Package::new("goyo.vim", "https://github.com/me/goyo.vim", "lang", false);
+        goyo.enabled = false;
+        goyo.submodules = false;
+        goyo.condition = Some("has('gui_running')".to_string());
+        // manifest defaults are merged on load but not saved
+        fs::create_dir_all(pack.path(&ctx)).unwrap();
+        let manifest = "build: make\nrequires: [junegunn/fzf, me/dep]\n";
+        fs::write(pack.path(&ctx).join("pac.yaml"), manifest).unwrap();
+        save(&ctx, vec![pack, goyo]).unwrap();
+
+        let packs = fetch(&ctx).unwrap();
+        assert_eq!(packs[0].build_commands, vec!["make"]);
+        assert_eq!(packs[0].requires, vec!["junegunn/fzf", "me/dep"]);
+
+        // other sections survive rewriting the packages
+        let mut data = fs::read_to_string(&ctx.config_file).unwrap();
+        data.push_str("\nsettings:\n  theme: ascii\n");
+        fs::write(&ctx.config_file, data).unwrap();
+        save(&ctx, packs.clone()).unwrap();
+        let settings = Settings::load(&ctx).unwrap();
+        assert_eq!(settings.theme, Theme::ascii());
+        let data = fs::read_to_string(&ctx.config_file).unwrap();
+        assert!(data.contains("junegunn/fzf") && !data.contains("me/dep"));
+        assert!(!data.contains("make"));
+
+        // the loader is replaced whole, carrying the hash of what it loads
+        update_pac_plugin(&ctx, &packs).unwrap();
+        let loader = fs::read_to_string(ctx.plugin_dir.join(PAC_PLUGIN_FILENAME)).unwrap();
+        let hash = loader.lines().nth(1).unwrap().to_string();
+        assert!(hash.starts_with("\" Inputs: "));
+        assert!(!loader.contains("goyo"));
+        update_pac_plugin(&ctx, &[]).unwrap();
+        let loader = fs::read_to_string(ctx.plugin_dir.join(PAC_PLUGIN_FILENAME)).unwrap();
+        assert_ne!(loader.lines().nth(1), Some(hash.as_str()));
+        assert_eq!(fs::read_dir(&ctx.plugin_dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(packs.len(), 2);
+        assert!(!packs[1].enabled);
+        assert!(packs[0].submodules && !packs[1].submodules);
+        // a condition needs the package under opt/ to packadd
+        assert!(packs[1].opt && packs[1].condition.as_deref() == Some("has('gui_running')"));
+        assert_eq!(packs[0].idname, "junegunn/fzf.vim");
+        assert_eq!(packs[0].branch.as_deref(), Some("devel"));
+        assert_eq!(packs[0].post_install, vec!["./setup.sh"]);
+        assert_eq!(packs[0].path(&ctx), root.join("pack/lang/opt/fzf.vim"));
+    }
+
+    #[test]
+    fn package_plugins_dict() {
+        let ctx = Context::with_paths("/vim", "/vim/.pac/paconfig.yaml");
+        let mut pack = Package::new("it's", "https://github.com/me/it's", "lang", true);
+        pack.load_commands = vec!["Run".to_string()];
+        let dict = plugins_dict(&ctx, &[pack]);
+        assert_eq!(
+            dict.lines().nth(1).unwrap(),
+            "let g:pac_plugins['it''s'] = {'idname': 'me/it''s', 'category': 'lang', \
+             'opt': 1, 'on': ['Run'], 'for': [], 'requires': [], \
+             'path': '/vim/pack/lang/opt/it''s'}"
+        );
+
+        let pack = |name: &str, requires: &[&str]| {
+            let mut p = Package::new(name, &format!("https://github.com/me/{}", name), "a", true);
+            p.requires = requires.iter().map(|r| format!("me/{}", r)).collect();
+            p
+        };
+        let packs = [
+            pack("a", &["b", "c"]),
+            pack("b", &["c", "a"]),
+            pack("c", &[]),
+        ];
+        let order: Vec<&str> = dependency_order(&packs)
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(order, ["c", "b", "a"]);
+        assert!(plugins_dict(&ctx, &packs).contains("'for': [], 'requires': ['c', 'a'],"));
+    }
+
+    #[test]
+    fn package_loader_conditions() {
+        let pack = |name: &str, condition: &str| {
+            let mut p = Package::new(name, &format!("https://github.com/me/{}", name), "a", true);
+            p.condition = Some(condition.to_string()).filter(|c| !c.is_empty());
+            p
+        };
+        let mut lsp = pack("lsp", "has('nvim')");
+        lsp.for_types = vec!["go".to_string()];
+        let packs = [
+            pack("ts", "has('nvim')"),
+            pack("plain", ""),
+            lsp,
+            pack("gui", "has('gui')"),
+        ];
+        assert_eq!(
+            loader_setup(&packs),
+            "if has('nvim')\n    \" ts\n    if s:enabled('ts', 'me/ts')\n        \
+             call s:packadd('ts')\n    endif\n\n    \" lsp\n    \
+             if s:enabled('lsp', 'me/lsp')\n        autocmd FileType go call s:packadd('lsp')\n    \
+             endif\nendif\n\n\
+             if has('gui')\n    \" gui\n    if s:enabled('gui', 'me/gui')\n        \
+             call s:packadd('gui')\n    endif\nendif\n\n"
+        );
+
+        let mut quoted = pack("it's", "");
+        quoted.load_commands = vec!["Run".to_string()];
+        assert!(loader_setup(&[quoted]).contains("s:lazy_cmd(['Run'], 'it''s', 'Run', "));
+    }
+
+    #[test]
+    fn package_store_relpath() {
+        let path = PathBuf::from("github.com/tpope/vim-surround");
+        assert_eq!(store_relpath("https://github.com/tpope/vim-surround"), path);
+        assert_eq!(store_relpath("git@github.com:tpope/vim-surround.git"), path);
+
+        let mut pack = Package::new("s", "git@github.com:tpope/vim-surround", "default", false);
+        pack.fork = Some("me".to_string());
+        assert_eq!(pack.clone_url(), "git@github.com:me/vim-surround");
+        assert_eq!(
+            fork_remote("https://github.com/tpope/vim-surround", "me"),
+            "https://github.com/me/vim-surround"
+        );
+
+        let local = Package::new("vim-foo", "/src/vim-foo", "default", false);
+        assert_eq!(local.local_dir(), Some(PathBuf::from("/src/vim-foo")));
+        assert_eq!(store_relpath(&local.remote), PathBuf::from("src/vim-foo"));
+        let local = Package::new("vim-foo", "file:///src/vim-foo", "default", false);
+        assert_eq!(local.local_dir(), Some(PathBuf::from("/src/vim-foo")));
+        assert_eq!(pack.local_dir(), None);
+    }
 
     #[test]
     fn package_idname_from_remote() {
         let remote = "https://github.com/username/repo";
         assert_eq!(Package::idname_from_remote(remote), "username/repo");
+        for remote in [
+            "https://gitlab.com/group/username/repo.git",
+            "git@codeberg.org:username/repo.git",
+            "ssh://git@gitea.example.com:2222/username/repo/",
+        ] {
+            assert_eq!(Package::idname_from_remote(remote), "username/repo");
+        }
+        let remote = "https://git.sr.ht/~username/repo";
+        assert_eq!(Package::idname_from_remote(remote), "~username/repo");
+        assert_eq!(Package::name_from_remote("git@host:u/vim-go.git"), "vim-go");
+
+        for remote in [
+            "https://github.com/username/repo/releases/download/v1.0/repo-1.0.tar.gz",
+            "https://gitlab.com/username/repo/-/archive/v1/repo-v1.zip",
+            "https://example.com/username/repo.tgz?download=1",
+        ] {
+            assert!(is_archive(remote));
+            assert_eq!(Package::idname_from_remote(remote), "username/repo");
+        }
+        assert!(!is_archive("https://github.com/username/repo.git"));
+    }
+
+    #[test]
+    fn package_sort() {
+        let pack = |name: &str, order: i64| {
+            let mut p = Package::new(name, &format!("https://github.com/me/{}", name), "x", false);
+            p.order = order;
+            p
+        };
+        let mut packs = vec![
+            pack("c", 0),
+            pack("last", 5),
+            pack("a", 0),
+            pack("first", -1),
+        ];
+        sort(&mut packs);
+        let names: Vec<&str> = packs.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["first", "c", "a", "last"]);
+        let doc = packs[3].clone().into_yaml();
+        assert_eq!(Package::from_yaml(&doc).unwrap().order, 5);
+    }
+
+    #[test]
+    fn package_validate_category() {
+        assert!(validate_category("colors").is_ok());
+        assert!(validate_category("my-plugins").is_ok());
+        for name in &["", "..", ".hidden", "a/b", "a\\b", "a\nb"] {
+            assert!(validate_category(name).is_err(), "{:?}", name);
+            assert!(validate_name(name).is_err(), "{:?}", name);
+        }
+        assert!(validate_name("fugitive").is_ok());
+    }
+
+    #[test]
+    fn package_suggested_build() {
+        let root = env::temp_dir().join(format!("pac-suggest-{}", process::id()));
+        let ctx = Context::with_paths(root.join("vim"), root.join("paconfig.yaml"));
+        let pack = Package::new("ui", "https://github.com/me/ui", "default", false);
+        let dir = pack.repo_path(&ctx);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut found = vec![pack.suggested_build(&ctx)];
+        fs::write(dir.join("package.json"), r#"{"scripts": {"test": "jest"}}"#).unwrap();
+        found.push(pack.suggested_build(&ctx));
+        fs::write(
+            dir.join("package.json"),
+            r#"{"name": "build", "scripts": {"build" : "tsc"}}"#,
+        )
+        .unwrap();
+        found.push(pack.suggested_build(&ctx));
+        fs::write(dir.join("Makefile"), "all:\n").unwrap();
+        found.push(pack.suggested_build(&ctx));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            found,
+            vec![
+                None,
+                None,
+                Some("npm install && npm run build".to_string()),
+                Some("make".to_string()),
+            ]
+        );
     }
 }
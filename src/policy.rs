@@ -0,0 +1,158 @@
+use crate::auth;
+use crate::context::Context;
+use crate::{Error, Result};
+
+use lazy_static::lazy_static;
+use std::fs;
+use std::sync::RwLock;
+
+use yaml_rust::{Yaml, YamlLoader};
+
+lazy_static! {
+    static ref POLICY: RwLock<Policy> = RwLock::new(Policy::default());
+}
+
+/// Guardrails from the `policy` section of paconfig on what pac fetches and
+/// runs. Local paths are never fetched over the network so only the build
+/// rule applies to them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    /// Refuse remotes over http, ssh or the git protocol
+    pub https_only: bool,
+    /// Run build commands of paconfig and of plugin manifests
+    pub allow_build: bool,
+    /// Hosts remotes may point to, any if not given
+    pub allowed_hosts: Option<Vec<String>>,
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy {
+            https_only: false,
+            allow_build: true,
+            allowed_hosts: None,
+        }
+    }
+}
+
+impl Policy {
+    pub fn load(ctx: &Context) -> Result<Policy> {
+        if !ctx.config_file.is_file() {
+            return Ok(Policy::default());
+        }
+        Self::parse(&fs::read_to_string(&ctx.config_file)?)
+    }
+
+    pub fn parse(data: &str) -> Result<Policy> {
+        let docs = YamlLoader::load_from_str(data)?;
+        match docs.first() {
+            Some(doc) => Self::from_yaml(&doc["policy"]),
+            None => Ok(Policy::default()),
+        }
+    }
+
+    fn from_yaml(doc: &Yaml) -> Result<Policy> {
+        let mut policy = Policy::default();
+        match doc {
+            Yaml::Hash(_) => (),
+            Yaml::BadValue | Yaml::Null => return Ok(policy),
+            _ => return Err(Error::settings("`policy` must be a mapping")),
+        }
+        let flag = |key: &str, default: bool| match doc[key] {
+            Yaml::Boolean(b) => Ok(b),
+            Yaml::BadValue => Ok(default),
+            _ => Err(Error::settings(format!(
+                "policy `{}` must be true or false",
+                key
+            ))),
+        };
+        policy.https_only = flag("https_only", policy.https_only)?;
+        policy.allow_build = flag("allow_build", policy.allow_build)?;
+        policy.allowed_hosts = match doc["allowed_hosts"] {
+            Yaml::Array(ref hosts) => Some(
+                hosts
+                    .iter()
+                    .map(|h| h.as_str().map(str::to_lowercase))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| Error::settings("policy `allowed_hosts` must list hosts"))?,
+            ),
+            Yaml::BadValue => None,
+            _ => return Err(Error::settings("policy `allowed_hosts` must be a list")),
+        };
+        Ok(policy)
+    }
+
+    fn allows_remote(&self, remote: &str) -> Result<()> {
+        let host = match remote_host(remote) {
+            Some(host) => host.to_lowercase(),
+            None => return Ok(()),
+        };
+        if self.https_only && !remote.starts_with("https://") {
+            return Err(Error::policy(format!(
+                "`{}` is not fetched over https",
+                remote
+            )));
+        }
+        match self.allowed_hosts {
+            Some(ref hosts) if !hosts.iter().any(|h| *h == auth::canonical_host(&host)) => {
+                Err(Error::policy(format!("{} is not an allowed host", host)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Host of a url or of a scp like `git@host:owner/repo`, `None` for local paths
+fn remote_host(remote: &str) -> Option<&str> {
+    if remote.starts_with("file://") {
+        return None;
+    }
+    if remote.contains("://") {
+        return auth::host(remote);
+    }
+    let (user_host, _) = remote.split_once(':')?;
+    let host = user_host.rsplit('@').next()?;
+    Some(host).filter(|h| !h.is_empty() && !h.contains('/'))
+}
+
+/// Enforce `policy` for the rest of the run
+pub fn set(policy: Policy) {
+    *POLICY.write().unwrap() = policy;
+}
+
+/// Whether pac may fetch from `remote`
+pub fn check_remote(remote: &str) -> Result<()> {
+    POLICY.read().unwrap().allows_remote(remote)
+}
+
+/// Whether pac may run the build commands of a package
+pub fn check_build() -> Result<()> {
+    if POLICY.read().unwrap().allow_build {
+        Ok(())
+    } else {
+        Err(Error::policy("building packages is not allowed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_remotes() {
+        let data = "policy:\n  https_only: true\n  allowed_hosts: [GitHub.com]";
+        let policy = Policy::parse(data).unwrap();
+        assert!(policy.allow_build);
+        assert!(policy.allows_remote("https://github.com/a/b").is_ok());
+        assert!(policy
+            .allows_remote("https://raw.githubusercontent.com/a/b")
+            .is_ok());
+        assert!(policy.allows_remote("http://github.com/a/b").is_err());
+        assert!(policy.allows_remote("git@github.com:a/b").is_err());
+        assert!(policy.allows_remote("https://gitlab.com/a/b").is_err());
+        assert!(policy.allows_remote("/home/me/src/plugin").is_ok());
+
+        assert!(Policy::parse("policy:\n  allow_build: no").is_err());
+        assert_eq!(Policy::parse("version: 2").unwrap(), Policy::default());
+    }
+}
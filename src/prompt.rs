@@ -0,0 +1,120 @@
+use crate::Result;
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static MODE: AtomicU8 = AtomicU8::new(Mode::Implicit as u8);
+
+/// How destructive steps, like discarding local changes or deleting
+/// directories, are confirmed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Each command's own habit, most go ahead and `pac sync` asks
+    Implicit,
+    /// `--yes`, never ask and go ahead
+    Yes,
+    /// `--confirm`, ask before every destructive step
+    Confirm,
+}
+
+/// Confirm destructive steps this way for the rest of the run
+pub fn set_mode(mode: Mode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+fn mode() -> Mode {
+    match MODE.load(Ordering::Relaxed) {
+        m if m == Mode::Yes as u8 => Mode::Yes,
+        m if m == Mode::Confirm as u8 => Mode::Confirm,
+        _ => Mode::Implicit,
+    }
+}
+
+/// Whether to go ahead with a destructive step. `asks` is whether the step
+/// asks even without `--confirm`.
+pub fn confirm(question: &str, asks: bool) -> Result<bool> {
+    match mode() {
+        Mode::Yes => Ok(true),
+        Mode::Implicit if !asks => Ok(true),
+        _ => ask(question),
+    }
+}
+
+/// Ask on the terminal, anything but a yes declines. Without a terminal
+/// nobody can answer, which declines as well.
+fn ask(question: &str) -> Result<bool> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        println!("{} Pass --yes to go ahead without a terminal", question);
+        return Ok(false);
+    }
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    stdin.lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Pick one of `choices` by its first letter, or the whole word, on the
+/// terminal. Without a terminal, or at the end of input, nothing is picked.
+pub fn choose(question: &str, choices: &[&str]) -> Result<Option<usize>> {
+    let listed: Vec<String> = choices
+        .iter()
+        .map(|c| format!("[{}]{}", &c[..1], &c[1..]))
+        .collect();
+    loop {
+        let answer = match read(&format!("{} {}:", question, listed.join(", ")))? {
+            Some(a) => a,
+            None => return Ok(None),
+        };
+        let picked = choices
+            .iter()
+            .position(|c| answer == *c || (answer.len() == 1 && c.starts_with(answer.as_str())));
+        if picked.is_some() {
+            return Ok(picked);
+        }
+    }
+}
+
+/// Pick one of `choices`, listed one per line, by its number on the
+/// terminal. An empty answer, no terminal or the end of input picks nothing.
+pub fn pick(question: &str, choices: &[String]) -> Result<Option<usize>> {
+    if !io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    println!("{}", question);
+    for (i, choice) in choices.iter().enumerate() {
+        println!("  {}) {}", i + 1, choice);
+    }
+    let range = format!("[1-{}, nothing to skip]:", choices.len());
+    loop {
+        let answer = match read(&range)? {
+            Some(a) if !a.is_empty() => a,
+            _ => return Ok(None),
+        };
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=choices.len()).contains(&n) => return Ok(Some(n - 1)),
+            _ => continue,
+        }
+    }
+}
+
+/// A line of text answering `question` on the terminal, `None` for an empty
+/// answer or without a terminal
+pub fn input(question: &str) -> Result<Option<String>> {
+    Ok(read(question)?.filter(|a| !a.is_empty()))
+}
+
+fn read(question: &str) -> Result<Option<String>> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Ok(None);
+    }
+    print!("{} ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    if stdin.lock().read_line(&mut answer)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_string()))
+}
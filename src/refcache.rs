@@ -0,0 +1,200 @@
+use crate::context::Context;
+use crate::utils;
+use crate::{Error, Result};
+
+use git2::Oid;
+use lazy_static::lazy_static;
+use std::fs;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+const REF_CACHE_HEADER: &str = "# vim: ft=yaml
+#
+# Generated by pac, the refs remotes listed recently. Safe to delete.
+
+";
+
+/// Minutes listings are kept across runs, see the `ref_cache` setting
+static TTL: AtomicU32 = AtomicU32::new(DEFAULT_TTL);
+
+pub const DEFAULT_TTL: u32 = 15;
+
+lazy_static! {
+    /// Listings of this run and the ones read from the cache file, `None`
+    /// until it is read
+    static ref LISTINGS: Mutex<Option<Vec<Listing>>> = Mutex::new(None);
+}
+
+/// The refs of a remote as `git ls-remote` lists them. Only `HEAD`, branches
+/// and tags are kept, which is all pac compares with.
+#[derive(Debug, Clone, PartialEq)]
+struct Listing {
+    remote: String,
+    /// Seconds since the epoch when the remote was listed
+    time: u64,
+    refs: Vec<(String, Oid)>,
+}
+
+pub fn set_ttl(minutes: u32) {
+    TTL.store(minutes, Ordering::Relaxed);
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Whether a listing made at `time` is still used at `now`
+fn fresh(time: u64, now: u64, ttl: u32) -> bool {
+    now.saturating_sub(time) < u64::from(ttl) * 60
+}
+
+/// Whether pac compares with `refname`
+pub fn kept(refname: &str) -> bool {
+    refname == "HEAD" || refname.starts_with("refs/heads/") || refname.starts_with("refs/tags/")
+}
+
+/// The refs `remote` had when it was last listed, in this run or in one
+/// within `ref_cache` minutes
+pub fn get(ctx: &Context, remote: &str) -> Option<Vec<(String, Oid)>> {
+    let mut listings = LISTINGS.lock().unwrap();
+    let listings = listings.get_or_insert_with(|| load(ctx).unwrap_or_default());
+    listings
+        .iter()
+        .find(|l| l.remote == remote)
+        .map(|l| l.refs.clone())
+}
+
+/// Remember the refs `remote` has now, see `save`
+pub fn put(remote: &str, refs: Vec<(String, Oid)>) {
+    let mut listings = LISTINGS.lock().unwrap();
+    let listings = listings.get_or_insert_with(Vec::new);
+    listings.retain(|l| l.remote != remote);
+    listings.push(Listing {
+        remote: remote.to_string(),
+        time: now(),
+        refs,
+    });
+}
+
+/// The listings of the cache file which are fresh enough
+fn load(ctx: &Context) -> Result<Vec<Listing>> {
+    let ttl = TTL.load(Ordering::Relaxed);
+    if ttl == 0 {
+        return Ok(vec![]);
+    }
+    let data = match fs::read_to_string(ctx.ref_cache_file()) {
+        Ok(d) => d,
+        Err(_) => return Ok(vec![]),
+    };
+    let now = now();
+    Ok(parse(&data)?
+        .into_iter()
+        .filter(|l| fresh(l.time, now, ttl))
+        .collect())
+}
+
+fn parse(data: &str) -> Result<Vec<Listing>> {
+    let docs = YamlLoader::load_from_str(data)?;
+    let entries = match docs.first().map(|d| &d["remotes"]) {
+        Some(Yaml::Array(entries)) => entries,
+        _ => return Err(Error::Format),
+    };
+    entries
+        .iter()
+        .map(|e| {
+            let refs = match e["refs"] {
+                Yaml::Array(ref refs) => refs
+                    .iter()
+                    .map(|r| {
+                        let (oid, name) = r.as_str().and_then(|r| r.split_once(' '))?;
+                        Some((name.to_string(), Oid::from_str(oid).ok()?))
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(Error::Format)?,
+                _ => return Err(Error::Format),
+            };
+            Ok(Listing {
+                remote: e["remote"].as_str().ok_or(Error::Format)?.to_string(),
+                time: e["time"].as_i64().ok_or(Error::Format)? as u64,
+                refs,
+            })
+        })
+        .collect()
+}
+
+fn to_yaml(listings: &[Listing]) -> Result<String> {
+    let entries = listings
+        .iter()
+        .map(|l| {
+            let refs = l
+                .refs
+                .iter()
+                .map(|(name, oid)| Yaml::String(format!("{} {}", oid, name)))
+                .collect();
+            let mut doc = Hash::new();
+            doc.insert(Yaml::from_str("remote"), Yaml::String(l.remote.clone()));
+            doc.insert(Yaml::from_str("time"), Yaml::Integer(l.time as i64));
+            doc.insert(Yaml::from_str("refs"), Yaml::Array(refs));
+            Yaml::Hash(doc)
+        })
+        .collect();
+    let mut doc = Hash::new();
+    doc.insert(Yaml::from_str("remotes"), Yaml::Array(entries));
+    let mut out = String::from(REF_CACHE_HEADER);
+    {
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.dump(&Yaml::Hash(doc))?;
+    }
+    out.push('\n');
+    Ok(out)
+}
+
+/// Write the listings of this run and the fresh ones of earlier runs to the
+/// cache file, for the runs within `ref_cache` minutes
+pub fn save(ctx: &Context) -> Result<()> {
+    let ttl = TTL.load(Ordering::Relaxed);
+    let listings = LISTINGS.lock().unwrap();
+    let listings = match *listings {
+        Some(ref l) if ttl > 0 => l,
+        _ => return Ok(()),
+    };
+    let now = now();
+    let fresh: Vec<Listing> = listings
+        .iter()
+        .filter(|l| fresh(l.time, now, ttl))
+        .cloned()
+        .collect();
+    fs::create_dir_all(&ctx.cache_dir)?;
+    utils::write_atomic(ctx.ref_cache_file(), to_yaml(&fresh)?.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refcache_yaml_roundtrip() {
+        let oid = Oid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap();
+        let listings = vec![Listing {
+            remote: "https://github.com/me/a".to_string(),
+            time: 1_700_000_000,
+            refs: vec![
+                ("HEAD".to_string(), oid),
+                ("refs/tags/v1.0^{}".to_string(), oid),
+            ],
+        }];
+        assert_eq!(parse(&to_yaml(&listings).unwrap()).unwrap(), listings);
+        assert!(parse("remotes:\n  - remote: x\n    time: 1\n    refs: [HEAD]").is_err());
+
+        assert!(fresh(1000, 1000 + 14 * 60, 15));
+        assert!(!fresh(1000, 1000 + 15 * 60, 15));
+        assert!(!fresh(1000, 1000, 0));
+        assert!(kept("refs/tags/v1.0^{}") && !kept("refs/pull/1/head"));
+    }
+}
@@ -0,0 +1,277 @@
+use crate::context::Context;
+use crate::utils;
+use crate::{Error, Result};
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+const PAC_REGISTRY_HEADER: &[u8] = b"# vim: ft=yaml
+#
+# Generated by pac from `pac registry update`. DO NOT EDIT!
+
+";
+
+/// Recommended install options for a short plugin name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Short name the entry is looked up with, eg. `fzf`
+    pub name: String,
+    /// Either a full remote url or `username/repo` on github
+    pub remote: String,
+    pub category: Option<String>,
+    pub opt: bool,
+    pub load_commands: Vec<String>,
+    pub for_types: Vec<String>,
+    pub build_commands: Vec<String>,
+    pub description: Option<String>,
+}
+
+impl Entry {
+    /// Parse an entry which is either a plain remote string or a hash of options
+    fn from_yaml(name: &str, doc: &Yaml) -> Result<Entry> {
+        if let Some(remote) = doc.as_str() {
+            return Ok(Entry {
+                name: name.to_string(),
+                remote: remote.to_string(),
+                category: None,
+                opt: false,
+                load_commands: vec![],
+                for_types: vec![],
+                build_commands: vec![],
+                description: None,
+            });
+        }
+
+        let get_val = |key: &str| doc[key].as_str().map(|s| s.to_string());
+        let remote = get_val("remote")
+            .ok_or_else(|| Error::registry(format!("`{}` has no remote", name)))?;
+        let get_list = |key: &str| utils::yaml_list(&doc[key]).unwrap_or_default();
+
+        Ok(Entry {
+            name: name.to_string(),
+            remote,
+            category: get_val("category"),
+            opt: doc["opt"].as_bool().unwrap_or(false),
+            load_commands: get_list("on"),
+            for_types: get_list("for"),
+            // shell commands may contain commas so only a list can give several
+            build_commands: match doc["build"] {
+                Yaml::String(ref s) => vec![s.clone()],
+                _ => get_list("build"),
+            },
+            description: get_val("desc"),
+        })
+    }
+
+    fn into_yaml(self) -> Yaml {
+        let mut doc = Hash::new();
+        doc.insert(Yaml::from_str("remote"), Yaml::String(self.remote));
+        if self.opt {
+            doc.insert(Yaml::from_str("opt"), Yaml::Boolean(true));
+        }
+        if let Some(ref c) = self.category {
+            doc.insert(Yaml::from_str("category"), Yaml::from_str(c));
+        }
+        if let Some(d) = self.description {
+            doc.insert(Yaml::from_str("desc"), Yaml::String(d));
+        }
+        let lists = [
+            ("on", self.load_commands),
+            ("for", self.for_types),
+            ("build", self.build_commands),
+        ];
+        for (key, val) in lists.iter() {
+            if !val.is_empty() {
+                let list = val.iter().map(|t| Yaml::from_str(t)).collect();
+                doc.insert(Yaml::from_str(key), Yaml::Array(list));
+            }
+        }
+        Yaml::Hash(doc)
+    }
+
+    /// Remote url to git clone from
+    pub fn remote_url(&self) -> String {
+        if self.remote.contains("://") {
+            self.remote.clone()
+        } else {
+            format!("https://github.com/{}", self.remote)
+        }
+    }
+}
+
+/// Locally cached copy of all configured registries
+#[derive(Debug, Default)]
+pub struct Registry {
+    /// Urls (or paths) the index was fetched from
+    pub urls: Vec<String>,
+    pub entries: Vec<Entry>,
+}
+
+impl Registry {
+    /// Load the cached index, returning an empty one if it was never fetched
+    pub fn load(ctx: &Context) -> Result<Registry> {
+        let path = match [ctx.registry_file(), ctx.legacy_registry_file()]
+            .iter()
+            .find(|p| p.is_file())
+        {
+            Some(p) => p.clone(),
+            None => return Ok(Registry::default()),
+        };
+        let mut data = String::new();
+        File::open(&path)?.read_to_string(&mut data)?;
+        let docs = YamlLoader::load_from_str(&data)?;
+        let doc = match docs.first() {
+            Some(d) => d,
+            None => return Ok(Registry::default()),
+        };
+
+        let urls = doc["urls"]
+            .as_vec()
+            .map(|v| {
+                v.iter()
+                    .flat_map(|u| u.as_str())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Registry {
+            urls,
+            entries: parse_index(doc)?,
+        })
+    }
+
+    pub fn save(self, ctx: &Context) -> Result<()> {
+        let mut doc = Hash::new();
+        let urls = self.urls.iter().map(|u| Yaml::from_str(u)).collect();
+        doc.insert(Yaml::from_str("urls"), Yaml::Array(urls));
+
+        let mut packages = Hash::new();
+        for e in self.entries {
+            packages.insert(Yaml::String(e.name.clone()), e.into_yaml());
+        }
+        doc.insert(Yaml::from_str("packages"), Yaml::Hash(packages));
+
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.dump(&Yaml::Hash(doc))?;
+        }
+        if !ctx.cache_dir.is_dir() {
+            fs::create_dir_all(&ctx.cache_dir)?;
+        }
+        let mut f = File::create(ctx.registry_file())?;
+        // the old copy would be read again whenever the cache is cleared
+        let _ = fs::remove_file(ctx.legacy_registry_file());
+        f.write_all(PAC_REGISTRY_HEADER)?;
+        f.write_all(out.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// Entry of an already installed package
+    pub fn lookup_remote(&self, remote: &str) -> Option<&Entry> {
+        let remote = remote.trim_end_matches(".git");
+        self.entries
+            .iter()
+            .find(|e| e.remote_url().trim_end_matches(".git") == remote)
+    }
+
+    /// Refetch the index from `urls`. When no urls are given the ones used for
+    /// the previous update are reused, falling back to `$PAC_REGISTRY_URL`.
+    /// Entries from earlier urls take precedence over later ones.
+    pub fn update(&mut self, urls: Vec<String>) -> Result<()> {
+        let urls = if !urls.is_empty() {
+            urls
+        } else if !self.urls.is_empty() {
+            self.urls.clone()
+        } else if let Ok(url) = env::var("PAC_REGISTRY_URL") {
+            vec![url]
+        } else {
+            return Err(Error::registry(
+                "No registry configured, use --url or set $PAC_REGISTRY_URL",
+            ));
+        };
+
+        let mut entries: Vec<Entry> = Vec::new();
+        for url in &urls {
+            let body = utils::download(url)?;
+            let body = String::from_utf8(body)
+                .map_err(|_| Error::registry(format!("{} is not valid utf-8", url)))?;
+            // json is (mostly) a subset of yaml so both formats are accepted
+            let docs = YamlLoader::load_from_str(&body)
+                .map_err(|e| Error::registry(format!("{}: {}", url, e)))?;
+            if let Some(doc) = docs.first() {
+                for e in parse_index(doc)? {
+                    if !entries.iter().any(|x| x.name == e.name) {
+                        entries.push(e);
+                    }
+                }
+            }
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.urls = urls;
+        self.entries = entries;
+        Ok(())
+    }
+}
+
+/// Parse the name -> entry mapping either at the top level of the document or
+/// under a `packages` key.
+fn parse_index(doc: &Yaml) -> Result<Vec<Entry>> {
+    let index = match doc["packages"] {
+        Yaml::Hash(ref h) => h,
+        _ => match *doc {
+            Yaml::Hash(ref h) => h,
+            _ => return Err(Error::registry("Index should be a mapping of names")),
+        },
+    };
+
+    let mut entries = Vec::with_capacity(index.len());
+    for (k, v) in index {
+        if let Some(name) = k.as_str() {
+            if name == "urls" {
+                continue;
+            }
+            entries.push(Entry::from_yaml(name, v)?);
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_parse_index() {
+        let index = r#"{
+            "fzf": {"remote": "junegunn/fzf.vim", "on": "Files", "desc": "Fuzzy finder"},
+            "surround": "tpope/vim-surround"
+        }"#;
+        let docs = YamlLoader::load_from_str(index).unwrap();
+        let entries = parse_index(&docs[0]).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].remote_url(),
+            "https://github.com/junegunn/fzf.vim"
+        );
+        assert_eq!(entries[0].load_commands, vec!["Files"]);
+        assert_eq!(entries[0].description.as_deref(), Some("Fuzzy finder"));
+        assert_eq!(entries[1].name, "surround");
+
+        let registry = Registry {
+            urls: vec![],
+            entries,
+        };
+        let found = registry.lookup_remote("https://github.com/junegunn/fzf.vim.git");
+        assert_eq!(found.map(|e| e.name.as_str()), Some("fzf"));
+    }
+}
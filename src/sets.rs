@@ -0,0 +1,129 @@
+use crate::context::Context;
+use crate::package::Package;
+use crate::{Error, Result};
+
+use std::fs;
+
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Named groups of packages, the `sets` of paconfig. The packages of a set
+/// named in `disabled_sets` stay installed under opt/ with nothing set up to
+/// load them.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Sets {
+    /// Names with the idnames or names of their packages, in paconfig order
+    pub sets: Vec<(String, Vec<String>)>,
+    pub disabled: Vec<String>,
+}
+
+impl Sets {
+    pub fn load(ctx: &Context) -> Result<Sets> {
+        if !ctx.config_file.is_file() {
+            return Ok(Sets::default());
+        }
+        let docs = YamlLoader::load_from_str(&fs::read_to_string(&ctx.config_file)?)?;
+        match docs.first() {
+            Some(doc) => Self::from_yaml(doc),
+            None => Ok(Sets::default()),
+        }
+    }
+
+    /// Read `sets` and `disabled_sets` from the whole paconfig document.
+    /// Like other lists of paconfig, a set of one package may be a string.
+    pub fn from_yaml(doc: &Yaml) -> Result<Sets> {
+        let list = |value: &Yaml, what: &str| -> Result<Vec<String>> {
+            match value {
+                Yaml::Array(items) => items
+                    .iter()
+                    .map(|i| {
+                        i.as_str()
+                            .map(String::from)
+                            .ok_or_else(|| Error::set(format!("invalid entry in {}", what)))
+                    })
+                    .collect(),
+                Yaml::String(s) => Ok(vec![s.clone()]),
+                Yaml::BadValue | Yaml::Null => Ok(vec![]),
+                _ => Err(Error::set(format!("{} should be a list", what))),
+            }
+        };
+
+        let mut sets = Sets::default();
+        match doc["sets"] {
+            Yaml::Hash(ref h) => {
+                for (name, members) in h {
+                    let name = name
+                        .as_str()
+                        .ok_or_else(|| Error::set("set names should be strings"))?;
+                    let members = list(members, &format!("set `{}`", name))?;
+                    sets.sets.push((name.to_string(), members));
+                }
+            }
+            Yaml::BadValue | Yaml::Null => (),
+            _ => return Err(Error::set("`sets` should map names to packages")),
+        }
+        sets.disabled = list(&doc["disabled_sets"], "`disabled_sets`")?;
+        if let Some(name) = sets.disabled.iter().find(|n| sets.members(n).is_none()) {
+            return Err(Error::set(format!("no set named `{}` to disable", name)));
+        }
+        Ok(sets)
+    }
+
+    pub fn members(&self, name: &str) -> Option<&[String]> {
+        self.sets
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, m)| m.as_slice())
+    }
+
+    pub fn is_disabled(&self, name: &str) -> bool {
+        self.disabled.iter().any(|n| n == name)
+    }
+
+    /// Names of the sets listing `pack`
+    pub fn of(&self, pack: &Package) -> Vec<&str> {
+        self.sets
+            .iter()
+            .filter(|(_, m)| m.iter().any(|m| *m == pack.idname || *m == pack.name))
+            .map(|(n, _)| n.as_str())
+            .collect()
+    }
+
+    /// Whether any disabled set lists `pack`
+    pub fn disables(&self, pack: &Package) -> bool {
+        self.of(pack).into_iter().any(|n| self.is_disabled(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_from_yaml() {
+        let doc = YamlLoader::load_from_str(
+            "sets:\n  writing: [me/goyo, limelight.vim]\n  golang: me/vim-go\n\
+             disabled_sets: [writing]\n",
+        )
+        .unwrap()
+        .remove(0);
+        let sets = Sets::from_yaml(&doc).unwrap();
+        assert_eq!(sets.members("golang"), Some(&["me/vim-go".to_string()][..]));
+
+        let goyo = Package::new("goyo.vim", "https://github.com/me/goyo", "default", false);
+        let lime = Package::new(
+            "limelight.vim",
+            "https://github.com/o/lime",
+            "default",
+            false,
+        );
+        let go = Package::new("vim-go", "https://github.com/me/vim-go", "default", false);
+        assert!(sets.disables(&goyo) && sets.disables(&lime));
+        assert!(!sets.disables(&go));
+        assert_eq!(sets.of(&go), vec!["golang"]);
+
+        let doc = YamlLoader::load_from_str("sets: {a: []}\ndisabled_sets: b")
+            .unwrap()
+            .remove(0);
+        assert!(Sets::from_yaml(&doc).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use crate::context::Context;
+use crate::package;
+use crate::refcache;
+use crate::task;
+use crate::{Error, Result};
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crossterm::style::Color;
+use yaml_rust::{Yaml, YamlLoader};
+
+/// Glyphs used by the progress display
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// Frames of the spinner in front of running tasks
+    pub spinner: Vec<char>,
+    /// Filled and empty cell of the overall progress bar
+    pub bar: (char, char),
+    pub success: char,
+    pub failure: char,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            spinner: "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏".chars().collect(),
+            bar: ('█', '░'),
+            success: '✓',
+            failure: '✗',
+        }
+    }
+}
+
+impl Theme {
+    /// For fonts and terminals without the unicode glyphs
+    pub fn ascii() -> Theme {
+        Theme {
+            spinner: "|/-\\".chars().collect(),
+            bar: ('#', '-'),
+            success: '+',
+            failure: 'x',
+        }
+    }
+
+    /// `theme` is either the name of a preset or a mapping overriding
+    /// single glyphs of the default theme, or of `preset` if given.
+    fn from_yaml(doc: &Yaml) -> Result<Theme> {
+        let preset = |name: &str| match name {
+            "default" => Ok(Theme::default()),
+            "ascii" => Ok(Theme::ascii()),
+            _ => Err(Error::settings(format!("unknown theme `{}`", name))),
+        };
+        let hash = match doc {
+            Yaml::String(name) => return preset(name),
+            Yaml::Hash(_) => doc,
+            Yaml::BadValue | Yaml::Null => return Ok(Theme::default()),
+            _ => return Err(Error::settings("invalid `theme` value")),
+        };
+
+        let mut theme = match hash["preset"].as_str() {
+            Some(name) => preset(name)?,
+            None => Theme::default(),
+        };
+        let glyphs = |key: &str| -> Result<Option<Vec<char>>> {
+            match hash[key] {
+                Yaml::String(ref s) if !s.is_empty() => Ok(Some(s.chars().collect())),
+                Yaml::BadValue => Ok(None),
+                _ => Err(Error::settings(format!("invalid theme `{}`", key))),
+            }
+        };
+        if let Some(spinner) = glyphs("spinner")? {
+            theme.spinner = spinner;
+        }
+        if let Some(bar) = glyphs("bar")? {
+            if bar.len() != 2 {
+                return Err(Error::settings(
+                    "theme `bar` needs a filled and an empty glyph",
+                ));
+            }
+            theme.bar = (bar[0], bar[1]);
+        }
+        if let Some(success) = glyphs("success")? {
+            theme.success = success[0];
+        }
+        if let Some(failure) = glyphs("failure")? {
+            theme.failure = failure[0];
+        }
+        Ok(theme)
+    }
+}
+
+/// Colors of the semantic parts of the output
+#[derive(Debug, Clone, PartialEq)]
+pub struct Colors {
+    pub success: Color,
+    pub failure: Color,
+    /// Category of a package in `pac list`
+    pub category: Color,
+    /// The `opt` marker of optional packages in `pac list`
+    pub opt: Color,
+}
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            success: Color::Green,
+            failure: Color::Red,
+            category: Color::Blue,
+            opt: Color::Yellow,
+        }
+    }
+}
+
+impl Colors {
+    /// Every key is optional and given as a name like `dark_green`, `#rrggbb`
+    /// or `reset` for the terminal's own color.
+    fn from_yaml(doc: &Yaml) -> Result<Colors> {
+        let mut colors = Colors::default();
+        match doc {
+            Yaml::Hash(_) => (),
+            Yaml::BadValue | Yaml::Null => return Ok(colors),
+            _ => return Err(Error::settings("`colors` must be a mapping")),
+        }
+        let color = |key: &str| -> Result<Option<Color>> {
+            match doc[key] {
+                Yaml::String(ref s) => parse_color(s)
+                    .map(Some)
+                    .ok_or_else(|| Error::settings(format!("unknown color `{}` for `{}`", s, key))),
+                Yaml::BadValue => Ok(None),
+                _ => Err(Error::settings(format!("invalid color for `{}`", key))),
+            }
+        };
+        for (key, field) in [
+            ("success", &mut colors.success),
+            ("failure", &mut colors.failure),
+            ("category", &mut colors.category),
+            ("opt", &mut colors.opt),
+        ] {
+            if let Some(c) = color(key)? {
+                *field = c;
+            }
+        }
+        Ok(colors)
+    }
+}
+
+fn wildcard(pattern: &[u8], s: &[u8]) -> bool {
+    match (pattern.first(), s.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard(&pattern[1..], s) || (!s.is_empty() && wildcard(pattern, &s[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard(&pattern[1..], &s[1..]),
+        (Some(p), Some(c)) if p == c => wildcard(&pattern[1..], &s[1..]),
+        _ => false,
+    }
+}
+
+fn parse_color(s: &str) -> Option<Color> {
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    Color::try_from(s).ok()
+}
+
+/// User preferences from the `settings` section of paconfig
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub theme: Theme,
+    pub colors: Colors,
+    /// Send a desktop notification after runs without a terminal
+    pub notify: bool,
+    /// Category of installs and imports without `--category`
+    pub default_category: String,
+    /// Remove category directories emptied by moves and removals
+    pub prune_categories: bool,
+    /// Patterns of directories under pack/ which pac leaves alone, like
+    /// `manual` or `*/opt/local-*`
+    pub ignore: Vec<String>,
+    /// The git pac runs where libgit2 falls short, a name or a path
+    pub git_binary: Option<String>,
+    /// Days after the last sync the loader reminds to sync again, once a day
+    pub sync_reminder: Option<u32>,
+    /// Minutes the refs listed on a remote are reused by later runs, 0 to
+    /// list them again every run
+    pub ref_cache: u32,
+    /// Times a clone or pull failing on the network is tried again
+    pub retries: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            theme: Theme::default(),
+            colors: Colors::default(),
+            notify: false,
+            default_category: "default".to_string(),
+            prune_categories: false,
+            ignore: vec![],
+            git_binary: None,
+            sync_reminder: None,
+            ref_cache: refcache::DEFAULT_TTL,
+            retries: task::DEFAULT_RETRIES,
+        }
+    }
+}
+
+impl Settings {
+    /// Whether `relpath`, relative to pack/, is or is inside a directory an
+    /// `ignore` pattern matches. `*` and `?` match within one path component.
+    pub fn ignores(&self, relpath: &Path) -> bool {
+        let parts: Vec<&str> = relpath.iter().filter_map(|p| p.to_str()).collect();
+        self.ignore.iter().any(|pattern| {
+            let pattern: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+            pattern.len() <= parts.len()
+                && pattern
+                    .iter()
+                    .zip(&parts)
+                    .all(|(p, part)| wildcard(p.as_bytes(), part.as_bytes()))
+        })
+    }
+
+    pub fn load(ctx: &Context) -> Result<Settings> {
+        if !ctx.config_file.is_file() {
+            return Ok(Settings::default());
+        }
+        let mut data = String::new();
+        File::open(&ctx.config_file)?.read_to_string(&mut data)?;
+        Self::parse(&data)
+    }
+
+    pub fn parse(data: &str) -> Result<Settings> {
+        let docs = YamlLoader::load_from_str(data)?;
+        match docs.first() {
+            Some(doc) => Self::from_yaml(&doc["settings"]),
+            None => Ok(Settings::default()),
+        }
+    }
+
+    fn from_yaml(doc: &Yaml) -> Result<Settings> {
+        let flag = |key: &str| match doc[key] {
+            Yaml::Boolean(b) => Ok(b),
+            Yaml::BadValue => Ok(false),
+            _ => Err(Error::settings(format!("`{}` must be true or false", key))),
+        };
+        match doc {
+            Yaml::Hash(_) => Ok(Settings {
+                theme: Theme::from_yaml(&doc["theme"])?,
+                colors: Colors::from_yaml(&doc["colors"])?,
+                notify: flag("notify")?,
+                default_category: match doc["default_category"] {
+                    Yaml::String(ref s) => {
+                        package::validate_category(s)?;
+                        s.clone()
+                    }
+                    Yaml::BadValue => Settings::default().default_category,
+                    _ => return Err(Error::settings("`default_category` must be a name")),
+                },
+                prune_categories: flag("prune_categories")?,
+                ignore: match doc["ignore"] {
+                    Yaml::Array(ref items) => items
+                        .iter()
+                        .map(|i| i.as_str().map(String::from))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| Error::settings("`ignore` must list patterns"))?,
+                    Yaml::String(ref s) => vec![s.clone()],
+                    Yaml::BadValue => vec![],
+                    _ => return Err(Error::settings("`ignore` must list patterns")),
+                },
+                git_binary: match doc["git_binary"] {
+                    Yaml::String(ref s) if !s.is_empty() => Some(s.clone()),
+                    Yaml::BadValue => None,
+                    _ => return Err(Error::settings("`git_binary` must be a name or path")),
+                },
+                sync_reminder: match doc["sync_reminder"] {
+                    Yaml::Integer(0) | Yaml::Boolean(false) | Yaml::BadValue => None,
+                    Yaml::Integer(days) if days > 0 && days <= u32::MAX as i64 => Some(days as u32),
+                    _ => {
+                        return Err(Error::settings(
+                            "`sync_reminder` must be a number of days or false",
+                        ))
+                    }
+                },
+                ref_cache: match doc["ref_cache"] {
+                    Yaml::Integer(0) | Yaml::Boolean(false) => 0,
+                    Yaml::BadValue => refcache::DEFAULT_TTL,
+                    Yaml::Integer(minutes) if minutes > 0 && minutes <= u32::MAX as i64 => {
+                        minutes as u32
+                    }
+                    _ => {
+                        return Err(Error::settings(
+                            "`ref_cache` must be a number of minutes or false",
+                        ))
+                    }
+                },
+                retries: match doc["retries"] {
+                    Yaml::Integer(n) if n >= 0 && n <= u32::MAX as i64 => n as u32,
+                    Yaml::BadValue => task::DEFAULT_RETRIES,
+                    _ => return Err(Error::settings("`retries` must be a number")),
+                },
+            }),
+            Yaml::BadValue | Yaml::Null => Ok(Settings::default()),
+            _ => Err(Error::settings("`settings` must be a mapping")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_theme() {
+        let s = Settings::parse("packages: []\nsettings:\n  theme: ascii").unwrap();
+        assert_eq!(s.theme, Theme::ascii());
+
+        let s = Settings::parse("settings:\n  theme:\n    preset: ascii\n    success: '*'");
+        let theme = s.unwrap().theme;
+        assert_eq!(theme.success, '*');
+        assert_eq!(theme.bar, ('#', '-'));
+
+        assert_eq!(
+            Settings::parse("version: 2").unwrap().theme,
+            Theme::default()
+        );
+        assert!(Settings::parse("settings:\n  theme: neon").is_err());
+
+        let s = Settings::parse("settings:\n  default_category: lang").unwrap();
+        assert_eq!(s.default_category, "lang");
+        assert_eq!(Settings::default().default_category, "default");
+        assert!(Settings::parse("settings:\n  default_category: ../x").is_err());
+    }
+
+    #[test]
+    fn settings_ignore() {
+        let s = Settings::parse("settings:\n  ignore: [manual, '*/opt/local-*']").unwrap();
+        assert!(s.ignores(Path::new("manual")));
+        assert!(s.ignores(Path::new("manual/start/x")));
+        assert!(s.ignores(Path::new("default/opt/local-fzf")));
+        assert!(!s.ignores(Path::new("default/start/local-fzf")));
+        assert!(!s.ignores(Path::new("manuals/start/x")));
+        assert!(Settings::parse("settings:\n  ignore: [1]").is_err());
+
+        let s = Settings::parse("settings:\n  git_binary: /opt/homebrew/bin/git").unwrap();
+        assert_eq!(s.git_binary.as_deref(), Some("/opt/homebrew/bin/git"));
+        assert!(Settings::parse("settings:\n  git_binary: ''").is_err());
+
+        let s = Settings::parse("settings:\n  sync_reminder: 14").unwrap();
+        assert_eq!(s.sync_reminder, Some(14));
+        let s = Settings::parse("settings:\n  sync_reminder: false").unwrap();
+        assert_eq!(s.sync_reminder, None);
+        assert!(Settings::parse("settings:\n  sync_reminder: -1").is_err());
+
+        assert_eq!(Settings::default().ref_cache, refcache::DEFAULT_TTL);
+        let s = Settings::parse("settings:\n  ref_cache: false").unwrap();
+        assert_eq!(s.ref_cache, 0);
+        assert!(Settings::parse("settings:\n  ref_cache: soon").is_err());
+
+        let s = Settings::parse("settings:\n  retries: 0").unwrap();
+        assert_eq!(s.retries, 0);
+        assert!(Settings::parse("settings:\n  retries: -1").is_err());
+    }
+
+    #[test]
+    fn settings_colors() {
+        let s =
+            Settings::parse("settings:\n  colors:\n    success: dark_green\n    opt: '#ff8000'");
+        let colors = s.unwrap().colors;
+        assert_eq!(colors.success, Color::DarkGreen);
+        assert_eq!(
+            colors.opt,
+            Color::Rgb {
+                r: 255,
+                g: 128,
+                b: 0
+            }
+        );
+        assert_eq!(colors.failure, Color::Red);
+        assert!(Settings::parse("settings:\n  colors:\n    failure: '#12'").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::context::Context;
+use crate::git::{self, GitRepo};
+use crate::package::{self, Package};
+use crate::stats::{self, Event, Phase};
+use crate::{Error, Result};
+
+use std::fs;
+
+/// Where the files of a package come from and how they get into the store.
+/// A new kind of remote is a new implementation added to `SOURCES`, install
+/// and update only go through `of`.
+pub trait Source: Sync {
+    /// Whether `pack` comes from this source
+    fn handles(&self, pack: &Package) -> bool;
+
+    /// Fetch `pack` into its store path, which may hold an earlier fetch
+    fn install(&self, ctx: &Context, pack: &Package) -> Result<()>;
+
+    /// Bring the store path of an installed `pack` up to date
+    fn update(&self, ctx: &Context, pack: &Package) -> Result<()>;
+
+    /// Fail unless the store path holds a usable fetch of `pack`, checked
+    /// before it is linked into pack/
+    fn verify(&self, ctx: &Context, pack: &Package) -> Result<()>;
+
+    /// What installing or updating `pack` would do, for `--dry-run`
+    fn plan(&self, ctx: &Context, pack: &Package, update: bool) -> String;
+
+    /// Whether the source has commits to compare with its remote, for
+    /// `pac outdated`, `pac stale` and the lockfile
+    fn versioned(&self) -> bool {
+        false
+    }
+}
+
+/// A directory on this machine, copied since it is no repo to clone
+struct Local;
+
+impl Source for Local {
+    fn handles(&self, pack: &Package) -> bool {
+        pack.is_copied()
+    }
+
+    fn install(&self, ctx: &Context, pack: &Package) -> Result<()> {
+        pack.copy_local(ctx)
+    }
+
+    fn update(&self, ctx: &Context, pack: &Package) -> Result<()> {
+        pack.copy_local(ctx)
+    }
+
+    fn verify(&self, ctx: &Context, pack: &Package) -> Result<()> {
+        if pack.store_path(ctx).is_dir() {
+            return Ok(());
+        }
+        Err(Error::store(format!("no copy of {}", pack.remote)))
+    }
+
+    fn plan(&self, _ctx: &Context, pack: &Package, _update: bool) -> String {
+        format!("copy {} from {}", pack.idname, pack.remote)
+    }
+}
+
+/// A `.tar.gz` or `.zip` archive, downloaded again to see if it changed
+struct Archive;
+
+impl Source for Archive {
+    fn handles(&self, pack: &Package) -> bool {
+        pack.is_archive()
+    }
+
+    fn install(&self, ctx: &Context, pack: &Package) -> Result<()> {
+        pack.fetch_archive(ctx).map(|_| ())
+    }
+
+    fn update(&self, ctx: &Context, pack: &Package) -> Result<()> {
+        pack.fetch_archive(ctx).map(|_| ())
+    }
+
+    fn verify(&self, ctx: &Context, pack: &Package) -> Result<()> {
+        if pack.store_path(ctx).join(package::ARCHIVE_STAMP).is_file() {
+            return Ok(());
+        }
+        Err(Error::store(format!(
+            "{} is not fully unpacked",
+            pack.remote
+        )))
+    }
+
+    fn plan(&self, _ctx: &Context, pack: &Package, _update: bool) -> String {
+        format!("download {} from {}", pack.idname, pack.remote)
+    }
+}
+
+/// A git repo, cloned once into the store and shared by the packages with
+/// the same remote
+struct Git;
+
+impl Source for Git {
+    fn handles(&self, _pack: &Package) -> bool {
+        true
+    }
+
+    fn install(&self, ctx: &Context, pack: &Package) -> Result<()> {
+        let store = pack.store_path(ctx);
+        if store.is_dir() {
+            // the same remote may already be cloned for another package
+            if !git::is_partial_clone(&store) {
+                return Ok(());
+            }
+            log::info!("remove the partial clone {}", store.display());
+            fs::remove_dir_all(&store)?;
+        }
+        let times = pack.git_clone(ctx)?;
+        stats::record(&pack.idname, Phase::Clone, times.fetch);
+        stats::record(&pack.idname, Phase::Checkout, times.checkout);
+        Ok(())
+    }
+
+    fn update(&self, ctx: &Context, pack: &Package) -> Result<()> {
+        let before = pack.git_head(ctx).ok();
+        let times = pack.git_pull(ctx)?;
+        stats::record(&pack.idname, Phase::Fetch, times.fetch);
+        stats::record(&pack.idname, Phase::Checkout, times.checkout);
+        if pack.git_head(ctx).ok() != before {
+            stats::count(&pack.idname, Event::Update);
+        }
+        Ok(())
+    }
+
+    fn verify(&self, ctx: &Context, pack: &Package) -> Result<()> {
+        pack.git_head(ctx).map(|_| ()).map_err(|e| {
+            Error::store(format!(
+                "{} is no clone pac can read ({}), remove it to clone again",
+                pack.store_path(ctx).display(),
+                e
+            ))
+        })
+    }
+
+    fn plan(&self, ctx: &Context, pack: &Package, update: bool) -> String {
+        let store = pack.store_path(ctx);
+        if update {
+            format!("pull {} from {}", pack.idname, pack.clone_url())
+        } else if store.is_dir() && !git::is_partial_clone(&store) {
+            format!("link {} to {}", pack.path(ctx).display(), store.display())
+        } else if store.is_dir() {
+            format!(
+                "clone {} again from {}, the clone in {} is incomplete",
+                pack.idname,
+                pack.clone_url(),
+                store.display()
+            )
+        } else {
+            format!(
+                "clone {} from {} into {}",
+                pack.idname,
+                pack.clone_url(),
+                store.display()
+            )
+        }
+    }
+
+    fn versioned(&self) -> bool {
+        true
+    }
+}
+
+/// Tried in order, git takes whatever the others don't
+static SOURCES: [&dyn Source; 3] = [&Local, &Archive, &Git];
+
+/// The source `pack` is fetched from
+pub fn of(pack: &Package) -> &'static dyn Source {
+    SOURCES
+        .iter()
+        .copied()
+        .find(|s| s.handles(pack))
+        .unwrap_or(&Git)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_of() {
+        let git = Package::new("a", "https://github.com/me/a", "default", false);
+        let archive = Package::new("b", "https://example.com/b-1.0.tar.gz", "default", false);
+        assert!(of(&git).versioned());
+        assert!(!of(&archive).versioned());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::auth;
+use crate::context::Context;
+use crate::notify;
+use crate::package::Package;
+use crate::utils;
+use crate::{Error, Result};
+
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Kept free on top of what a run is estimated to need
+const MARGIN: u64 = 64 << 20;
+/// Assumed for repos the GitHub API gives no size for
+const UNKNOWN_SIZE: u64 = 2 << 20;
+/// GitHub API lookups running at once
+const LOOKUPS: usize = 8;
+/// Repos asked for in one GraphQL query
+const BATCH: usize = 50;
+
+/// Bytes available to unprivileged users on the filesystem of `path`, or of
+/// its closest existing parent
+#[cfg(unix)]
+pub fn free(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let c_path = CString::new(existing.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path is a valid C string and stat is only read on success
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Not found out elsewhere, the checks are then skipped
+#[cfg(not(unix))]
+pub fn free(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// `owner/name` of a github remote
+fn github_path(remote: &str) -> Option<&str> {
+    let path = remote.split("github.com/").nth(1)?.trim_end_matches(".git");
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+    };
+    match path.split_once('/') {
+        Some((owner, name)) if valid(owner) && valid(name) => Some(path),
+        _ => None,
+    }
+}
+
+/// Size of a github repo as its API reports it, `None` for other hosts or
+/// when the API can't be reached
+fn repo_size(remote: &str) -> Option<u64> {
+    let path = github_path(remote)?;
+    let url = format!("https://api.github.com/repos/{}", path);
+    let body = utils::download_within(&url, Duration::from_secs(5)).ok()?;
+    let kb = size_field(&String::from_utf8_lossy(&body))?;
+    Some(kb << 10)
+}
+
+/// The top level `"size": N` of a repo's API response, in KB
+fn size_field(json: &str) -> Option<u64> {
+    let at = json.find("\"size\":")?;
+    let digits: String = json[at + 7..]
+        .trim_start()
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+/// The GraphQL request for the sizes of the github repos at `paths`, with
+/// the aliases `r0`, `r1`... in their order
+fn size_query(paths: &[&str]) -> String {
+    let fields: Vec<String> = paths
+        .iter()
+        .enumerate()
+        .filter_map(|(i, path)| {
+            let (owner, name) = path.split_once('/')?;
+            Some(format!(
+                "r{}: repository(owner: \\\"{}\\\", name: \\\"{}\\\") {{ diskUsage }}",
+                i, owner, name
+            ))
+        })
+        .collect();
+    format!("{{\"query\": \"query {{ {} }}\"}}", fields.join(" "))
+}
+
+/// The `diskUsage` of alias `r<i>` in a GraphQL response, in KB. Missing
+/// repos are `null`.
+fn batch_field(json: &str, i: usize) -> Option<u64> {
+    let alias = format!("\"r{}\":", i);
+    let at = json.find(&alias)?;
+    let rest = json[at + alias.len()..].trim_start().strip_prefix('{')?;
+    let end = rest.find('}')?;
+    let field = rest[..end].split("\"diskUsage\":").nth(1)?;
+    let digits: String = field
+        .trim_start()
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+/// Sizes of the github repos among `remotes` in GraphQL queries of `BATCH`
+/// repos, where `repo_size` asks for one at a time. GraphQL needs a token,
+/// `None` without one or when a query fails.
+fn batch_sizes(remotes: &[String]) -> Option<Vec<Option<u64>>> {
+    auth::token("github.com")?;
+    let paths: Vec<Option<&str>> = remotes.iter().map(|r| github_path(r)).collect();
+    let mut sizes = vec![None; remotes.len()];
+    let github: Vec<(usize, &str)> = paths
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| p.map(|p| (i, p)))
+        .collect();
+    for chunk in github.chunks(BATCH) {
+        let batch: Vec<&str> = chunk.iter().map(|(_, p)| *p).collect();
+        let body = utils::post_within(
+            "https://api.github.com/graphql",
+            &size_query(&batch),
+            Duration::from_secs(10),
+        )
+        .ok()?;
+        let json = String::from_utf8_lossy(&body);
+        for (n, (i, _)) in chunk.iter().enumerate() {
+            sizes[*i] = batch_field(&json, n).map(|kb| kb << 10);
+        }
+    }
+    Some(sizes)
+}
+
+/// The bytes cloning `packs` takes, their packed history and a checkout of
+/// about the same size
+fn estimate(packs: &[&Package]) -> u64 {
+    let remotes: Vec<String> = packs.iter().map(|p| p.clone_url()).collect();
+    if let Some(sizes) = batch_sizes(&remotes) {
+        return sizes.iter().map(|s| s.unwrap_or(UNKNOWN_SIZE) * 2).sum();
+    }
+    let sizes: Vec<Option<u64>> = remotes
+        .chunks(LOOKUPS)
+        .flat_map(|chunk| {
+            thread::scope(|s| {
+                let lookups: Vec<_> = chunk
+                    .iter()
+                    .map(|r| s.spawn(move || repo_size(r)))
+                    .collect();
+                lookups
+                    .into_iter()
+                    .map(|l| l.join().ok().flatten())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    sizes.iter().map(|s| s.unwrap_or(UNKNOWN_SIZE) * 2).sum()
+}
+
+/// Fail before cloning `packs` into the store if the filesystem can't take
+/// them. Nothing is checked when the free space can't be found out.
+pub fn check_install(ctx: &Context, packs: &[&Package]) -> Result<()> {
+    if packs.is_empty() {
+        return Ok(());
+    }
+    let available = match free(&ctx.store_dir) {
+        Some(a) => a,
+        None => return Ok(()),
+    };
+    let needed = estimate(packs) + MARGIN;
+    if needed <= available {
+        return Ok(());
+    }
+    Err(Error::space(format!(
+        "installing {} needs about {} but {} has only {} free",
+        notify::packages(packs.len()),
+        human(needed),
+        ctx.store_dir.display(),
+        human(available)
+    )))
+}
+
+/// Fail before updating if not even the margin is free, an update fetches
+/// too little to be worth estimating
+pub fn check_update(ctx: &Context) -> Result<()> {
+    match free(&ctx.store_dir) {
+        Some(available) if available < MARGIN => Err(Error::space(format!(
+            "{} has only {} free",
+            ctx.store_dir.display(),
+            human(available)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Bytes the files under `path` take, symlinks are not followed
+pub fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.path().symlink_metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+pub fn human(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1} GB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{} MB", b >> 20),
+        b => format!("{} KB", b >> 10),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn space_estimates() {
+        let json = r#"{"id": 1, "name": "fzf", "size": 12345, "stargazers_count": 9}"#;
+        assert_eq!(size_field(json), Some(12345));
+        assert_eq!(size_field("{}"), None);
+        assert_eq!(
+            github_path("https://github.com/junegunn/fzf.git"),
+            Some("junegunn/fzf")
+        );
+        assert_eq!(github_path("https://github.com/a\"b/c"), None);
+        assert_eq!(
+            size_query(&["me/fzf"]),
+            r#"{"query": "query { r0: repository(owner: \"me\", name: \"fzf\") { diskUsage } }"}"#
+        );
+        assert!(
+            size_query(&["a/b", "tpope/vim"]).contains(" } r1: repository(owner: \\\"tpope\\\"")
+        );
+        let json = r#"{"data":{"r0":{"diskUsage":4321},"r1":null,"r10":{"diskUsage":7}}}"#;
+        assert_eq!(batch_field(json, 0), Some(4321));
+        assert_eq!(batch_field(json, 1), None);
+        assert_eq!(batch_field(json, 10), Some(7));
+        assert_eq!(human(3 << 20), "3 MB");
+        assert_eq!(human(3 << 29), "1.5 GB");
+        assert!(free(&env::temp_dir().join("pac-missing/dir")).is_some());
+        assert_eq!(dir_size(&env::temp_dir().join("pac-missing/dir")), 0);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::context::Context;
+use crate::utils;
+use crate::Result;
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Filename of the generated autoload script in `<root>/autoload`
+const PAC_AUTOLOAD_FILENAME: &str = "pac.vim";
+
+/// What vim's `pac#status()` reports, kept as `key=value` lines so the
+/// autoload script can read it without a yaml parser
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SyncState {
+    /// Packages `pac outdated` found updates for
+    pub updates: usize,
+    /// Seconds since the epoch of the last `pac update` of all packages
+    pub synced: u64,
+    /// Seconds since the epoch of the last `pac outdated` of all packages
+    pub checked: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl SyncState {
+    pub fn load(ctx: &Context) -> SyncState {
+        let data = fs::read_to_string(ctx.sync_state_file()).unwrap_or_default();
+        Self::parse(&data)
+    }
+
+    /// Unknown keys and broken lines are skipped
+    fn parse(data: &str) -> SyncState {
+        let mut state = SyncState::default();
+        for line in data.lines() {
+            let (key, val) = match line.split_once('=') {
+                Some(kv) => kv,
+                None => continue,
+            };
+            match key {
+                "updates" => state.updates = val.parse().unwrap_or(0),
+                "synced" => state.synced = val.parse().unwrap_or(0),
+                "checked" => state.checked = val.parse().unwrap_or(0),
+                _ => (),
+            }
+        }
+        state
+    }
+
+    fn save(&self, ctx: &Context) -> Result<()> {
+        fs::create_dir_all(&ctx.state_dir)?;
+        let data = format!(
+            "updates={}\nsynced={}\nchecked={}\n",
+            self.updates, self.synced, self.checked
+        );
+        fs::write(ctx.sync_state_file(), data)?;
+        Ok(())
+    }
+}
+
+/// `pac outdated` looked at every package and found `updates` behind
+pub fn record_check(ctx: &Context, updates: usize) -> Result<()> {
+    let mut state = SyncState::load(ctx);
+    state.updates = updates;
+    state.checked = now();
+    state.save(ctx)
+}
+
+/// `updated` packages were synced, all of them if `all` is set
+pub fn record_sync(ctx: &Context, updated: usize, all: bool) -> Result<()> {
+    let mut state = SyncState::load(ctx);
+    if all {
+        state.updates = 0;
+        state.synced = now();
+    } else {
+        state.updates = state.updates.saturating_sub(updated);
+    }
+    state.save(ctx)
+}
+
+/// Write `autoload/pac.vim` with `pac#state()` and `pac#status()` reading
+/// the state file. The file is only read again once it changed.
+pub fn update_autoload(ctx: &Context) -> Result<()> {
+    let path = ctx.sync_state_file().display().to_string();
+    let script = format!(
+        "\" Generated by pac. DO NOT EDIT!
+
+let s:file = '{file}'
+let s:time = -2
+let s:state = {{}}
+
+\" {{'updates': N, 'synced': epoch, 'checked': epoch}}, 0 for unknown
+function! pac#state() abort
+    let time = getftime(s:file)
+    if time != s:time
+        let s:time = time
+        let s:state = {{'updates': 0, 'synced': 0, 'checked': 0}}
+        for line in time < 0 ? [] : readfile(s:file)
+            let kv = split(line, '=')
+            if len(kv) == 2
+                let s:state[kv[0]] = str2nr(kv[1])
+            endif
+        endfor
+    endif
+    return s:state
+endfunction
+
+\" Text for a statusline, empty without pending updates
+function! pac#status() abort
+    let n = pac#state().updates
+    if n == 0
+        return ''
+    endif
+    return n == 1 ? '1 plugin update available' : n . ' plugin updates available'
+endfunction
+",
+        file = path.replace('\'', "''")
+    );
+    fs::create_dir_all(&ctx.autoload_dir)?;
+    utils::write_atomic(
+        ctx.autoload_dir.join(PAC_AUTOLOAD_FILENAME),
+        script.as_bytes(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_parse() {
+        let state = SyncState::parse("updates=3\nsynced=12\nbroken\nother=1\n");
+        let expected = SyncState {
+            updates: 3,
+            synced: 12,
+            checked: 0,
+        };
+        assert_eq!(state, expected);
+    }
+}
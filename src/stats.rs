@@ -0,0 +1,370 @@
+use crate::context::Context;
+use crate::Result;
+
+use lazy_static::lazy_static;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+/// Runs kept in the stats file, older ones are dropped
+const MAX_RUNS: usize = 20;
+
+const PAC_STATS_HEADER: &[u8] = b"# vim: ft=yaml
+#
+# Generated by pac, timings of the latest install and update runs.
+
+";
+
+const PAC_COUNTS_HEADER: &[u8] = b"# vim: ft=yaml
+#
+# Generated by pac, how often packages were installed, updated and failed.
+# Only kept on this machine.
+
+";
+
+/// Timed step of syncing a package
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// Fetching a new clone
+    Clone,
+    /// Fetching into an existing clone
+    Fetch,
+    Checkout,
+    Build,
+    /// Generating the helptags of every package, once a run and recorded
+    /// for `RUN`
+    Helptags,
+}
+
+/// What the samples of a whole run are recorded for instead of a package
+pub const RUN: &str = "(run)";
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::Clone,
+        Phase::Fetch,
+        Phase::Checkout,
+        Phase::Build,
+        Phase::Helptags,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Clone => "clone",
+            Phase::Fetch => "fetch",
+            Phase::Checkout => "checkout",
+            Phase::Build => "build",
+            Phase::Helptags => "helptags",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Phase> {
+        Phase::ALL.iter().copied().find(|p| p.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub idname: String,
+    pub phase: Phase,
+    pub duration: Duration,
+}
+
+/// Samples of a single `pac install` or `pac update`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub command: String,
+    /// Seconds since the epoch when the run was saved
+    pub time: u64,
+    pub samples: Vec<Sample>,
+}
+
+/// What happened to a package, counted for as long as the counts file is kept
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    /// Cloned, copied or downloaded into place
+    Install,
+    /// An update which changed the checkout
+    Update,
+    /// A failed install, update or build
+    Failure,
+}
+
+/// Events of a package since `Counts::since`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tally {
+    pub idname: String,
+    pub installs: u64,
+    pub updates: u64,
+    pub failures: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Counts {
+    /// Seconds since the epoch when the first event was counted
+    pub since: u64,
+    pub packages: Vec<Tally>,
+}
+
+lazy_static! {
+    static ref SAMPLES: Mutex<Vec<Sample>> = Mutex::new(vec![]);
+    static ref EVENTS: Mutex<Vec<(String, Event)>> = Mutex::new(vec![]);
+}
+
+/// Note how long `phase` took for a package in the current run
+pub fn record(idname: &str, phase: Phase, duration: Duration) {
+    SAMPLES.lock().unwrap().push(Sample {
+        idname: idname.to_string(),
+        phase,
+        duration,
+    });
+}
+
+/// Count `event` for a package in the current run
+pub fn count(idname: &str, event: Event) {
+    EVENTS.lock().unwrap().push((idname.to_string(), event));
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Append the samples recorded so far as a run of `command`, and add the
+/// events counted to the counts
+pub fn save(ctx: &Context, command: &str) -> Result<()> {
+    save_counts(ctx)?;
+    let samples: Vec<Sample> = SAMPLES.lock().unwrap().drain(..).collect();
+    if samples.is_empty() {
+        return Ok(());
+    }
+    let time = now();
+    let mut runs = load(ctx)?;
+    runs.push(Run {
+        command: command.to_string(),
+        time,
+        samples,
+    });
+    let skip = runs.len().saturating_sub(MAX_RUNS);
+
+    let doc: Vec<Yaml> = runs.into_iter().skip(skip).map(run_into_yaml).collect();
+    let mut out = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.dump(&Yaml::Array(doc))?;
+    }
+    fs::create_dir_all(&ctx.state_dir)?;
+    let mut f = File::create(ctx.stats_file())?;
+    f.write_all(PAC_STATS_HEADER)?;
+    f.write_all(out.as_bytes())?;
+    Ok(())
+}
+
+fn save_counts(ctx: &Context) -> Result<()> {
+    let events: Vec<(String, Event)> = EVENTS.lock().unwrap().drain(..).collect();
+    if events.is_empty() {
+        return Ok(());
+    }
+    let mut counts = load_counts(ctx)?;
+    if counts.packages.is_empty() {
+        counts.since = now();
+    }
+    counts.add(&events);
+
+    let mut out = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.dump(&counts.into_yaml())?;
+    }
+    fs::create_dir_all(&ctx.state_dir)?;
+    let mut f = File::create(ctx.counts_file())?;
+    f.write_all(PAC_COUNTS_HEADER)?;
+    f.write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// Counts saved so far, empty if there are none
+pub fn load_counts(ctx: &Context) -> Result<Counts> {
+    let path = ctx.counts_file();
+    if !path.is_file() {
+        return Ok(Counts::default());
+    }
+    let data = fs::read_to_string(&path)?;
+    let docs = YamlLoader::load_from_str(&data)?;
+    Ok(docs.first().map(Counts::from_yaml).unwrap_or_default())
+}
+
+impl Counts {
+    fn add(&mut self, events: &[(String, Event)]) {
+        for (idname, event) in events {
+            let index = match self.packages.iter().position(|t| t.idname == *idname) {
+                Some(i) => i,
+                None => {
+                    self.packages.push(Tally {
+                        idname: idname.clone(),
+                        ..Tally::default()
+                    });
+                    self.packages.len() - 1
+                }
+            };
+            let tally = &mut self.packages[index];
+            match event {
+                Event::Install => tally.installs += 1,
+                Event::Update => tally.updates += 1,
+                Event::Failure => tally.failures += 1,
+            }
+        }
+    }
+
+    fn into_yaml(self) -> Yaml {
+        let packages = self
+            .packages
+            .into_iter()
+            .map(|t| {
+                let mut doc = Hash::new();
+                doc.insert(Yaml::from_str("package"), Yaml::String(t.idname));
+                doc.insert(Yaml::from_str("installs"), Yaml::Integer(t.installs as i64));
+                doc.insert(Yaml::from_str("updates"), Yaml::Integer(t.updates as i64));
+                doc.insert(Yaml::from_str("failures"), Yaml::Integer(t.failures as i64));
+                Yaml::Hash(doc)
+            })
+            .collect();
+        let mut doc = Hash::new();
+        doc.insert(Yaml::from_str("since"), Yaml::Integer(self.since as i64));
+        doc.insert(Yaml::from_str("packages"), Yaml::Array(packages));
+        Yaml::Hash(doc)
+    }
+
+    /// Entries which can't be parsed are skipped like those of the runs
+    fn from_yaml(doc: &Yaml) -> Counts {
+        let number = |t: &Yaml, key: &str| t[key].as_i64().unwrap_or(0).max(0) as u64;
+        let packages = doc["packages"]
+            .as_vec()
+            .map(|v| {
+                v.iter()
+                    .filter_map(|t| {
+                        Some(Tally {
+                            idname: t["package"].as_str()?.to_string(),
+                            installs: number(t, "installs"),
+                            updates: number(t, "updates"),
+                            failures: number(t, "failures"),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Counts {
+            since: number(doc, "since"),
+            packages,
+        }
+    }
+}
+
+/// Saved runs, oldest first
+pub fn load(ctx: &Context) -> Result<Vec<Run>> {
+    let path = ctx.stats_file();
+    if !path.is_file() {
+        return Ok(vec![]);
+    }
+    let mut data = String::new();
+    File::open(&path)?.read_to_string(&mut data)?;
+    let docs = YamlLoader::load_from_str(&data)?;
+    let runs = match docs.first().and_then(|d| d.as_vec()) {
+        Some(v) => v.iter().filter_map(run_from_yaml).collect(),
+        None => vec![],
+    };
+    Ok(runs)
+}
+
+fn run_into_yaml(run: Run) -> Yaml {
+    let samples = run
+        .samples
+        .into_iter()
+        .map(|s| {
+            let mut doc = Hash::new();
+            doc.insert(Yaml::from_str("package"), Yaml::String(s.idname));
+            doc.insert(Yaml::from_str("phase"), Yaml::from_str(s.phase.as_str()));
+            let ms = s.duration.as_millis() as i64;
+            doc.insert(Yaml::from_str("ms"), Yaml::Integer(ms));
+            Yaml::Hash(doc)
+        })
+        .collect();
+    let mut doc = Hash::new();
+    doc.insert(Yaml::from_str("command"), Yaml::String(run.command));
+    doc.insert(Yaml::from_str("time"), Yaml::Integer(run.time as i64));
+    doc.insert(Yaml::from_str("samples"), Yaml::Array(samples));
+    Yaml::Hash(doc)
+}
+
+/// Entries which can't be parsed are skipped, the file is only a cache
+fn run_from_yaml(doc: &Yaml) -> Option<Run> {
+    let samples = doc["samples"]
+        .as_vec()?
+        .iter()
+        .filter_map(|s| {
+            Some(Sample {
+                idname: s["package"].as_str()?.to_string(),
+                phase: Phase::parse(s["phase"].as_str()?)?,
+                duration: Duration::from_millis(s["ms"].as_i64()? as u64),
+            })
+        })
+        .collect();
+    Some(Run {
+        command: doc["command"].as_str()?.to_string(),
+        time: doc["time"].as_i64()? as u64,
+        samples,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_yaml_roundtrip() {
+        let run = Run {
+            command: "update".to_string(),
+            time: 1_600_000_000,
+            samples: vec![
+                Sample {
+                    idname: "tpope/vim-fugitive".to_string(),
+                    phase: Phase::Build,
+                    duration: Duration::from_millis(1234),
+                },
+                Sample {
+                    idname: RUN.to_string(),
+                    phase: Phase::Helptags,
+                    duration: Duration::from_millis(56),
+                },
+            ],
+        };
+        assert_eq!(run_from_yaml(&run_into_yaml(run.clone())), Some(run));
+
+        let mut counts = Counts {
+            since: 1_600_000_000,
+            packages: vec![],
+        };
+        counts.add(&[
+            ("me/a".to_string(), Event::Install),
+            ("me/b".to_string(), Event::Failure),
+            ("me/a".to_string(), Event::Update),
+            ("me/a".to_string(), Event::Update),
+        ]);
+        assert_eq!(
+            counts.packages[0],
+            Tally {
+                idname: "me/a".to_string(),
+                installs: 1,
+                updates: 2,
+                failures: 0,
+            }
+        );
+        assert_eq!(Counts::from_yaml(&counts.clone().into_yaml()), counts);
+    }
+}
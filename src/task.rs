@@ -1,101 +1,305 @@
-use crate::echo;
+use crate::context::Context;
+use crate::dry_run;
+use crate::echo::{self, Status, Ui};
 use crate::package::Package;
+use crate::source;
+use crate::stats::{self, Event, Phase};
 use crate::utils::Spinner;
 use crate::Error;
 use crate::Result;
 
-use crossbeam_channel::{bounded, select, Receiver};
+use crossbeam_channel::{bounded, select, Receiver, TryRecvError};
 use crossbeam_utils::sync::WaitGroup;
+use crossterm::terminal;
 use signal_hook::iterator::Signals;
+use std::cmp::Reverse;
 use std::fs;
 use std::io;
 use std::process;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use termion::{color, terminal_size};
+use std::time;
+
+/// Times a clone or pull failing with a git or network error is tried again
+/// when neither `--retries` nor the `retries` setting say otherwise
+pub const DEFAULT_RETRIES: u32 = 2;
+
+static RETRIES: AtomicU32 = AtomicU32::new(DEFAULT_RETRIES);
+
+pub fn set_retries(n: u32) {
+    RETRIES.store(n, Ordering::Relaxed);
+}
+
+const MAX_BACKOFF: time::Duration = time::Duration::from_secs(30);
+
+/// Wait before the retry after `failures` failed tries: 1s, 2s, 4s... up
+/// to `MAX_BACKOFF`
+fn backoff(failures: u32) -> time::Duration {
+    time::Duration::from_secs(1 << failures.saturating_sub(1).min(5)).min(MAX_BACKOFF)
+}
 
 pub enum TaskType {
     Install,
     Update,
+    /// Run an arbitrary command, no building or helptags afterwards
+    Exec,
+    /// Only query the remotes, neither building nor helptags
+    Check,
 }
 
 pub struct TaskManager {
+    ctx: Arc<Context>,
     task_type: TaskType,
-    packs: Vec<Package>,
+    /// Packages sharing a remote, and so a clone in the store, are grouped
+    /// and synced one after another by the same worker
+    packs: Vec<Vec<Package>>,
     thread_num: usize,
+    build_threads: usize,
+}
+
+/// A synced package waiting for its build commands
+struct Build {
+    id: usize,
+    spinner: Spinner,
+    pack: Package,
+    /// Installed by this run, its `post_install` commands run after the build
+    installed: bool,
 }
 
 impl TaskManager {
-    pub fn new(task_type: TaskType, thread_num: usize) -> TaskManager {
+    pub fn new(ctx: &Context, task_type: TaskType, thread_num: usize) -> TaskManager {
         TaskManager {
+            ctx: Arc::new(ctx.clone()),
             task_type,
             packs: Vec::new(),
             thread_num,
+            build_threads: num_cpus::get(),
         }
     }
 
+    /// Queue `pack`, a package already queued is only synced once
     pub fn add(&mut self, pack: Package) {
-        self.packs.push(pack);
+        if self.packs.iter().flatten().any(|p| p.idname == pack.idname) {
+            log::info!("{} is queued already", pack.idname);
+            return;
+        }
+        let ctx = &self.ctx;
+        let store_path = pack.store_path(ctx);
+        match self
+            .packs
+            .iter_mut()
+            .find(|g| g[0].store_path(ctx) == store_path)
+        {
+            Some(group) => group.push(pack),
+            None => self.packs.push(vec![pack]),
+        }
     }
 
-    /// returns true on success otherwise false
-    fn update<F>(pack: &Package, line: u16, func: F) -> bool
+    /// The queued groups, highest priority first so that an interrupted run
+    /// still leaves those synced. Otherwise in the order they were added.
+    fn by_priority(&self) -> Vec<Vec<Package>> {
+        let mut groups = self.packs.clone();
+        for group in groups.iter_mut() {
+            group.sort_by_key(|p| Reverse(p.priority));
+        }
+        groups.sort_by_key(|g| Reverse(g[0].priority));
+        groups
+    }
+
+    /// Run build commands on this many packages at once, all of the
+    /// packages are synced before the first build starts
+    pub fn build_threads(&mut self, n: usize) {
+        self.build_threads = n;
+    }
+
+    /// Run `func` on `pack`. Returns whether it succeeded, and the package
+    /// if its build or post install commands are still to run.
+    fn sync<F>(
+        ctx: &Context,
+        pack: &Package,
+        build: bool,
+        installing: bool,
+        func: &F,
+    ) -> (bool, Option<Build>)
     where
-        F: Fn(&Package) -> (Result<()>, bool),
+        F: Fn(&Context, &Package) -> (Result<()>, bool),
     {
-        let msg = format!(" [{}]", &pack.idname);
-        let pos = msg.len() as u16;
-        echo::message(line, 0, &format!("    {} syncing", &msg));
-
-        const MSG_MARGIN: u16 = 5;
-        const SIGN_MARGIN: u16 = 3;
-
-        macro_rules! print_err {
-            ($err:expr) => {
-                let msg = format!("{}", $err);
-                echo::character(line, SIGN_MARGIN, '✗', color::Red);
-                echo::inline_message(line, MSG_MARGIN + pos, &msg);
-            };
+        let id = echo::task(&pack.idname);
+        echo::message(id, "syncing");
+
+        let spinner = Spinner::spin(id);
+        let mut res = func(ctx, pack);
+        // clones and pulls are tried again, they may fail on a network hiccup
+        let mut failures = 1;
+        while build && failures <= RETRIES.load(Ordering::Relaxed) {
+            if !matches!(res.0, Err(Error::Git(_)) | Err(Error::Network(_))) {
+                break;
+            }
+            let wait = backoff(failures);
+            log::info!("retry {} in {:?}: {:?}", pack.idname, wait, res.0);
+            echo::message(id, &format!("retrying in {}s", wait.as_secs()));
+            thread::sleep(wait);
+            echo::message(id, "syncing");
+            res = func(ctx, pack);
+            failures += 1;
+        }
+        if let (Err(e), status) = res {
+            echo::finish(id, Status::Failure, spinner.stop(), &format!("{}", e));
+            return (status, None);
+        }
+        // the plugin's own manifest may provide build commands
+        let mut pack = pack.clone();
+        if let Ok(Some(manifest)) = pack.manifest(ctx) {
+            pack.merge_manifest(&manifest);
+        }
+        // packages already installed fail `func`, the others were just installed
+        let installed = installing && !pack.post_install.is_empty();
+        if build && (installed || !pack.build_commands.is_empty()) {
+            echo::message(id, "waiting to build");
+            return (
+                true,
+                Some(Build {
+                    id,
+                    spinner,
+                    pack,
+                    installed,
+                }),
+            );
+        }
+        echo::finish(id, Status::Success, spinner.stop(), "done");
+        (true, None)
+    }
+
+    /// A failed build is shown but the package stays, it is installed
+    fn build(ctx: &Context, job: Build) {
+        let Build {
+            id,
+            spinner,
+            pack,
+            installed,
+        } = job;
+        echo::message(id, "building");
+        let start = time::Instant::now();
+        let res = pack.try_build(ctx);
+        stats::record(&pack.idname, Phase::Build, start.elapsed());
+        let res = match res {
+            Ok(()) if installed => {
+                echo::message(id, "running post install");
+                pack.try_post_install(ctx)
+            }
+            res => res,
+        };
+        match res.map_err(|e| Error::build(format!("{}", e))) {
+            Err(e) => {
+                stats::count(&pack.idname, Event::Failure);
+                echo::finish(id, Status::Failure, spinner.stop(), &format!("{}", e))
+            }
+            Ok(()) => echo::finish(id, Status::Success, spinner.stop(), "done"),
         }
+    }
 
-        let mut successful = true;
-        let spinner = Spinner::spin(line, SIGN_MARGIN);
-        if let (Err(e), status) = func(pack) {
-            spinner.stop();
-            print_err!(e);
-            successful = status;
-        } else {
-            if pack.build_command.is_some() {
-                echo::inline_message(line, MSG_MARGIN + pos, "building");
-                if let Err(e) = pack.try_build().map_err(|e| Error::build(format!("{}", e))) {
-                    print_err!(e);
+    /// The second stage, builds of the synced packages on `build_threads`
+    fn run_builds(
+        &self,
+        jobs: Vec<Build>,
+        pending: &Arc<Mutex<Vec<Package>>>,
+        quit_notifier: &Receiver<()>,
+    ) {
+        let wg = WaitGroup::new();
+        let queue = Arc::new(Mutex::new(jobs.into_iter().rev().collect::<Vec<_>>()));
+        let workers = self.build_threads.min(queue.lock().unwrap().len());
+        for _ in 0..workers {
+            let queue = queue.clone();
+            let pending = pending.clone();
+            let wg = wg.clone();
+            let quit_notifier = quit_notifier.clone();
+            let ctx = self.ctx.clone();
+            thread::spawn(move || {
+                let _wg = wg;
+                loop {
+                    let job = match queue.lock().unwrap().pop() {
+                        Some(job) => job,
+                        None => return,
+                    };
+                    let name = job.pack.idname.clone();
+                    let (wtx, wrx) = bounded(0);
+                    let ctx = ctx.clone();
+                    thread::spawn(move || {
+                        Self::build(&ctx, job);
+                        let _ = wtx.send(());
+                    });
+                    select! {
+                        recv(wrx) -> _ => {},
+                        recv(quit_notifier) -> _ => {
+                            log::info!("quit received {}", &name);
+                            return;
+                        }
+                    }
+                    pending.lock().unwrap().retain(|x| x.idname != name);
                 }
-            }
+            });
+        }
+        wg.wait();
+        // left over after an interrupt
+        for job in queue.lock().unwrap().drain(..) {
+            job.spinner.stop();
+        }
+    }
+
+    /// Print what installing or updating the packages would change, `--dry-run`
+    fn plan(&self) {
+        let ctx = &self.ctx;
+        for pack in self.by_priority().iter().flatten() {
+            let path = pack.path(ctx);
+            let installed = path.is_dir();
+            let change = match self.task_type {
+                TaskType::Install if installed => continue,
+                TaskType::Update if !installed => {
+                    format!("skip {}, it is not installed", pack.idname)
+                }
+                TaskType::Install => source::of(pack).plan(ctx, pack, false),
+                TaskType::Update => source::of(pack).plan(ctx, pack, true),
+                TaskType::Exec | TaskType::Check => continue,
+            };
+            dry_run::would(change);
 
-            spinner.stop();
-            if successful {
-                echo::character(line, SIGN_MARGIN, '✓', color::Green);
-                echo::inline_message(line, MSG_MARGIN + pos, "done");
+            let mut pack = pack.clone();
+            if let Ok(Some(manifest)) = pack.manifest(ctx) {
+                pack.merge_manifest(&manifest);
+            }
+            for cmd in &pack.build_commands {
+                dry_run::would(format!("build {} with `{}`", pack.idname, cmd));
+            }
+            if matches!(self.task_type, TaskType::Install) {
+                for cmd in &pack.post_install {
+                    dry_run::would(format!("set up {} with `{}`", pack.idname, cmd));
+                }
             }
         }
-        successful
     }
 
     pub fn run<F>(self, func: F) -> Vec<String>
     where
-        F: Fn(&Package) -> (Result<()>, bool) + Send + 'static + Copy,
+        F: Fn(&Context, &Package) -> (Result<()>, bool) + Send + 'static + Clone,
     {
         if self.packs.is_empty() {
             die!("No plugins to sync");
         }
+        if dry_run::enabled() && matches!(self.task_type, TaskType::Install | TaskType::Update) {
+            self.plan();
+            return vec![];
+        }
 
-        let y = match terminal_size() {
-            Err(e) => die!("Fail to get terminal size. {}", e),
-            Ok((_, y)) => y,
-        };
+        if echo::ui() == Ui::Tty {
+            let y = match terminal::size() {
+                Err(e) => die!("Fail to get terminal size. {}", e),
+                Ok((_, y)) => y,
+            };
 
-        if y <= 2 {
-            die!("Terminal size too small.");
+            if y <= 2 {
+                die!("Terminal size too small.");
+            }
         }
 
         let quit_notifier = match setup_signal() {
@@ -104,62 +308,104 @@ impl TaskManager {
         };
 
         let threads = self.thread_num;
+        let start = time::Instant::now();
+        let build = matches!(self.task_type, TaskType::Install | TaskType::Update);
+        let installing = matches!(self.task_type, TaskType::Install);
+        // "[idname]" padded to the longest name
+        let width = self
+            .packs
+            .iter()
+            .flatten()
+            .map(|p| echo::width(&p.idname) + 2)
+            .max()
+            .unwrap_or(0);
+        echo::start(echo::renderer());
+        let names = self
+            .packs
+            .iter()
+            .flatten()
+            .map(|p| p.idname.clone())
+            .collect();
+        let syncing = match self.task_type {
+            TaskType::Install => "cloning",
+            TaskType::Update => "fetching",
+            TaskType::Exec => "running",
+            TaskType::Check => "checking",
+        };
+        echo::begin(names, width, syncing);
 
         let wg = WaitGroup::new();
-        let (tx, rx) = bounded::<Option<Package>>(threads);
+        let (tx, rx) = bounded::<Option<Vec<Package>>>(threads);
 
         let failures = Arc::new(Mutex::new(vec![]));
         let pending = Arc::new(Mutex::new(vec![]));
+        let builds = Arc::new(Mutex::new(vec![]));
 
         for _ in 0..threads {
             let rx = rx.clone();
             let failures = failures.clone();
             let pending = pending.clone();
+            let builds = builds.clone();
             let wg = wg.clone();
             let quit_notifier = quit_notifier.clone();
+            let func = func.clone();
+            let ctx = self.ctx.clone();
             thread::spawn(move || {
-                while let Ok(Some(pack)) = rx.recv() {
-                    log::info!("pack {}", &pack.idname);
-                    let _wg = wg.clone();
-                    {
-                        let mut p = pending.lock().unwrap();
-                        log::info!("add to pending:{}", &pack.idname);
-                        p.push(pack.clone());
-                    }
+                while let Ok(Some(group)) = rx.recv() {
+                    for pack in group {
+                        log::info!("pack {}", &pack.idname);
+                        let _wg = wg.clone();
+                        {
+                            let mut p = pending.lock().unwrap();
+                            log::info!("add to pending:{}", &pack.idname);
+                            p.push(pack.clone());
+                        }
 
-                    let name = pack.idname.clone();
-                    let failures = failures.clone();
+                        let name = pack.idname.clone();
+                        let failures = failures.clone();
+                        let builds = builds.clone();
 
-                    let (wtx, wrx) = bounded(0);
-                    thread::spawn(move || {
-                        let index = echo::line();
-                        if !Self::update(&pack, index, func) {
-                            let mut f = failures.lock().unwrap();
-                            f.push(pack.idname);
-                        }
-                        let _ = wtx.send(());
-                    });
-                    select! {
-                        recv(wrx) -> _ => {},
-                        recv(quit_notifier) -> _ => {
-                            log::info!("quit received {}", &name);
-                            return;
+                        let (wtx, wrx) = bounded(0);
+                        let func = func.clone();
+                        let ctx = ctx.clone();
+                        thread::spawn(move || {
+                            let (successful, job) =
+                                Self::sync(&ctx, &pack, build, installing, &func);
+                            if !successful {
+                                if build {
+                                    stats::count(&pack.idname, Event::Failure);
+                                }
+                                let mut f = failures.lock().unwrap();
+                                f.push(pack.idname);
+                            }
+                            let queued = job.is_some();
+                            builds.lock().unwrap().extend(job);
+                            let _ = wtx.send(queued);
+                        });
+                        let queued = select! {
+                            recv(wrx) -> queued => queued.unwrap_or(false),
+                            recv(quit_notifier) -> _ => {
+                                log::info!("quit received {}", &name);
+                                return;
+                            }
+                        };
+                        // still pending until it is built
+                        if !queued {
+                            let mut p = pending.lock().unwrap();
+                            log::info!("remove from pending: {}", &name);
+                            p.retain(|x| x.idname != name);
                         }
                     }
-                    {
-                        let mut p = pending.lock().unwrap();
-                        log::info!("remove from pending: {}", &name);
-                        p.retain(|x| x.idname != name);
-                    }
                 }
             });
         }
-        if !self.packs.is_empty() {
+        let jsonl = echo::ui() == Ui::Jsonl;
+        if !self.packs.is_empty() && !jsonl {
             println!();
         }
 
-        for pack in self.packs.iter() {
-            let _ = tx.send(Some(pack.clone()));
+        for group in self.by_priority() {
+            let _ = tx.send(Some(group));
         }
 
         for _ in 0..threads {
@@ -167,18 +413,45 @@ impl TaskManager {
         }
         wg.wait();
 
-        if !self.packs.is_empty() {
+        let mut jobs = std::mem::take(&mut *builds.lock().unwrap());
+        jobs.sort_by_key(|j| Reverse(j.pack.priority));
+        if matches!(quit_notifier.try_recv(), Err(TryRecvError::Disconnected)) {
+            for job in jobs {
+                job.spinner.stop();
+            }
+        } else if !jobs.is_empty() {
+            self.run_builds(jobs, &pending, &quit_notifier);
+        }
+        echo::stop();
+
+        if !self.packs.is_empty() && !jsonl {
             println!();
+            println!("Finished in {:.1}s", start.elapsed().as_secs_f64());
         }
 
         log::info!("quit");
 
-        helptags();
+        if build {
+            let start = time::Instant::now();
+            helptags();
+            stats::record(stats::RUN, Phase::Helptags, start.elapsed());
+            let command = match self.task_type {
+                TaskType::Install => "install",
+                _ => "update",
+            };
+            if let Err(e) = stats::save(&self.ctx, command) {
+                log::info!("fail to save stats: {}", e);
+            }
+        }
 
         if let TaskType::Install = self.task_type {
             for p in pending.lock().unwrap().iter() {
-                log::info!("delete {:?}", p.path());
-                let _ = fs::remove_dir_all(p.path());
+                log::info!("delete {:?}", p.path(&self.ctx));
+                // not being linked yet means the clone itself was interrupted
+                if !p.is_linked(&self.ctx) {
+                    let _ = fs::remove_dir_all(p.store_path(&self.ctx));
+                }
+                let _ = p.unlink(&self.ctx);
             }
         }
 
@@ -187,40 +460,105 @@ impl TaskManager {
     }
 }
 
+/// Generate the helptags of every package. The editor is left to do it in
+/// the background, except with the simple and jsonl uis, which scripts,
+/// containers and frontends use and which should not exit before the tags
+/// are written. The time recorded for it is how long the run waited.
+#[allow(clippy::zombie_processes)]
 fn helptags() {
-    match process::Command::new("vim")
-        .arg("--not-a-term")
-        .arg("-c")
-        .arg("silent! helptags ALL")
-        .stdout(process::Stdio::null())
-        .spawn()
-    {
-        Ok(_) => (),
-        Err(e) => {
-            if let std::io::ErrorKind::NotFound = e.kind() {
-                process::Command::new("nvim")
-                    .arg("--headless")
-                    .arg("-c")
-                    .arg("silent! helptags ALL")
-                    .stdout(process::Stdio::null())
-                    .spawn()
-                    .expect("Error opening nvim");
-            } else {
-                panic!("Somthing happened when calling vim!")
+    let editors: [(&str, &[&str]); 2] = [
+        (
+            "vim",
+            &["--not-a-term", "-c", "silent! helptags ALL", "-c", "qa!"],
+        ),
+        (
+            "nvim",
+            &["--headless", "-c", "silent! helptags ALL", "-c", "qa!"],
+        ),
+    ];
+    for (editor, args) in editors.iter() {
+        let child = process::Command::new(editor)
+            .args(args.iter())
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .spawn();
+        match child {
+            Ok(mut child) => {
+                if echo::ui() != Ui::Tty {
+                    let _ = child.wait();
+                }
+                return;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                log::info!("fail to run {} for helptags: {}", editor, e);
+                return;
             }
         }
     }
+    log::info!("neither vim nor nvim found, no helptags generated");
 }
 
 fn setup_signal() -> io::Result<Receiver<()>> {
     let (s, r) = bounded(10);
-    let signals = Signals::new(&[signal_hook::SIGTERM, signal_hook::SIGINT])?;
+    let signals = Signals::new([signal_hook::SIGTERM, signal_hook::SIGINT])?;
 
     thread::spawn(move || {
-        for _ in signals.forever() {
-            drop(s);
-            return;
-        }
+        // dropping the sender disconnects every receiver on the first signal
+        let _ = signals.forever().next();
+        drop(s);
     });
     Ok(r)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_add_coalesces() {
+        let ctx = Context::with_paths("/tmp/vim", "/tmp/vim/.pac/paconfig.yaml");
+        let mut manager = TaskManager::new(&ctx, TaskType::Install, 2);
+        let fzf = Package::new("fzf", "https://github.com/junegunn/fzf", "default", false);
+        manager.add(fzf.clone());
+        manager.add(fzf);
+        // a second name for the same remote
+        let mut other = Package::new(
+            "fzf2",
+            "https://github.com/junegunn/fzf.git",
+            "default",
+            true,
+        );
+        other.idname = "me/fzf2".to_string();
+        manager.add(other);
+        manager.add(Package::new(
+            "a",
+            "https://github.com/b/a",
+            "default",
+            false,
+        ));
+
+        let groups: Vec<Vec<&str>> = manager
+            .packs
+            .iter()
+            .map(|g| g.iter().map(|p| p.idname.as_str()).collect())
+            .collect();
+        assert_eq!(groups, vec![vec!["junegunn/fzf", "me/fzf2"], vec!["b/a"]]);
+
+        manager.packs[0][1].priority = 1;
+        manager.packs[1][0].priority = 5;
+        let order: Vec<Vec<String>> = manager
+            .by_priority()
+            .iter()
+            .map(|g| g.iter().map(|p| p.idname.clone()).collect())
+            .collect();
+        assert_eq!(order, vec![vec!["b/a"], vec!["me/fzf2", "junegunn/fzf"]]);
+    }
+
+    #[test]
+    fn task_backoff() {
+        let waits: Vec<u64> = (1..=7).map(|f| backoff(f).as_secs()).collect();
+        assert_eq!(waits, vec![1, 2, 4, 8, 16, 30, 30]);
+    }
+}
@@ -1,18 +1,23 @@
+use crate::auth;
 use crate::echo;
-use crate::Result;
-use std::fs;
-use std::path::Path;
+use crate::policy;
+use crate::{Error, Result};
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::mpsc::{channel, Sender};
 use std::thread;
 use std::time;
-use termion::color;
-use walkdir::WalkDir;
-
-const SPINNER_CHARS: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+use walkdir::{DirEntry, WalkDir, WalkDirIterator};
+use yaml_rust::Yaml;
 
 macro_rules! die {
     ($($arg:tt)*) => ({
         use std::io::Write;
+        // warnings collected so far are not lost to the exit
+        $crate::warnings::flush();
         (writeln!(&mut ::std::io::stderr(), $($arg)*)).expect("stderr");
         ::std::process::exit(1)
     })
@@ -21,42 +26,349 @@ macro_rules! die {
 pub struct Spinner {
     tx: Sender<bool>,
     handle: thread::JoinHandle<()>,
+    start: time::Instant,
 }
 
 impl Spinner {
-    pub fn spin(x: u16, y: u16) -> Spinner {
+    /// Spin in front of task `id`, updating the time elapsed since the
+    /// spinner started.
+    pub fn spin(id: usize) -> Spinner {
         let (tx, rx) = channel();
+        let start = time::Instant::now();
         let handle = thread::spawn(move || {
-            for &c in SPINNER_CHARS.iter().cycle() {
-                if rx.try_recv().is_ok() {
-                    break;
-                }
-
-                echo::character(x, y, c, color::Reset);
+            while rx.try_recv().is_err() {
+                echo::spin(id, start.elapsed());
                 thread::sleep(time::Duration::from_millis(100));
             }
         });
-        Spinner { tx, handle }
+        Spinner { tx, handle, start }
     }
 
-    pub fn stop(self) {
+    /// Stop spinning and return for how long the spinner ran
+    pub fn stop(self) -> time::Duration {
         self.tx.send(true).unwrap();
         self.handle.join().unwrap();
+        self.start.elapsed()
+    }
+}
+
+/// Format a duration into a fixed width column, eg. ` 12.3s`
+pub fn format_duration(d: time::Duration) -> String {
+    format!("{:>5.1}s", d.as_secs_f64())
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ` of seconds since the epoch
+pub fn utc_timestamp(secs: i64) -> String {
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Quote and escape `s` as a JSON string, also valid as a DOT identifier
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Whether an executable `name` is in one of the `$PATH` directories
+pub fn on_path(name: &str) -> bool {
+    find_executable(name).is_some()
+}
+
+/// Where `name` is found in the `$PATH` directories, `name` itself if it is
+/// a path
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        return Some(PathBuf::from(name)).filter(|p| p.is_file());
+    }
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|d| d.join(name))
+        .find(|p| p.is_file())
+}
+
+/// A list of paconfig, `pac.yaml` or the registry, which may also be given
+/// as one comma separated string. Entries are trimmed and empty ones left
+/// out, a missing key is an empty list. `None` for anything but strings.
+pub fn yaml_list(value: &Yaml) -> Option<Vec<String>> {
+    let entries: Vec<&str> = match value {
+        Yaml::Array(v) => v.iter().map(|e| e.as_str()).collect::<Option<_>>()?,
+        Yaml::String(s) => s.split(',').collect(),
+        Yaml::BadValue | Yaml::Null => vec![],
+        _ => return None,
+    };
+    Some(
+        entries
+            .into_iter()
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(String::from)
+            .collect(),
+    )
+}
+
+/// FNV-1a, a hash stable across builds to tell whether generated files changed
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Replace `path` with `contents` through a temporary file in the same
+/// directory, so that readers see the old or the new file but never half
+/// of one
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("pac");
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name, process::id()));
+    let written = File::create(&tmp).and_then(|mut f| {
+        f.write_all(contents)?;
+        f.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Make `link` a link to the directory `target`, as pack/ entries point into
+/// the store. Windows only lets developer mode make symlinks, without it the
+/// link is a junction, which needs no privileges.
+#[cfg(unix)]
+pub fn link_dir<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link: Q) -> Result<()> {
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn link_dir<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link: Q) -> Result<()> {
+    let (target, link) = (target.as_ref(), link.as_ref());
+    if std::os::windows::fs::symlink_dir(target, link).is_ok() {
+        return Ok(());
     }
+    let status = process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(link)
+        .arg(target)
+        .stdout(process::Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(Error::command(format!(
+            "mklink /J {} {}",
+            link.display(),
+            target.display()
+        )));
+    }
+    Ok(())
 }
 
+/// Remove a link made by `link_dir`, leaving what it points to alone
+#[cfg(unix)]
+pub fn remove_link<P: AsRef<Path>>(link: P) -> Result<()> {
+    fs::remove_file(link)?;
+    Ok(())
+}
+
+/// Directory symlinks and junctions are removed as directories on Windows
+#[cfg(windows)]
+pub fn remove_link<P: AsRef<Path>>(link: P) -> Result<()> {
+    fs::remove_dir(link)?;
+    Ok(())
+}
+
+/// Copy the files of a checkout, leaving out the `.git` directory
+pub fn copy_worktree<P: AsRef<Path>>(src: P, dst: P) -> Result<()> {
+    copy_tree(src.as_ref(), dst.as_ref(), |e| e.file_name() != ".git")
+}
+
+/// Copy a directory tree as it is: files keep their executable bits,
+/// directories their permissions and symlinks are recreated instead of
+/// followed, so a dangling one doesn't fail the copy
 pub fn copy_directory<P: AsRef<Path>>(src: P, dst: P) -> Result<()> {
-    let wd = WalkDir::new(&src);
-    for entry in wd {
+    copy_tree(src.as_ref(), dst.as_ref(), |_| true)
+}
+
+fn copy_tree<F>(src: &Path, dst: &Path, keep: F) -> Result<()>
+where
+    F: FnMut(&DirEntry) -> bool,
+{
+    let mut dirs = vec![];
+    for entry in WalkDir::new(src).into_iter().filter_entry(keep) {
         let e = entry?;
         let path = e.path();
-        let stem = path.strip_prefix(&src)?;
-        let new_path = dst.as_ref().join(stem);
-        if path.is_dir() {
-            fs::create_dir_all(new_path)?;
-        } else if path.is_file() {
+        let new_path = dst.join(path.strip_prefix(src)?);
+        let kind = e.file_type();
+        if kind.is_dir() {
+            fs::create_dir_all(&new_path)?;
+            dirs.push((new_path, e.metadata()?.permissions()));
+        } else if kind.is_symlink() {
+            copy_link(path, &new_path)?;
+        } else if kind.is_file() {
+            // the permission bits are copied along
             fs::copy(path, new_path)?;
         }
     }
+    // deepest first, a read-only directory is only locked once it is filled
+    for (dir, permissions) in dirs.into_iter().rev() {
+        fs::set_permissions(dir, permissions)?;
+    }
+    Ok(())
+}
+
+/// Recreate the symlink `src` at `dst`, pointing where it points
+#[cfg(unix)]
+fn copy_link(src: &Path, dst: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(src)?, dst)?;
     Ok(())
 }
+
+/// Windows tells file and directory links apart, and only lets developer
+/// mode make them. Without it what the link points to is copied, a dangling
+/// link is left out.
+#[cfg(windows)]
+fn copy_link(src: &Path, dst: &Path) -> Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+    let target = fs::read_link(src)?;
+    let is_dir = fs::metadata(src).map(|m| m.is_dir());
+    let linked = match is_dir {
+        Ok(true) => symlink_dir(&target, dst),
+        _ => symlink_file(&target, dst),
+    };
+    match (linked, is_dir) {
+        (Ok(()), _) | (Err(_), Err(_)) => Ok(()),
+        (Err(_), Ok(true)) => copy_tree(&fs::canonicalize(src)?, dst, |_| true),
+        (Err(_), Ok(false)) => fs::copy(src, dst).map(|_| ()).map_err(Error::from),
+    }
+}
+
+/// Fetch the body of a http(s) url. Anything else is treated as a path on disk.
+pub fn download(url: &str) -> Result<Vec<u8>> {
+    fetch(url, None)
+}
+
+/// Like `download`, giving up after `timeout`, for lookups nothing waits on
+pub fn download_within(url: &str, timeout: time::Duration) -> Result<Vec<u8>> {
+    fetch(url, Some(timeout))
+}
+
+/// Post a json `body` to a http(s) url, giving up after `timeout`, like an
+/// API query
+pub fn post_within(url: &str, body: &str, timeout: time::Duration) -> Result<Vec<u8>> {
+    policy::check_remote(url)?;
+    let req = request(ureq::post(url), url, Some(timeout)).set("Content-Type", "application/json");
+    let resp = req
+        .send_string(body)
+        .map_err(|e| Error::network(url, e.to_string()))?;
+    let mut body = Vec::new();
+    resp.into_reader()
+        .read_to_end(&mut body)
+        .map_err(|e| Error::network(url, e.to_string()))?;
+    Ok(body)
+}
+
+/// `req` with the timeout and the token of the url's host, if it has one
+fn request(mut req: ureq::Request, url: &str, timeout: Option<time::Duration>) -> ureq::Request {
+    if let Some(t) = timeout {
+        req = req.timeout(t);
+    }
+    let token = Some(url)
+        .filter(|u| u.starts_with("https://"))
+        .and_then(auth::host)
+        .and_then(auth::token);
+    if let Some((token, _)) = token {
+        req = req.set("Authorization", &format!("Bearer {}", token));
+    }
+    req
+}
+
+fn fetch(url: &str, timeout: Option<time::Duration>) -> Result<Vec<u8>> {
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        let path = url.trim_start_matches("file://");
+        return Ok(fs::read(path)?);
+    }
+    policy::check_remote(url)?;
+    let req = request(ureq::get(url), url, timeout);
+    let resp = req.call().map_err(|e| Error::network(url, e.to_string()))?;
+    let mut body = Vec::new();
+    resp.into_reader()
+        .read_to_end(&mut body)
+        .map_err(|e| Error::network(url, e.to_string()))?;
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaml_list_trims_and_drops_empty() {
+        let doc = &yaml_rust::YamlLoader::load_from_str(
+            "for: 'rust, toml,,'\non: [' Files ', '']\nif: 1\nrequires: [a, 1]",
+        )
+        .unwrap()[0];
+        assert_eq!(yaml_list(&doc["for"]).unwrap(), vec!["rust", "toml"]);
+        assert_eq!(yaml_list(&doc["on"]).unwrap(), vec!["Files"]);
+        assert_eq!(yaml_list(&doc["build"]).unwrap(), Vec::<String>::new());
+        assert!(yaml_list(&doc["if"]).is_none() && yaml_list(&doc["requires"]).is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_directory_keeps_modes_and_links() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let root = env::temp_dir().join(format!("pac-copy-{}", process::id()));
+        let (src, dst) = (root.join("src"), root.join("dst"));
+        fs::create_dir_all(src.join("bin")).unwrap();
+        fs::write(src.join("bin/run"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(src.join("bin/run"), fs::Permissions::from_mode(0o755)).unwrap();
+        symlink("bin", src.join("scripts")).unwrap();
+        symlink("missing", src.join("dangling")).unwrap();
+
+        copy_directory(&src, &dst).unwrap();
+        let mode = fs::metadata(dst.join("bin/run"))
+            .unwrap()
+            .permissions()
+            .mode();
+        let scripts = fs::read_link(dst.join("scripts")).unwrap();
+        let dangling = fs::read_link(dst.join("dangling")).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(mode & 0o777, 0o755);
+        assert_eq!(scripts, Path::new("bin"));
+        assert_eq!(dangling, Path::new("missing"));
+    }
+}
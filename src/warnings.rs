@@ -0,0 +1,144 @@
+use crate::utils::json_string;
+
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// What a warning is about, warnings are grouped by it at the end of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    /// A package left out of the run, like one with local changes
+    Skipped,
+    Filetype,
+    Manifest,
+    /// A paconfig key which is no longer read
+    Deprecated,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Skipped => "skipped",
+            Kind::Filetype => "filetype",
+            Kind::Manifest => "manifest",
+            Kind::Deprecated => "deprecated",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Kind::Skipped => "Skipped packages",
+            Kind::Filetype => "Unknown filetypes",
+            Kind::Manifest => "Manifests not applied",
+            Kind::Deprecated => "Deprecated paconfig keys",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub kind: Kind,
+    /// Idname of the package it is about, if any
+    pub package: Option<String>,
+    pub message: String,
+}
+
+lazy_static! {
+    static ref WARNINGS: Mutex<Vec<Warning>> = Mutex::new(vec![]);
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Print the warnings as JSON, `--warnings json`
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Keep a non-fatal warning for the end of the run, where the live progress
+/// area can't overwrite it. The same warning is kept once.
+pub fn push<T: Into<String>>(kind: Kind, package: Option<&str>, message: T) {
+    let warning = Warning {
+        kind,
+        package: package.map(String::from),
+        message: message.into(),
+    };
+    let mut warnings = WARNINGS.lock().unwrap();
+    if !warnings.contains(&warning) {
+        warnings.push(warning);
+    }
+}
+
+/// Print and forget the warnings of the run on stderr, leaving stdout to the
+/// output of the command
+pub fn flush() {
+    let warnings: Vec<Warning> = WARNINGS.lock().unwrap().drain(..).collect();
+    if JSON.load(Ordering::Relaxed) {
+        eprintln!("{}", to_json(&warnings));
+    } else if !warnings.is_empty() {
+        eprint!("{}", to_text(&warnings));
+    }
+}
+
+fn to_text(warnings: &[Warning]) -> String {
+    let mut kinds: Vec<Kind> = warnings.iter().map(|w| w.kind).collect();
+    kinds.sort();
+    kinds.dedup();
+    let mut out = String::from("\nWarnings:\n");
+    for kind in kinds {
+        out += &format!("  {}:\n", kind.title());
+        for w in warnings.iter().filter(|w| w.kind == kind) {
+            match w.package {
+                Some(ref p) => out += &format!("    {}: {}\n", p, w.message),
+                None => out += &format!("    {}\n", w.message),
+            }
+        }
+    }
+    out
+}
+
+fn to_json(warnings: &[Warning]) -> String {
+    let items: Vec<String> = warnings
+        .iter()
+        .map(|w| {
+            format!(
+                "{{\"kind\": {}, \"package\": {}, \"message\": {}}}",
+                json_string(w.kind.name()),
+                w.package.as_deref().map_or("null".to_string(), json_string),
+                json_string(&w.message)
+            )
+        })
+        .collect();
+    format!("{{\"warnings\": [{}]}}", items.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warnings_grouped() {
+        let warning = |kind, package: Option<&str>, message: &str| Warning {
+            kind,
+            package: package.map(String::from),
+            message: message.to_string(),
+        };
+        let warnings = vec![
+            warning(Kind::Filetype, Some("me/a"), "unknown filetype `pyton`"),
+            warning(Kind::Deprecated, None, "`branch` is no longer read"),
+            warning(Kind::Skipped, Some("me/b"), "has local changes"),
+            warning(Kind::Filetype, Some("me/c"), "unknown filetype `gp`"),
+        ];
+        assert_eq!(
+            to_text(&warnings),
+            "\nWarnings:\n  Skipped packages:\n    me/b: has local changes\n  \
+             Unknown filetypes:\n    me/a: unknown filetype `pyton`\n    \
+             me/c: unknown filetype `gp`\n  Deprecated paconfig keys:\n    \
+             `branch` is no longer read\n"
+        );
+        assert_eq!(
+            to_json(&warnings[1..2]),
+            "{\"warnings\": [{\"kind\": \"deprecated\", \"package\": null, \
+             \"message\": \"`branch` is no longer read\"}]}"
+        );
+    }
+}